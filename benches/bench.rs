@@ -1,27 +1,31 @@
 use chrono::NaiveDateTime;
 use criterion::{black_box, criterion_group, criterion_main, Criterion};
-use flate2::read::GzDecoder;
-use std::fs::File;
-use std::io::Read;
-use std::path::Path;
 use std::time::Duration;
-use tf_log_parser::{parse, EventHandler, GameEvent, LineSplit, LogHandler, RawEvent, SubjectMap};
-
-fn gz_read<P: AsRef<Path>>(path: P) -> String {
-    let mut content = String::new();
-    GzDecoder::new(File::open(path).expect("failed to open"))
-        .read_to_string(&mut content)
-        .expect("failed to read");
-    content
-}
+use tf_log_parser::{
+    parse, read_cache, read_log, write_cache, EventHandler, GameEvent, LineSplit, LogHandler,
+    RawEvent, SubjectMap,
+};
 
 pub fn parse_benchmark(c: &mut Criterion) {
-    let input = gz_read("tests/data/log_2892242.log.gz");
+    let input = read_log("tests/data/log_2892242.log.gz").expect("failed to read");
     c.bench_function("parse log 2892242", |b| b.iter(|| parse(black_box(&input))));
 }
 
+pub fn replay_cache(c: &mut Criterion) {
+    let input = read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let mut cache = Vec::new();
+    write_cache(&input, &mut cache).expect("failed to write cache");
+    c.bench_function("replay cache 2892242", |b| {
+        b.iter(|| {
+            read_cache(black_box(&cache))
+                .unwrap()
+                .replay::<LogHandler>()
+        })
+    });
+}
+
 pub fn parse_event(c: &mut Criterion) {
-    let input = gz_read("tests/data/log_2892242.log.gz");
+    let input = read_log("tests/data/log_2892242.log.gz").expect("failed to read");
     let raw: Vec<_> = LineSplit::new(&input).flat_map(RawEvent::parse).collect();
     c.bench_function("parse event 2892242", |b| {
         b.iter(|| {
@@ -31,7 +35,7 @@ pub fn parse_event(c: &mut Criterion) {
 }
 
 pub fn parse_raw(c: &mut Criterion) {
-    let input = gz_read("tests/data/log_2892242.log.gz");
+    let input = read_log("tests/data/log_2892242.log.gz").expect("failed to read");
     c.bench_function("parse raw 2892242", |b| {
         b.iter(|| {
             LineSplit::new(black_box(&input))
@@ -43,7 +47,7 @@ pub fn parse_raw(c: &mut Criterion) {
 }
 
 pub fn handle_event(c: &mut Criterion) {
-    let input = gz_read("tests/data/log_2892242.log.gz");
+    let input = read_log("tests/data/log_2892242.log.gz").expect("failed to read");
     let events: Vec<_> = LineSplit::new(&input)
         .flat_map(RawEvent::parse)
         .map(|raw| (GameEvent::parse(&raw).unwrap(), raw))
@@ -67,5 +71,5 @@ pub fn handle_event(c: &mut Criterion) {
 criterion_group!(
     name = benches;
     config = Criterion::default().measurement_time(Duration::from_secs(10));
-    targets = parse_benchmark, parse_raw, parse_event, handle_event);
+    targets = parse_benchmark, replay_cache, parse_raw, parse_event, handle_event);
 criterion_main!(benches);
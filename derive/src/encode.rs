@@ -0,0 +1,77 @@
+use crate::{err, Derivable, DeriveParams};
+use proc_macro2::{Ident, TokenStream};
+use quote::quote_spanned;
+use syn::{parse_quote, Data, DeriveInput, Fields, Generics, Lifetime, Result};
+
+pub struct Encode;
+
+impl Derivable for Encode {
+    type Params = EncodeParams;
+
+    fn derive(params: EncodeParams) -> Result<TokenStream> {
+        let struct_ident = params.name;
+        let span = struct_ident.span();
+        let (_, ty_generics, where_clause) = params.generics.split_for_impl();
+
+        // decoded events borrow from the cache, structs without borrowed data get a lifetime for the impl
+        let mut generics = params.generics.clone();
+        let lifetime = match params.generics.lifetimes().next() {
+            Some(def) => def.lifetime.clone(),
+            None => {
+                let lifetime = Lifetime::new("'a", span);
+                generics.params.insert(0, parse_quote!(#lifetime));
+                lifetime
+            }
+        };
+        let (impl_generics, _, _) = generics.split_for_impl();
+
+        let encode = params.fields.iter().map(|name| {
+            quote_spanned!(name.span() => crate::cache::Encode::encode(&self.#name, writer);)
+        });
+        let decode = params.fields.iter().map(
+            |name| quote_spanned!(name.span() => #name: crate::cache::Encode::decode(reader)?),
+        );
+
+        Ok(quote_spanned!(span =>
+            impl #impl_generics crate::cache::Encode<#lifetime> for #struct_ident #ty_generics #where_clause {
+                fn encode(&self, writer: &mut crate::cache::CacheWriter<#lifetime>) {
+                    #(#encode)*
+                }
+
+                fn decode(reader: &mut crate::cache::CacheReader<#lifetime>) -> Option<Self> {
+                    Some(#struct_ident {
+                        #(#decode),*
+                    })
+                }
+            }
+        ))
+    }
+}
+
+pub struct EncodeParams {
+    name: Ident,
+    generics: Generics,
+    fields: Vec<Ident>,
+}
+
+impl DeriveParams for EncodeParams {
+    fn parse(input: &DeriveInput) -> Result<EncodeParams> {
+        let Data::Struct(data) = &input.data else {
+            return err("only supported on structs", input);
+        };
+        let Fields::Named(fields) = &data.fields else {
+            return err("only supported with named fields", input);
+        };
+        let fields = fields
+            .named
+            .iter()
+            .map(|field| field.ident.clone().expect("no name on named fields"))
+            .collect();
+
+        Ok(EncodeParams {
+            name: input.ident.clone(),
+            generics: input.generics.clone(),
+            fields,
+        })
+    }
+}
@@ -1,7 +1,7 @@
 use crate::{err, Derivable, DeriveParams};
 use proc_macro2::{Ident, TokenStream};
-use quote::quote_spanned;
-use syn::{Data, DeriveInput, Generics, Result};
+use quote::{format_ident, quote_spanned};
+use syn::{Data, DeriveInput, GenericArgument, Generics, PathArguments, Result, Type, TypePath};
 
 pub struct Events;
 
@@ -12,15 +12,63 @@ impl Derivable for Events {
         let enum_ident = params.name;
         let span = enum_ident.span();
 
-        let variants = params.variants.iter().map(|(variant_name, empty)| {
+        let variants = params.variants.iter().map(|(variant_name, ty)| {
             let span = variant_name.span();
-            if *empty {
+            if ty.is_none() {
                 quote_spanned!(span => RawEventType::#variant_name => Self::#variant_name,)
             } else {
                 quote_spanned!(span => RawEventType::#variant_name => Self::#variant_name(parse_event(raw.params).with_raw(raw)?),)
             }
         });
 
+        let variant_names = params.variants.iter().map(|(variant_name, _)| variant_name);
+        let variant_types = params.variants.iter().map(|(variant_name, ty)| {
+            let span = variant_name.span();
+            if ty.is_some() {
+                quote_spanned!(span => Self::#variant_name(_) => RawEventType::#variant_name,)
+            } else {
+                quote_spanned!(span => Self::#variant_name => RawEventType::#variant_name,)
+            }
+        });
+
+        let encoders = params.variants.iter().map(|(variant_name, ty)| {
+            let span = variant_name.span();
+            if ty.is_some() {
+                quote_spanned!(span => Self::#variant_name(event) => crate::cache::Encode::encode(event, writer),)
+            } else {
+                quote_spanned!(span => Self::#variant_name => {})
+            }
+        });
+        let decoders = params.variants.iter().map(|(variant_name, ty)| {
+            let span = variant_name.span();
+            if ty.is_some() {
+                quote_spanned!(span => RawEventType::#variant_name => Self::#variant_name(crate::cache::Encode::decode(reader)?),)
+            } else {
+                quote_spanned!(span => RawEventType::#variant_name => Self::#variant_name,)
+            }
+        });
+
+        let owned_ident = format_ident!("Owned{}", enum_ident);
+        let owned_doc = format!("Owned version of [`{}`]", enum_ident);
+        let owned_variants = params.variants.iter().map(|(variant_name, ty)| {
+            let span = variant_name.span();
+            match ty {
+                Some(ty) => {
+                    let ty = owned_event_type(ty);
+                    quote_spanned!(span => #variant_name(#ty))
+                }
+                None => quote_spanned!(span => #variant_name),
+            }
+        });
+        let conversions = params.variants.iter().map(|(variant_name, ty)| {
+            let span = variant_name.span();
+            if ty.is_some() {
+                quote_spanned!(span => Self::#variant_name(event) => #owned_ident::#variant_name(crate::event::IntoOwned::into_owned(event)?),)
+            } else {
+                quote_spanned!(span => Self::#variant_name => #owned_ident::#variant_name,)
+            }
+        });
+
         let (impl_generics, ty_generics, where_clause) = params.generics.split_for_impl();
 
         Ok(
@@ -33,15 +81,84 @@ impl Derivable for Events {
                         }
                     })
                 }
+
+                /// Whether events of this type can be parsed
+                pub fn is_supported(ty: RawEventType) -> bool {
+                    matches!(ty, #(RawEventType::#variant_names)|*)
+                }
+
+                /// The type of the event
+                pub fn ty(&self) -> RawEventType {
+                    match self {
+                        #(#variant_types)*
+                    }
+                }
+
+                /// Encode the fields of the event into the event cache
+                pub(crate) fn encode(&self, writer: &mut crate::cache::CacheWriter<'a>) {
+                    match self {
+                        #(#encoders)*
+                    }
+                }
+
+                /// Decode the fields of an event of type `ty` from the event cache
+                pub(crate) fn decode(ty: RawEventType, reader: &mut crate::cache::CacheReader<'a>) -> Option<Self> {
+                    Some(match ty {
+                        #(#decoders)*
+                        _ => return None,
+                    })
+                }
+
+                /// Convert the event into a version that doesn't borrow from the log
+                pub fn into_owned(self) -> std::result::Result<#owned_ident, crate::SubjectError> {
+                    Ok(match self {
+                        #(#conversions)*
+                    })
+                }
+            }
+
+            #[doc = #owned_doc]
+            #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+            #[serde(tag = "type")]
+            pub enum #owned_ident {
+                #(#owned_variants),*
+            }
+
+            impl #impl_generics crate::event::IntoOwned for #enum_ident #ty_generics #where_clause {
+                type Owned = #owned_ident;
+
+                fn into_owned(self) -> std::result::Result<#owned_ident, crate::SubjectError> {
+                    #enum_ident::into_owned(self)
+                }
             }),
         )
     }
 }
 
+/// The owned version of event structs that borrow from the log is prefixed with `Owned`
+fn owned_event_type(ty: &Type) -> Type {
+    let Type::Path(TypePath { qself: None, path }) = ty else {
+        return ty.clone();
+    };
+    let mut path = path.clone();
+    let last = path.segments.last_mut().expect("empty type path");
+    if let PathArguments::AngleBracketed(arguments) = &last.arguments {
+        if arguments
+            .args
+            .iter()
+            .any(|arg| matches!(arg, GenericArgument::Lifetime(_)))
+        {
+            last.ident = format_ident!("Owned{}", last.ident);
+            last.arguments = PathArguments::None;
+        }
+    }
+    Type::Path(TypePath { qself: None, path })
+}
+
 pub struct EventsParam {
     name: Ident,
     generics: Generics,
-    variants: Vec<(Ident, bool)>,
+    variants: Vec<(Ident, Option<Type>)>,
 }
 
 impl DeriveParams for EventsParam {
@@ -55,7 +172,12 @@ impl DeriveParams for EventsParam {
         let variants = data
             .variants
             .iter()
-            .map(|variant| (variant.ident.clone(), variant.fields.is_empty()))
+            .map(|variant| {
+                (
+                    variant.ident.clone(),
+                    variant.fields.iter().next().map(|field| field.ty.clone()),
+                )
+            })
             .collect();
 
         Ok(EventsParam {
@@ -2,11 +2,15 @@
 
 extern crate proc_macro;
 
+mod encode;
 mod event;
 mod events;
+mod owned;
 
+use crate::encode::Encode;
 use crate::event::Event;
 use crate::events::Events;
+use crate::owned::IntoOwned;
 use proc_macro2::TokenStream;
 use quote::ToTokens;
 use std::fmt::Display;
@@ -28,6 +32,22 @@ pub fn derive_events(input: proc_macro::TokenStream) -> proc_macro::TokenStream
     proc_macro::TokenStream::from(expanded)
 }
 
+/// Derive the `IntoOwned` trait for a struct, generating an owned version of structs that borrow from the log
+#[proc_macro_derive(IntoOwned)]
+pub fn derive_into_owned(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let expanded = derive_trait::<IntoOwned>(parse_macro_input!(input as DeriveInput));
+
+    proc_macro::TokenStream::from(expanded)
+}
+
+/// Derive the encoding of an event struct in the binary event cache, fields are encoded in order
+#[proc_macro_derive(Encode)]
+pub fn derive_encode(input: proc_macro::TokenStream) -> proc_macro::TokenStream {
+    let expanded = derive_trait::<Encode>(parse_macro_input!(input as DeriveInput));
+
+    proc_macro::TokenStream::from(expanded)
+}
+
 /// Basic wrapper for error handling
 fn derive_trait<Trait: Derivable>(input: DeriveInput) -> TokenStream {
     derive_trait_inner::<Trait>(input).unwrap_or_else(|err| err.into_compile_error())
@@ -0,0 +1,137 @@
+use crate::{err, Derivable, DeriveParams};
+use proc_macro2::{Ident, TokenStream};
+use quote::{format_ident, quote_spanned};
+use syn::{
+    Data, DeriveInput, Fields, GenericArgument, Generics, PathArguments, Result, Type, TypePath,
+    TypeTuple,
+};
+
+pub struct IntoOwned;
+
+impl Derivable for IntoOwned {
+    type Params = IntoOwnedParams;
+
+    fn derive(params: IntoOwnedParams) -> Result<TokenStream> {
+        let struct_ident = params.name;
+        let span = struct_ident.span();
+        let (impl_generics, ty_generics, where_clause) = params.generics.split_for_impl();
+
+        // structs without borrowed data are already owned
+        if params.generics.lifetimes().next().is_none() {
+            return Ok(
+                quote_spanned!(span => impl #impl_generics crate::event::IntoOwned for #struct_ident #ty_generics #where_clause {
+                    type Owned = Self;
+
+                    fn into_owned(self) -> std::result::Result<Self, crate::SubjectError> {
+                        Ok(self)
+                    }
+                }),
+            );
+        }
+
+        let owned_ident = format_ident!("Owned{}", struct_ident);
+        let doc = format!("Owned version of [`{}`]", struct_ident);
+        let fields = params.fields.iter().map(|(name, ty)| {
+            let ty = owned_type(ty);
+            quote_spanned!(name.span() => pub #name: #ty)
+        });
+        let conversions = params.fields.iter().map(|(name, _)| {
+            quote_spanned!(name.span() => #name: crate::event::IntoOwned::into_owned(self.#name)?)
+        });
+
+        Ok(quote_spanned!(span =>
+            #[doc = #doc]
+            #[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+            pub struct #owned_ident {
+                #(#fields),*
+            }
+
+            impl #impl_generics crate::event::IntoOwned for #struct_ident #ty_generics #where_clause {
+                type Owned = #owned_ident;
+
+                fn into_owned(self) -> std::result::Result<#owned_ident, crate::SubjectError> {
+                    Ok(#owned_ident {
+                        #(#conversions),*
+                    })
+                }
+            }
+        ))
+    }
+}
+
+/// Get the owned equivalent of a field type
+///
+/// Borrowed strings become `String` and subjects are resolved into a `SubjectId`
+pub fn owned_type(ty: &Type) -> Type {
+    match ty {
+        Type::Reference(reference) => {
+            let mut elem = owned_type(&reference.elem);
+            if let Type::Path(TypePath { path, .. }) = &elem {
+                if path.is_ident("str") {
+                    elem = syn::parse_quote!(String);
+                }
+            }
+            elem
+        }
+        Type::Path(TypePath { qself: None, path }) => {
+            let mut path = path.clone();
+            let last = path.segments.last_mut().expect("empty type path");
+            if last.ident == "RawSubject" {
+                return syn::parse_quote!(crate::SubjectId);
+            }
+            if let PathArguments::AngleBracketed(arguments) = &mut last.arguments {
+                arguments.args = arguments
+                    .args
+                    .iter()
+                    .filter_map(|arg| match arg {
+                        GenericArgument::Lifetime(_) => None,
+                        GenericArgument::Type(ty) => Some(GenericArgument::Type(owned_type(ty))),
+                        arg => Some(arg.clone()),
+                    })
+                    .collect();
+                if arguments.args.is_empty() {
+                    last.arguments = PathArguments::None;
+                }
+            }
+            Type::Path(TypePath { qself: None, path })
+        }
+        Type::Tuple(tuple) => Type::Tuple(TypeTuple {
+            paren_token: tuple.paren_token,
+            elems: tuple.elems.iter().map(owned_type).collect(),
+        }),
+        ty => ty.clone(),
+    }
+}
+
+pub struct IntoOwnedParams {
+    name: Ident,
+    generics: Generics,
+    fields: Vec<(Ident, Type)>,
+}
+
+impl DeriveParams for IntoOwnedParams {
+    fn parse(input: &DeriveInput) -> Result<IntoOwnedParams> {
+        let Data::Struct(data) = &input.data else {
+            return err("only supported on structs", input);
+        };
+        let Fields::Named(fields) = &data.fields else {
+            return err("only supported with named fields", input);
+        };
+        let fields = fields
+            .named
+            .iter()
+            .map(|field| {
+                (
+                    field.ident.clone().expect("no name on named fields"),
+                    field.ty.clone(),
+                )
+            })
+            .collect();
+
+        Ok(IntoOwnedParams {
+            name: input.ident.clone(),
+            generics: input.generics.clone(),
+            fields,
+        })
+    }
+}
@@ -1,22 +1,14 @@
-use indicatif::{ParallelProgressIterator, ProgressBar, ProgressStyle};
+use indicatif::{ProgressBar, ProgressStyle};
 use main_error::MainError;
-use rayon::prelude::*;
 use std::env::args;
-use std::ffi::OsStr;
-use std::fs;
 use std::hint::black_box;
-use std::mem::forget;
-use std::sync::atomic::{AtomicUsize, Ordering};
-use std::time::{Duration, Instant};
-use tf_log_parser::parse;
-use walkdir::WalkDir;
+use std::time::Instant;
+use tf_log_parser::batch::BatchParser;
+use tf_log_parser::LogHandler;
 
 fn main() -> Result<(), MainError> {
     let path = args().nth(1).expect("No path provided");
 
-    let parse_time = AtomicUsize::default();
-    let read_time = AtomicUsize::default();
-    let count = AtomicUsize::default();
     let start = Instant::now();
 
     let style = ProgressStyle::with_template(
@@ -24,102 +16,34 @@ fn main() -> Result<(), MainError> {
     )
     .unwrap();
 
-    let pb = ProgressBar::new_spinner();
-    pb.enable_steady_tick(Duration::from_millis(120));
-    pb.set_style(
-        ProgressStyle::with_template("[{elapsed_precise}] {spinner:.blue} {msg}")
-            .unwrap()
-            .tick_strings(&[
-                "▹▹▹▹▹",
-                "▸▹▹▹▹",
-                "▹▸▹▹▹",
-                "▹▹▸▹▹",
-                "▹▹▹▸▹",
-                "▹▹▹▹▸",
-                "▪▪▪▪▪",
-            ]),
-    );
-    pb.set_message("Collecting paths...");
-
-    let paths: Vec<_> = WalkDir::new(path)
-        .into_iter()
-        .flatten()
-        .filter(|entry| entry.path().extension() == Some(OsStr::new("log")))
-        .collect();
-
-    pb.finish_with_message("Done");
-
-    let bar = ProgressBar::new(paths.len() as u64);
+    let bar = ProgressBar::new(0);
     bar.set_style(style);
-
-    paths.into_par_iter().progress_with(bar).for_each(|entry| {
-        let path = entry.path();
-        if path.extension() == Some(OsStr::new("log")) {
-            // let _ = print!("{} - ", path.display());
-            let read_start = Instant::now();
-            let input = match fs::read(path) {
-                Ok(input) => input,
-                Err(_e) => {
-                    // println!("failed to read file: {}", e);
-                    return;
-                }
-            };
-            let input = String::from_utf8_lossy(&input);
-
-            read_time.fetch_add(read_start.elapsed().as_micros() as usize, Ordering::Relaxed);
-            let parse_start = Instant::now();
-            on_panic(
-                || {
-                    let (output, _) = match parse(&input) {
-                        Ok(val) => val,
-                        Err(e) => {
-                            eprintln!("failed to parse {}: {}", path.display(), e);
-                            return;
-                        }
-                    };
-                    black_box(output);
-                },
-                || eprintln!("Paniced on {}", path.display()),
-            );
-
-            parse_time.fetch_add(
-                parse_start.elapsed().as_micros() as usize,
-                Ordering::Relaxed,
-            );
-            count.fetch_add(1, Ordering::Relaxed);
+    let progress = bar.clone();
+
+    let results = BatchParser::new()
+        .progress(move |progress_update| {
+            progress.set_length(progress_update.total as u64);
+            progress.inc(1);
+        })
+        .parse_dir::<LogHandler>(path);
+
+    let mut count = 0;
+    for (path, result) in results {
+        match result {
+            Ok(output) => {
+                black_box(output);
+            }
+            Err(e) => bar.println(format!("failed to parse {}: {}", path.display(), e)),
         }
-    });
-
-    let total = start.elapsed();
-
-    let read_time = read_time.load(Ordering::Relaxed);
-    let parse_time = parse_time.load(Ordering::Relaxed);
+        count += 1;
+    }
+    bar.finish();
 
     println!(
-        "Parsed {} in {:01}s, spend {:01}s reading files, in {:01}s of real time",
-        count.load(Ordering::Relaxed),
-        micros_as_sec(read_time),
-        micros_as_sec(parse_time),
-        micros_as_sec(total.as_micros() as usize)
+        "Parsed {} in {:01}s of real time",
+        count,
+        start.elapsed().as_secs_f32()
     );
 
     Ok(())
 }
-
-fn micros_as_sec(micros: usize) -> f32 {
-    micros as f32 / 1_000_000.0
-}
-
-struct PanicCanary<F: Fn() -> ()>(F);
-
-impl<F: Fn() -> ()> Drop for PanicCanary<F> {
-    fn drop(&mut self) {
-        (self.0)()
-    }
-}
-
-fn on_panic<F: Fn() -> (), P: Fn() -> ()>(f: F, panic: P) {
-    let canary = PanicCanary(panic);
-    f();
-    forget(canary)
-}
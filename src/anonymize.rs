@@ -0,0 +1,395 @@
+//! Remove identifying information from logs
+
+use crate::event::{ParamIter, SayEvent, SayTeamEvent};
+use crate::raw_event::{split_player_subject, RawSubject};
+use crate::{GameEvent, LineSplit, LineSplitBytes, RawEvent, RawEventType};
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::ops::Range;
+use steamid_ng::{AccountType, Instance, SteamID, Universe};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum AnonymizeError {
+    #[error("Malformed mapping file: {0}")]
+    MalformedMapping(#[from] serde_json::Error),
+    #[error("Invalid steam id in mapping: {0}")]
+    InvalidSteamId(String),
+}
+
+/// The identity a player is replaced with
+#[derive(Debug, Clone, PartialEq)]
+pub struct Identity {
+    pub name: String,
+    pub steam_id: SteamID,
+}
+
+/// Rewrites player names and steam ids in a log
+///
+/// Players are replaced by the identity from the mapping if they have one,
+/// otherwise the account id is encrypted with a key derived from the salt, so no two players get the same identity.
+/// The same player is always replaced by the same identity, across logs.
+///
+/// Anyone who knows the salt can recover the original steam ids, so the salt should be random and kept secret.
+#[derive(Debug, Default, Clone)]
+pub struct Anonymizer {
+    salt: String,
+    mapping: HashMap<u32, Identity>,
+    scrub_chat: bool,
+}
+
+impl Anonymizer {
+    pub fn new(salt: impl Into<String>) -> Self {
+        Anonymizer {
+            salt: salt.into(),
+            ..Anonymizer::default()
+        }
+    }
+
+    /// Use a fixed identity for the players in the mapping
+    pub fn with_mapping(mut self, mapping: impl IntoIterator<Item = (SteamID, Identity)>) -> Self {
+        self.mapping.extend(
+            mapping
+                .into_iter()
+                .map(|(steam_id, identity)| (steam_id.account_id(), identity)),
+        );
+        self
+    }
+
+    /// Load the identity mapping from json, mapping steam ids to a name and steam id
+    ///
+    /// `{"[U:1:64229260]": {"name": "player 1", "steam_id": "[U:1:1]"}}`
+    pub fn with_mapping_json(self, json: &str) -> Result<Self, AnonymizeError> {
+        #[derive(Deserialize)]
+        struct RawIdentity {
+            name: String,
+            steam_id: String,
+        }
+
+        fn parse_steam_id(steam_id: &str) -> Result<SteamID, AnonymizeError> {
+            SteamID::from_steam3(steam_id)
+                .or_else(|_| SteamID::from_steam2(steam_id))
+                .map_err(|_| AnonymizeError::InvalidSteamId(steam_id.into()))
+        }
+
+        let raw: HashMap<String, RawIdentity> = serde_json::from_str(json)?;
+        let mapping = raw
+            .into_iter()
+            .map(|(steam_id, identity)| {
+                Ok((
+                    parse_steam_id(&steam_id)?,
+                    Identity {
+                        name: identity.name,
+                        steam_id: parse_steam_id(&identity.steam_id)?,
+                    },
+                ))
+            })
+            .collect::<Result<Vec<_>, AnonymizeError>>()?;
+        Ok(self.with_mapping(mapping))
+    }
+
+    /// Replace the content of all chat messages from players
+    pub fn scrub_chat(mut self, scrub_chat: bool) -> Self {
+        self.scrub_chat = scrub_chat;
+        self
+    }
+
+    /// Get the identity that replaces a player
+    pub fn identity(&self, steam_id: SteamID) -> Identity {
+        if let Some(identity) = self.mapping.get(&steam_id.account_id()) {
+            return identity.clone();
+        }
+
+        // 0 isn't a valid account id, walk the cycle of the permutation until we get a valid one
+        let mut account_id = self.permute(steam_id.account_id());
+        while account_id == 0 {
+            account_id = self.permute(account_id);
+        }
+        Identity {
+            name: format!("player-{:08x}", account_id),
+            steam_id: SteamID::new(
+                account_id,
+                Instance::Desktop,
+                AccountType::Individual,
+                Universe::Public,
+            ),
+        }
+    }
+
+    /// Keyed permutation of account ids, as a feistel network with the salted hash as round function
+    fn permute(&self, account_id: u32) -> u32 {
+        let (mut left, mut right) = ((account_id >> 16) as u16, account_id as u16);
+        for round in 0..4u8 {
+            let mut hasher = Sha256::new();
+            hasher.update(self.salt.as_bytes());
+            hasher.update([round]);
+            hasher.update(right.to_le_bytes());
+            let hash = hasher.finalize();
+            let mixed = left ^ u16::from_le_bytes([hash[0], hash[1]]);
+            left = right;
+            right = mixed;
+        }
+        (left as u32) << 16 | right as u32
+    }
+
+    /// Anonymize a full log
+    pub fn anonymize(&self, log: &str) -> String {
+        let mut output = String::with_capacity(log.len());
+        let mut last = 0;
+        for line in LineSplit::new(log) {
+            let start = line.as_ptr() as usize - log.as_ptr() as usize;
+            output.push_str(&log[last..start]);
+            output.push_str(&self.anonymize_line(line));
+            last = start + line.len();
+        }
+        output.push_str(&log[last..]);
+        output
+    }
+
+    /// Anonymize a log that can contain invalid utf8
+    ///
+    /// Everything outside of the anonymized lines is copied unchanged.
+    pub fn anonymize_bytes(&self, log: &[u8]) -> Vec<u8> {
+        let mut output = Vec::with_capacity(log.len());
+        let mut last = 0;
+        for line in LineSplitBytes::new(log) {
+            let range = line.range();
+            output.extend_from_slice(&log[last..range.start]);
+            output.extend_from_slice(self.anonymize_line(line.as_str()).as_bytes());
+            last = range.end;
+        }
+        output.extend_from_slice(&log[last..]);
+        output
+    }
+
+    /// Anonymize a single log line, without the leading `L `
+    pub fn anonymize_line(&self, line: &str) -> String {
+        let raw = match RawEvent::parse(line) {
+            Ok(raw) => raw,
+            Err(_) => return line.to_string(),
+        };
+        let mut replacements = LineReplacements::new(line);
+
+        let subject_name = match &raw.subject {
+            RawSubject::Player(subject) => replacements.subject(self, subject),
+            _ => None,
+        };
+
+        if raw.ty == RawEventType::Killed {
+            if let Ok(GameEvent::Killed(kill)) = GameEvent::parse(&raw) {
+                if let RawSubject::Player(target) = kill.target {
+                    replacements.subject(self, target);
+                }
+            }
+        } else {
+            for (key, value) in ParamIter::new(raw.params) {
+                match key {
+                    "against" | "objectowner" => {
+                        replacements.subject(self, value);
+                    }
+                    key if key.starts_with("player") => {
+                        replacements.subject(self, value);
+                    }
+                    "address" if raw.ty == RawEventType::Connected => {
+                        replacements.replace(value, "0.0.0.0:0".into());
+                    }
+                    "reason" if raw.ty == RawEventType::Disconnect => {
+                        if let Some((name, anonymized)) = &subject_name {
+                            replacements.replace(value, value.replace(name, anonymized));
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        match (&raw.subject, GameEvent::parse(&raw)) {
+            (RawSubject::Console, Ok(GameEvent::Say(SayEvent { text }))) => {
+                if let Some(leader) = text.strip_prefix("Leader: ") {
+                    replacements.leader(self, leader);
+                }
+            }
+            (
+                RawSubject::Player(_),
+                Ok(GameEvent::Say(SayEvent { text }) | GameEvent::SayTeam(SayTeamEvent { text })),
+            ) if self.scrub_chat => {
+                replacements.replace(text, String::new());
+            }
+            _ => {}
+        }
+
+        replacements.apply()
+    }
+}
+
+/// Replacements of parts of a single line
+struct LineReplacements<'a> {
+    line: &'a str,
+    replacements: Vec<(Range<usize>, String)>,
+}
+
+impl<'a> LineReplacements<'a> {
+    fn new(line: &'a str) -> Self {
+        LineReplacements {
+            line,
+            replacements: Vec::new(),
+        }
+    }
+
+    /// Replace a slice of the line
+    fn replace(&mut self, part: &str, replacement: String) {
+        let start = part.as_ptr() as usize - self.line.as_ptr() as usize;
+        self.replacements
+            .push((start..start + part.len(), replacement));
+    }
+
+    /// Replace the name and steam id of a player subject, returning the original and replaced name
+    fn subject(&mut self, anonymizer: &Anonymizer, subject: &str) -> Option<(String, String)> {
+        let (name, _, steam_id, _) = split_player_subject(subject).ok()?;
+        let parsed = SteamID::from_steam3(steam_id)
+            .or_else(|_| SteamID::from_steam2(steam_id))
+            .ok()?;
+        let identity = anonymizer.identity(parsed);
+        self.replace(name, identity.name.clone());
+        self.replace(steam_id, identity.steam_id.steam3());
+        Some((name.to_string(), identity.name))
+    }
+
+    /// Replace the lobby leader in "NAME (STEAMID64)"
+    fn leader(&mut self, anonymizer: &Anonymizer, leader: &str) {
+        let Some((name, steam_id)) = leader.rsplit_once(" (") else {
+            return;
+        };
+        let steam_id = steam_id.trim_end_matches(')');
+        let Ok(parsed) = steam_id.parse::<u64>() else {
+            return;
+        };
+        let identity = anonymizer.identity(SteamID::from(parsed));
+        self.replace(name, identity.name);
+        self.replace(steam_id, u64::from(identity.steam_id).to_string());
+    }
+
+    fn apply(mut self) -> String {
+        self.replacements.sort_by_key(|(range, _)| range.start);
+        let mut output = String::with_capacity(self.line.len());
+        let mut last = 0;
+        for (range, replacement) in self.replacements {
+            // overlapping replacements can happen for malformed lines, keep the first one
+            if range.start < last {
+                continue;
+            }
+            output.push_str(&self.line[last..range.start]);
+            output.push_str(&replacement);
+            last = range.end;
+        }
+        output.push_str(&self.line[last..]);
+        output
+    }
+}
+
+#[test]
+fn test_anonymize_line() {
+    let anonymizer = Anonymizer::new("salt").with_mapping(vec![(
+        SteamID::from_steam3("[U:1:40364391]").unwrap(),
+        Identity {
+            name: "player \"1\"".into(),
+            steam_id: SteamID::from_steam3("[U:1:1]").unwrap(),
+        },
+    )]);
+    let other = anonymizer.identity(SteamID::from_steam3("[U:1:114365027]").unwrap());
+    assert_eq!(
+        format!(
+            r#"08/06/2018 - 21:13:57: "player "1"<27><[U:1:1]><Red>" killed "{}<37><{}><Blue>" with "smg" (attacker_position "-2437 -864 130")"#,
+            other.name,
+            other.steam_id.steam3()
+        ),
+        anonymizer.anonymize_line(r#"08/06/2018 - 21:13:57: "makxbi<27><[U:1:40364391]><Red>" killed "=(eGO)= "man" <skirt><37><[U:1:114365027]><Blue>" with "smg" (attacker_position "-2437 -864 130")"#)
+    );
+    assert_eq!(
+        r#"08/06/2018 - 21:13:57: "player "1"<27><[U:1:1]><>" connected, address "0.0.0.0:0""#,
+        anonymizer.anonymize_line(
+            r#"08/06/2018 - 21:13:57: "makxbi<27><[U:1:40364391]><>" connected, address "1.2.3.4:27005""#
+        )
+    );
+    let leader = anonymizer.identity(SteamID::from(76561198055036243));
+    assert_eq!(
+        format!(
+            r#"04/11/2021 - 23:33:27: "Console<0><Console><Console>" say "Leader: {} ({})""#,
+            leader.name,
+            u64::from(leader.steam_id)
+        ),
+        anonymizer.anonymize_line(r#"04/11/2021 - 23:33:27: "Console<0><Console><Console>" say "Leader: b4rney (76561198055036243)""#)
+    );
+    assert_eq!(
+        r#"08/06/2018 - 21:13:57: "player "1"<27><[U:1:1]><Red>" say """#,
+        anonymizer
+            .clone()
+            .scrub_chat(true)
+            .anonymize_line(r#"08/06/2018 - 21:13:57: "makxbi<27><[U:1:40364391]><Red>" say "gg""#)
+    );
+}
+
+#[test]
+fn test_identity_unique() {
+    use std::collections::HashSet;
+
+    let anonymizer = Anonymizer::new("salt");
+    let identities: HashSet<_> = (1..=100_000)
+        .map(|account_id| {
+            let steam_id = SteamID::new(
+                account_id,
+                Instance::Desktop,
+                AccountType::Individual,
+                Universe::Public,
+            );
+            anonymizer.identity(steam_id).steam_id.account_id()
+        })
+        .collect();
+    assert_eq!(100_000, identities.len());
+    assert!(!identities.contains(&0));
+
+    let steam_id = SteamID::from_steam3("[U:1:40364391]").unwrap();
+    assert_ne!(
+        anonymizer.identity(steam_id),
+        Anonymizer::new("other").identity(steam_id)
+    );
+}
+
+#[test]
+fn test_anonymize_log() {
+    use crate::{parse, SteamId3};
+
+    let input = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+
+    let anonymizer = Anonymizer::new("salt").scrub_chat(true);
+    let anonymized = anonymizer.anonymize(&input);
+    assert_eq!(anonymized, anonymizer.anonymize(&input));
+
+    let (_, original) = parse(&input).unwrap();
+    let (_, players) = parse(&anonymized).unwrap();
+    assert_eq!(original.len(), players.len());
+    for (steam_id, stats) in original {
+        assert!(!anonymized.contains(&steam_id.0.steam3()));
+        let anonymized_id = SteamId3(anonymizer.identity(steam_id.0).steam_id);
+        let mut expected = serde_json::to_value(&stats).unwrap();
+        // heal spread is keyed by the healed player
+        if let Some(heal_spread) = expected["heal_spread"].as_object() {
+            let heal_spread = heal_spread
+                .iter()
+                .map(|(target, healed)| {
+                    let target = SteamID::from_steam3(target).unwrap();
+                    (
+                        anonymizer.identity(target).steam_id.steam3(),
+                        healed.clone(),
+                    )
+                })
+                .collect::<serde_json::Map<_, _>>();
+            expected["heal_spread"] = heal_spread.into();
+        }
+        assert_eq!(
+            expected,
+            serde_json::to_value(&players[&anonymized_id]).unwrap()
+        );
+    }
+}
@@ -0,0 +1,421 @@
+//! Export events and per-player statistics as arrow record batches and parquet files
+//!
+//! Events are appended straight from the borrowed [`GameEvent`]s, strings like weapons and event types
+//! are dictionary encoded so they are only stored once per batch.
+
+use crate::raw_event::RawSubject;
+use crate::{
+    Error, GameEvent, LineSplit, LogHandlerPerSubjectOutput, RawEvent, SteamId3, SubjectId,
+};
+use arrow_array::builder::{
+    ArrayBuilder, Int32Builder, Int64Builder, StringDictionaryBuilder, TimestampSecondBuilder,
+    UInt32Builder,
+};
+use arrow_array::types::{Int16Type, Int32Type};
+use arrow_array::{ArrayRef, RecordBatch};
+use arrow_schema::{DataType, Field, Schema, SchemaRef, TimeUnit};
+use chrono::NaiveDateTime;
+use parquet::arrow::ArrowWriter;
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::fmt::Write as _;
+use std::io::Write;
+use std::sync::Arc;
+
+/// Number of rows that are buffered before a batch is written to a parquet file
+const DEFAULT_BATCH_SIZE: usize = 64 * 1024;
+
+/// Builds record batches from logs, one row at a time
+pub trait LogBatchBuilder: Default {
+    /// The parsed or unparsed log the rows are created from
+    type Input: ?Sized;
+
+    fn schema() -> SchemaRef;
+
+    /// Add the rows for a log, `name` is used for the `log` column and can be the log id or the filename
+    fn push_log(&mut self, name: &str, log: &Self::Input);
+
+    /// Number of rows added since the last batch was finished
+    fn len(&self) -> usize;
+
+    fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Create a batch from the added rows and reset the builder
+    fn finish(&mut self) -> Result<RecordBatch, Error>;
+}
+
+fn dictionary(key: DataType) -> DataType {
+    DataType::Dictionary(Box::new(key), Box::new(DataType::Utf8))
+}
+
+/// Builder for record batches with a row for every event in a log
+///
+/// Events that fail to parse are skipped.
+pub struct EventBatchBuilder {
+    log: StringDictionaryBuilder<Int32Type>,
+    time: TimestampSecondBuilder,
+    ty: StringDictionaryBuilder<Int16Type>,
+    subject: UInt32Builder,
+    target: UInt32Builder,
+    weapon: StringDictionaryBuilder<Int32Type>,
+    damage: Int64Builder,
+    healing: UInt32Builder,
+    attacker_position: [Int32Builder; 3],
+    victim_position: [Int32Builder; 3],
+    /// Reused buffer for formatting the event type
+    type_name: String,
+}
+
+impl Default for EventBatchBuilder {
+    fn default() -> Self {
+        EventBatchBuilder {
+            log: StringDictionaryBuilder::new(),
+            time: TimestampSecondBuilder::new(),
+            ty: StringDictionaryBuilder::new(),
+            subject: UInt32Builder::new(),
+            target: UInt32Builder::new(),
+            weapon: StringDictionaryBuilder::new(),
+            damage: Int64Builder::new(),
+            healing: UInt32Builder::new(),
+            attacker_position: Default::default(),
+            victim_position: Default::default(),
+            type_name: String::new(),
+        }
+    }
+}
+
+/// Account id of a player subject
+fn account_id(subject: &RawSubject) -> Option<u32> {
+    match subject.id() {
+        Ok(SubjectId::Player(account_id)) => Some(account_id),
+        _ => None,
+    }
+}
+
+fn append_position(builders: &mut [Int32Builder; 3], position: Option<(i32, i32, i32)>) {
+    let (x, y, z) = match position {
+        Some((x, y, z)) => (Some(x), Some(y), Some(z)),
+        None => (None, None, None),
+    };
+    builders[0].append_option(x);
+    builders[1].append_option(y);
+    builders[2].append_option(z);
+}
+
+impl EventBatchBuilder {
+    /// Add a single event
+    pub fn push(&mut self, name: &str, raw: &RawEvent, event: &GameEvent) {
+        let Ok(date) = NaiveDateTime::try_from(raw.date) else {
+            return;
+        };
+
+        let (target, weapon, damage, healing, attacker_position, victim_position) = match event {
+            GameEvent::Killed(kill) => (
+                account_id(&kill.target),
+                Some(kill.weapon),
+                None,
+                None,
+                kill.attacker_position,
+                kill.victim_position,
+            ),
+            GameEvent::KillAssist(assist) => (
+                account_id(&assist.target),
+                None,
+                None,
+                None,
+                assist.attacker_position,
+                assist.victim_position,
+            ),
+            GameEvent::Damage(damage) => (
+                account_id(&damage.target),
+                damage.weapon,
+                damage.damage,
+                None,
+                None,
+                None,
+            ),
+            GameEvent::Healed(heal) => (
+                heal.target.as_ref().and_then(account_id),
+                None,
+                None,
+                Some(heal.amount),
+                None,
+                None,
+            ),
+            GameEvent::ShotFired(shot) => (None, shot.weapon, None, None, None, None),
+            GameEvent::ShotHit(shot) => (None, shot.weapon, None, None, None, None),
+            _ => (None, None, None, None, None, None),
+        };
+
+        self.type_name.clear();
+        write!(self.type_name, "{:?}", raw.ty).expect("writing to a string can't fail");
+
+        self.log.append_value(name);
+        self.time.append_value(date.and_utc().timestamp());
+        self.ty.append_value(&self.type_name);
+        self.subject.append_option(account_id(&raw.subject));
+        self.target.append_option(target);
+        self.weapon.append_option(weapon);
+        self.damage.append_option(damage);
+        self.healing.append_option(healing);
+        append_position(&mut self.attacker_position, attacker_position);
+        append_position(&mut self.victim_position, victim_position);
+    }
+}
+
+impl LogBatchBuilder for EventBatchBuilder {
+    type Input = str;
+
+    fn schema() -> SchemaRef {
+        let position = |name: &str| {
+            ["x", "y", "z"]
+                .map(|axis| Field::new(format!("{}_{}", name, axis), DataType::Int32, true))
+        };
+        let mut fields = vec![
+            Field::new("log", dictionary(DataType::Int32), false),
+            Field::new("time", DataType::Timestamp(TimeUnit::Second, None), false),
+            Field::new("type", dictionary(DataType::Int16), false),
+            Field::new("subject", DataType::UInt32, true),
+            Field::new("target", DataType::UInt32, true),
+            Field::new("weapon", dictionary(DataType::Int32), true),
+            Field::new("damage", DataType::Int64, true),
+            Field::new("healing", DataType::UInt32, true),
+        ];
+        fields.extend(position("attacker"));
+        fields.extend(position("victim"));
+        Arc::new(Schema::new(fields))
+    }
+
+    fn push_log(&mut self, name: &str, log: &str) {
+        for line in LineSplit::new(log) {
+            let Ok(raw) = RawEvent::parse(line) else {
+                continue;
+            };
+            if !GameEvent::is_supported(raw.ty) {
+                continue;
+            }
+            if let Ok(event) = GameEvent::parse(&raw) {
+                self.push(name, &raw, &event);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.time.len()
+    }
+
+    fn finish(&mut self) -> Result<RecordBatch, Error> {
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.log.finish()),
+            Arc::new(self.time.finish()),
+            Arc::new(self.ty.finish()),
+            Arc::new(self.subject.finish()),
+            Arc::new(self.target.finish()),
+            Arc::new(self.weapon.finish()),
+            Arc::new(self.damage.finish()),
+            Arc::new(self.healing.finish()),
+        ];
+        for builder in self
+            .attacker_position
+            .iter_mut()
+            .chain(self.victim_position.iter_mut())
+        {
+            columns.push(Arc::new(builder.finish()));
+        }
+        Ok(RecordBatch::try_new(Self::schema(), columns)?)
+    }
+}
+
+/// Builder for record batches with a row of totals for every player in a log
+pub struct PlayerStatsBatchBuilder {
+    log: StringDictionaryBuilder<Int32Type>,
+    steam_id: UInt32Builder,
+    /// kills, assists, deaths, damage, healing, charges and drops
+    stats: [UInt32Builder; 7],
+}
+
+const PLAYER_STATS: [&str; 7] = [
+    "kills", "assists", "deaths", "damage", "healing", "charges", "drops",
+];
+
+impl Default for PlayerStatsBatchBuilder {
+    fn default() -> Self {
+        PlayerStatsBatchBuilder {
+            log: StringDictionaryBuilder::new(),
+            steam_id: UInt32Builder::new(),
+            stats: Default::default(),
+        }
+    }
+}
+
+impl LogBatchBuilder for PlayerStatsBatchBuilder {
+    type Input = BTreeMap<SteamId3, LogHandlerPerSubjectOutput>;
+
+    fn schema() -> SchemaRef {
+        let mut fields = vec![
+            Field::new("log", dictionary(DataType::Int32), false),
+            Field::new("steam_id", DataType::UInt32, false),
+        ];
+        fields.extend(
+            PLAYER_STATS
+                .iter()
+                .map(|name| Field::new(*name, DataType::UInt32, false)),
+        );
+        Arc::new(Schema::new(fields))
+    }
+
+    fn push_log(&mut self, name: &str, players: &Self::Input) {
+        for (steam_id, player) in players {
+            let stats = [
+                player.class_stats.kills.total(),
+                player.class_stats.assists.total(),
+                player.class_stats.deaths.total(),
+                player.class_stats.damage.total(),
+                player.heal_spread.total(),
+                player.medic_stats.charge_count,
+                player.medic_stats.drops,
+            ];
+            self.log.append_value(name);
+            self.steam_id.append_value(steam_id.0.account_id());
+            for (builder, value) in self.stats.iter_mut().zip(stats) {
+                builder.append_value(value);
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.steam_id.len()
+    }
+
+    fn finish(&mut self) -> Result<RecordBatch, Error> {
+        let mut columns: Vec<ArrayRef> = vec![
+            Arc::new(self.log.finish()),
+            Arc::new(self.steam_id.finish()),
+        ];
+        for builder in self.stats.iter_mut() {
+            columns.push(Arc::new(builder.finish()));
+        }
+        Ok(RecordBatch::try_new(Self::schema(), columns)?)
+    }
+}
+
+/// Writes the rows for many logs into a single parquet file
+///
+/// Rows are buffered and written in batches, [`close`](ParquetWriter::close) has to be called to write the
+/// remaining rows and the file footer.
+pub struct ParquetWriter<W: Write + Send, B: LogBatchBuilder> {
+    writer: ArrowWriter<W>,
+    builder: B,
+    batch_size: usize,
+}
+
+impl<W: Write + Send, B: LogBatchBuilder> ParquetWriter<W, B> {
+    pub fn new(writer: W) -> Result<Self, Error> {
+        Ok(ParquetWriter {
+            writer: ArrowWriter::try_new(writer, B::schema(), None)?,
+            builder: B::default(),
+            batch_size: DEFAULT_BATCH_SIZE,
+        })
+    }
+
+    /// Set the number of rows that are buffered before they are written
+    pub fn batch_size(mut self, batch_size: usize) -> Self {
+        self.batch_size = batch_size;
+        self
+    }
+
+    /// Add the rows for a log, `name` is used for the `log` column and can be the log id or the filename
+    pub fn write_log(&mut self, name: &str, log: &B::Input) -> Result<(), Error> {
+        self.builder.push_log(name, log);
+        if self.builder.len() >= self.batch_size {
+            self.writer.write(&self.builder.finish()?)?;
+        }
+        Ok(())
+    }
+
+    /// Write the remaining rows and the file footer, returning the underlying writer
+    pub fn close(mut self) -> Result<W, Error> {
+        if !self.builder.is_empty() {
+            self.writer.write(&self.builder.finish()?)?;
+        }
+        Ok(self.writer.into_inner()?)
+    }
+}
+
+#[test]
+fn test_event_batch() {
+    use arrow_array::cast::AsArray;
+    use arrow_array::Array;
+    use parquet::arrow::arrow_reader::ParquetRecordBatchReaderBuilder;
+
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let events: Vec<_> = LineSplit::new(&log)
+        .filter_map(|line| RawEvent::parse(line).ok())
+        .filter(|raw| GameEvent::is_supported(raw.ty) && GameEvent::parse(raw).is_ok())
+        .collect();
+
+    let mut builder = EventBatchBuilder::default();
+    builder.push_log("2892242", &log);
+    let batch = builder.finish().unwrap();
+    assert_eq!(events.len(), batch.num_rows());
+    assert!(builder.is_empty());
+
+    let types = batch
+        .column_by_name("type")
+        .unwrap()
+        .as_dictionary::<Int16Type>();
+    let kill = (0..batch.num_rows())
+        .find(|row| {
+            let key = types.keys().value(*row) as usize;
+            types.values().as_string::<i32>().value(key) == "Killed"
+        })
+        .expect("no kills in log");
+    let weapons = batch
+        .column_by_name("weapon")
+        .unwrap()
+        .as_dictionary::<Int32Type>();
+    assert!(weapons.is_valid(kill));
+    assert!(batch.column_by_name("victim_x").unwrap().is_valid(kill));
+
+    let path = std::env::temp_dir().join(format!("tf-log-parser-{}.parquet", std::process::id()));
+    let file = std::fs::File::create(&path).unwrap();
+    let mut writer = ParquetWriter::<_, EventBatchBuilder>::new(file)
+        .unwrap()
+        .batch_size(1000);
+    writer.write_log("2892242", &log).unwrap();
+    writer.write_log("again", &log).unwrap();
+    writer.close().unwrap();
+    let file = std::fs::File::open(&path).unwrap();
+    let rows: usize = ParquetRecordBatchReaderBuilder::try_new(file)
+        .unwrap()
+        .build()
+        .unwrap()
+        .map(|batch| batch.unwrap().num_rows())
+        .sum();
+    std::fs::remove_file(&path).unwrap();
+    assert_eq!(events.len() * 2, rows);
+}
+
+#[test]
+fn test_player_stats_batch() {
+    use arrow_array::cast::AsArray;
+    use arrow_array::types::UInt32Type;
+
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (_, players) = crate::parse(&log).unwrap();
+
+    let mut builder = PlayerStatsBatchBuilder::default();
+    builder.push_log("2892242", &players);
+    let batch = builder.finish().unwrap();
+    assert_eq!(players.len(), batch.num_rows());
+
+    let damage = batch
+        .column_by_name("damage")
+        .unwrap()
+        .as_primitive::<UInt32Type>();
+    for (row, player) in players.values().enumerate() {
+        assert_eq!(player.class_stats.damage.total(), damage.value(row));
+    }
+}
@@ -0,0 +1,264 @@
+//! Parse directories of logs in parallel
+
+use crate::read::is_log;
+use crate::{
+    parse_bytes_with_options, read_log_bytes, Error, EventHandler, HandlerOutput, ParseOptions,
+};
+use rayon::prelude::*;
+use std::any::Any;
+use std::marker::PhantomData;
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::Arc;
+use std::thread;
+use walkdir::WalkDir;
+
+/// Progress of a batch, reported after each parsed log
+#[derive(Debug, Clone, Copy)]
+pub struct Progress<'a> {
+    /// The log that was just parsed
+    pub path: &'a Path,
+    /// Number of logs parsed so far
+    pub done: usize,
+    /// Total number of logs in the batch
+    pub total: usize,
+}
+
+type ProgressCallback = Arc<dyn Fn(Progress) + Send + Sync>;
+
+/// Parse all `.log` files in a directory recursively, including compressed `.log.gz`, `.log.bz2` and `.log.zip` files
+pub fn parse_dir<Handler>(path: impl AsRef<Path>) -> BatchIter<Handler>
+where
+    Handler: EventHandler + 'static,
+    HandlerOutput<Handler>: Send,
+{
+    BatchParser::new().parse_dir(path)
+}
+
+/// Parser for a batch of logs
+///
+/// Logs are parsed in parallel, a panic while parsing a log is reported as an error for that log.
+#[derive(Default, Clone)]
+pub struct BatchParser {
+    options: ParseOptions,
+    progress: Option<ProgressCallback>,
+}
+
+impl BatchParser {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Set the options used to parse each log
+    pub fn options(mut self, options: ParseOptions) -> Self {
+        self.options = options;
+        self
+    }
+
+    /// Set a callback that is called after each log is parsed
+    ///
+    /// The callback is called from the worker threads.
+    pub fn progress<F: Fn(Progress) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.progress = Some(Arc::new(callback));
+        self
+    }
+
+    /// Parse all `.log` files in a directory recursively, including compressed `.log.gz`, `.log.bz2` and `.log.zip` files
+    pub fn parse_dir<Handler>(self, path: impl AsRef<Path>) -> BatchIter<Handler>
+    where
+        Handler: EventHandler + 'static,
+        HandlerOutput<Handler>: Send,
+    {
+        let paths = WalkDir::new(path)
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file() && is_log(entry.path()))
+            .map(|entry| entry.into_path())
+            .collect();
+        self.parse_files(paths)
+    }
+
+    /// Parse a list of log files
+    pub fn parse_files<Handler>(self, paths: Vec<PathBuf>) -> BatchIter<Handler>
+    where
+        Handler: EventHandler + 'static,
+        HandlerOutput<Handler>: Send,
+    {
+        let total = paths.len();
+        let (sender, receiver) = channel();
+        let cancelled = Arc::new(AtomicBool::new(false));
+        let worker_cancelled = cancelled.clone();
+        thread::spawn(move || {
+            let done = AtomicUsize::default();
+            paths.into_par_iter().for_each_with(sender, |sender, path| {
+                if worker_cancelled.load(Ordering::Relaxed) {
+                    return;
+                }
+                let result = parse_file::<Handler>(&path, &self.options);
+                if let Some(progress) = &self.progress {
+                    progress(Progress {
+                        path: &path,
+                        done: done.fetch_add(1, Ordering::Relaxed) + 1,
+                        total,
+                    });
+                }
+                // the receiver is gone if the iterator was dropped, stop parsing the remaining logs
+                if sender.send((path, result)).is_err() {
+                    worker_cancelled.store(true, Ordering::Relaxed);
+                }
+            });
+        });
+        BatchIter {
+            receiver,
+            cancelled,
+            total,
+            handler: PhantomData,
+        }
+    }
+}
+
+/// Iterator over the results of a batch, in the order the logs finish parsing
+///
+/// Dropping the iterator stops parsing any logs that haven't been started yet.
+pub struct BatchIter<Handler: EventHandler> {
+    receiver: Receiver<(PathBuf, Result<HandlerOutput<Handler>, Error>)>,
+    /// Set when the iterator is dropped, so the workers can skip the remaining logs
+    cancelled: Arc<AtomicBool>,
+    total: usize,
+    handler: PhantomData<Handler>,
+}
+
+impl<Handler: EventHandler> BatchIter<Handler> {
+    /// Total number of logs in the batch
+    pub fn total(&self) -> usize {
+        self.total
+    }
+}
+
+impl<Handler: EventHandler> Iterator for BatchIter<Handler> {
+    type Item = (PathBuf, Result<HandlerOutput<Handler>, Error>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.receiver.recv().ok()
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        (0, Some(self.total))
+    }
+}
+
+impl<Handler: EventHandler> Drop for BatchIter<Handler> {
+    fn drop(&mut self) {
+        self.cancelled.store(true, Ordering::Relaxed);
+    }
+}
+
+fn parse_file<Handler: EventHandler>(
+    path: &Path,
+    options: &ParseOptions,
+) -> Result<HandlerOutput<Handler>, Error> {
+    let content = read_log_bytes(path)?;
+    catch_unwind(AssertUnwindSafe(|| {
+        parse_bytes_with_options::<Handler>(&content, options)
+    }))
+    .unwrap_or_else(|panic| Err(Error::Panic(panic_message(panic))))
+}
+
+fn panic_message(panic: Box<dyn Any + Send>) -> String {
+    match panic.downcast::<String>() {
+        Ok(message) => *message,
+        Err(panic) => match panic.downcast::<&'static str>() {
+            Ok(message) => message.to_string(),
+            Err(_) => "unknown panic".into(),
+        },
+    }
+}
+
+#[test]
+fn test_parse_dir() {
+    use std::sync::Mutex;
+
+    let progress = Arc::new(Mutex::new(Vec::new()));
+    let reported = progress.clone();
+    let results: Vec<_> = BatchParser::new()
+        .progress(move |progress| {
+            reported
+                .lock()
+                .unwrap()
+                .push((progress.done, progress.total))
+        })
+        .parse_dir::<crate::LogHandler>("tests/data")
+        .collect();
+
+    let count = std::fs::read_dir("tests/data")
+        .unwrap()
+        .flatten()
+        .filter(|entry| is_log(&entry.path()))
+        .count();
+    assert_eq!(count, results.len());
+    for (path, result) in results {
+        let (_, players) = result.unwrap();
+        assert!(!players.is_empty(), "no players in {}", path.display());
+    }
+
+    let mut progress = progress.lock().unwrap().clone();
+    progress.sort();
+    assert_eq!(
+        (1..=count).map(|done| (done, count)).collect::<Vec<_>>(),
+        progress
+    );
+}
+
+#[test]
+fn test_parse_dir_panic() {
+    use crate::module::GlobalData;
+    use crate::{EventMeta, GameEvent, RawEventType, SubjectId, SubjectMap};
+
+    #[derive(Default)]
+    struct PanicHandler;
+
+    impl GlobalData for PanicHandler {
+        type Output = ();
+
+        fn does_handle(ty: RawEventType) -> bool {
+            matches!(ty, RawEventType::Killed)
+        }
+
+        fn handle_event(&mut self, _meta: &EventMeta, _subject: SubjectId, _event: &GameEvent) {
+            panic!("kill");
+        }
+
+        fn finish(self, _subjects: &SubjectMap) -> Self::Output {}
+    }
+
+    let results: Vec<_> = parse_dir::<PanicHandler>("tests/data").collect();
+    assert!(!results.is_empty());
+    for (_, result) in results {
+        assert!(matches!(result, Err(Error::Panic(message)) if message == "kill"));
+    }
+}
+
+#[test]
+fn test_parse_files_cancel() {
+    use std::time::Duration;
+
+    let total = 200;
+    let parsed = Arc::new(AtomicUsize::default());
+    let counter = parsed.clone();
+    let mut results = BatchParser::new()
+        .progress(move |_| {
+            counter.fetch_add(1, Ordering::Relaxed);
+        })
+        .parse_files::<crate::LogHandler>(vec![PathBuf::from("tests/data/log_6s.log.gz"); total]);
+    assert!(results.next().unwrap().1.is_ok());
+    drop(results);
+
+    // logs that were already being parsed are allowed to finish
+    thread::sleep(Duration::from_millis(500));
+    let after_drop = parsed.load(Ordering::Relaxed);
+    assert!(after_drop < total);
+    thread::sleep(Duration::from_millis(500));
+    assert_eq!(after_drop, parsed.load(Ordering::Relaxed));
+}
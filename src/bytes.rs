@@ -0,0 +1,270 @@
+use crate::parser::LogParser;
+use crate::{
+    validate_line, Error, EventHandler, HandlerOutput, LineLocation, LogHandler, ParseOptions,
+    RawEvent, Result,
+};
+use chrono::NaiveDateTime;
+use memchr::memmem::{find, find_iter, FindIter};
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::ops::Range;
+use std::str::from_utf8;
+
+/// A single line from a log that can contain invalid utf8
+///
+/// Lines with only valid utf8 are borrowed from the log, invalid utf8 is replaced per line
+/// so it can't affect any other lines.
+///
+/// The events are lexed from the converted text, not from the raw bytes. Replacing invalid utf8 never
+/// consumes any ascii bytes, so all quotes and other delimiters of the line are kept and invalid bytes
+/// in one field, like a player name, are only replaced within that field.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RawLine<'a> {
+    text: Cow<'a, str>,
+    /// Byte offset of the line in the log, excluding the leading `L `
+    offset: usize,
+    /// Length of the line in the log, in bytes
+    len: usize,
+}
+
+impl<'a> RawLine<'a> {
+    fn new(offset: usize, line: &'a [u8]) -> Self {
+        let text = match from_utf8(line) {
+            Ok(text) => Cow::Borrowed(text),
+            Err(_) => String::from_utf8_lossy(line),
+        };
+        RawLine {
+            text,
+            offset,
+            len: line.len(),
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.text
+    }
+
+    /// Whether invalid utf8 was replaced in this line
+    pub fn is_lossy(&self) -> bool {
+        matches!(self.text, Cow::Owned(_))
+    }
+
+    pub fn parse(&self) -> Result<RawEvent<'_>> {
+        RawEvent::parse(&self.text)
+    }
+
+    /// Get the line number and byte span of the line in the log
+    pub fn location(&self, log: &[u8]) -> LineLocation {
+        let start = self.offset.saturating_sub(2);
+        LineLocation {
+            line: memchr::memchr_iter(b'\n', &log[..start]).count() + 1,
+            span: start..self.offset + self.len,
+        }
+    }
+
+    /// Byte range of the line in the log, excluding the leading `L `
+    pub(crate) fn range(&self) -> Range<usize> {
+        self.offset..self.offset + self.len
+    }
+
+    /// Attach the location of the line in the log to an error
+    pub fn locate(&self, log: &[u8], error: Error) -> Error {
+        let location = self.location(log);
+        let text = format!("L {}", self.text);
+        error.located_at(&text, location)
+    }
+}
+
+/// Split a log into lines without requiring it to be valid utf8
+///
+/// This yields the same lines as [`LineSplit`](crate::LineSplit).
+pub struct LineSplitBytes<'a> {
+    full: &'a [u8],
+    /// Offset of the first line, where the search for line breaks starts
+    input_start: usize,
+    start: usize,
+    iter: FindIter<'a, 'static>,
+}
+
+impl<'a> LineSplitBytes<'a> {
+    pub fn new(full: &'a [u8]) -> Self {
+        // skip first delimiter, and any byte order mark
+        let start = find(full, b"L ").map(|pos| pos + 2).unwrap_or(full.len());
+        LineSplitBytes {
+            full,
+            input_start: start,
+            start,
+            iter: find_iter(&full[start..], b"\nL "),
+        }
+    }
+}
+
+impl<'a> Iterator for LineSplitBytes<'a> {
+    type Item = RawLine<'a>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        match self.iter.next() {
+            Some(next) => {
+                let start = self.start;
+                let end = self.input_start + next;
+                self.start = end + 3;
+                Some(RawLine::new(start, &self.full[start..end]))
+            }
+            None if self.start < self.full.len() => {
+                let start = self.start;
+                self.start = self.full.len();
+                let mut line = &self.full[start..];
+                while let [rest @ .., b'\n'] = line {
+                    line = rest;
+                }
+                Some(RawLine::new(start, line))
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Get the lines of a log that can contain invalid utf8, use [`RawLine::parse`] to get the raw event for each line
+pub fn raw_events_bytes(log: &[u8]) -> impl Iterator<Item = RawLine<'_>> {
+    LineSplitBytes::new(log)
+}
+
+/// The date of the first line of the log
+pub(crate) fn log_start(log: &[u8]) -> Option<NaiveDateTime> {
+    raw_events_bytes(log).find_map(|line| {
+        let raw = line.parse().ok()?;
+        raw.date.try_into().ok()
+    })
+}
+
+/// Parse a log that can contain invalid utf8
+pub fn parse_bytes(log: &[u8]) -> Result<HandlerOutput<LogHandler>, Error> {
+    parse_bytes_with_handler::<LogHandler>(log)
+}
+
+pub fn parse_bytes_with_handler<Handler: EventHandler>(
+    log: &[u8],
+) -> Result<HandlerOutput<Handler>, Error> {
+    parse_bytes_with_options::<Handler>(log, &ParseOptions::default())
+}
+
+/// Parse a log that can contain invalid utf8, see [`parse_with_options`](crate::parse_with_options)
+pub fn parse_bytes_with_options<Handler: EventHandler>(
+    log: &[u8],
+    options: &ParseOptions,
+) -> Result<HandlerOutput<Handler>, Error> {
+    // since we have the full log available, we can look ahead for the time origin and tournament start
+    let options = options.with_lookahead(|ty| {
+        LineSplitBytes::new(log).find_map(|line| {
+            let event = line.parse().ok().filter(|event| event.ty == ty)?;
+            event.date.try_into().ok()
+        })
+    });
+
+    let mut parser = LogParser::<Handler>::new(&options);
+    for line in LineSplitBytes::new(log) {
+        parser.handle_line(line.as_str(), |e, _| line.locate(log, e))?;
+    }
+    Ok(parser.finish())
+}
+
+/// Check all lines of a log that can contain invalid utf8, see [`validate`](crate::validate)
+pub fn validate_bytes(log: &[u8]) -> Vec<Error> {
+    let mut lines = LineSplitBytes::new(log).peekable();
+    let mut errors = Vec::new();
+    while let Some(line) = lines.next() {
+        let last = lines.peek().is_none();
+        errors.extend(
+            validate_line(line.as_str(), last)
+                .into_iter()
+                .map(|e| line.locate(log, e)),
+        );
+    }
+    errors
+}
+
+#[test]
+fn test_split_bytes() {
+    use crate::LineSplit;
+
+    let input = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let expected: Vec<_> = LineSplit::new(&input).collect();
+    let split: Vec<_> = LineSplitBytes::new(input.as_bytes()).collect();
+    assert_eq!(expected.len(), split.len());
+    for (expected, line) in expected.iter().zip(split.iter()) {
+        assert_eq!(*expected, line.as_str());
+        assert!(!line.is_lossy());
+    }
+    for (expected, line) in expected.iter().zip(split.iter()).step_by(100) {
+        assert_eq!(
+            LineLocation::new(&input, expected),
+            Some(line.location(input.as_bytes()))
+        );
+    }
+}
+
+#[test]
+fn test_parse_bytes() {
+    let input = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (global, players) = crate::parse(&input).unwrap();
+    let (bytes_global, bytes_players) = parse_bytes(input.as_bytes()).unwrap();
+    assert_eq!(
+        serde_json::to_value(&global).unwrap(),
+        serde_json::to_value(&bytes_global).unwrap()
+    );
+    assert_eq!(
+        serde_json::to_value(&players).unwrap(),
+        serde_json::to_value(&bytes_players).unwrap()
+    );
+}
+
+#[test]
+fn test_invalid_utf8() {
+    use crate::module::ChatMessages;
+
+    let log: &[u8] = b"L 08/06/2018 - 21:13:57: \"mak\xffxbi<27><[U:1:40364391]><Red>\" say \"gl \xe2\x82\"\nL 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" say \"\xe2\x82\xac\"\n";
+    let lines: Vec<_> = raw_events_bytes(log).collect();
+    assert!(lines[0].is_lossy());
+    assert!(!lines[1].is_lossy());
+    assert_eq!(
+        crate::raw_event::RawSubject::Player("mak\u{fffd}xbi<27><[U:1:40364391]><Red>"),
+        lines[0].parse().unwrap().subject
+    );
+
+    // invalid bytes right before a delimiter don't affect the rest of the line
+    let kill: &[u8] = b"L 08/06/2018 - 21:13:57: \"mak\xe2\x82<27><[U:1:40364391]><Red>\" killed \"\xf0\x9f<28><[U:1:2]><Blue>\" with \"scattergun\"";
+    let line = raw_events_bytes(kill).next().unwrap();
+    let raw = line.parse().unwrap();
+    assert_eq!(
+        crate::raw_event::RawSubject::Player("mak\u{fffd}<27><[U:1:40364391]><Red>"),
+        raw.subject
+    );
+    let crate::GameEvent::Killed(kill) = crate::GameEvent::parse(&raw).unwrap() else {
+        panic!("not a kill event");
+    };
+    assert_eq!(
+        crate::raw_event::RawSubject::Player("\u{fffd}<28><[U:1:2]><Blue>"),
+        kill.target
+    );
+    assert_eq!("scattergun", kill.weapon);
+
+    let (chat, _) = parse_bytes_with_handler::<ChatMessages>(log).unwrap();
+    let messages: Vec<_> = chat
+        .iter()
+        .map(|message| message.message.as_str())
+        .collect();
+    assert_eq!(vec!["gl \u{fffd}", "\u{20ac}"], messages);
+}
+
+#[test]
+fn test_validate_bytes() {
+    let log: &[u8] = b"L 08/06/2018 - 21:13:57: \"mak\xffxbi<27><[U:1:40364391]><Red>\" say \"gl\"\nL 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo<3><[U:1:4]><Blue>\" (damage \"abc\")\nL 08/06/2018 - 21:13:59: \"makxbi<27><[U:1:40364391]><Red>\" say \"gg\"\n";
+    let errors = validate_bytes(log);
+    assert_eq!(1, errors.len());
+    let location = errors[0].location().unwrap();
+    assert_eq!(2, location.line);
+    assert_eq!(
+        &b"L 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo<3><[U:1:4]><Blue>\" (damage \"abc\")"[..],
+        &log[location.span.clone()]
+    );
+}
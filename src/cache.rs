@@ -0,0 +1,619 @@
+//! Compact binary cache of the events in a log
+//!
+//! Caching a log once with [`write_cache`] allows it to be replayed into any [`EventHandler`] without
+//! having to parse the text of the log again.
+//!
+//! The cache starts with the `TFLC` magic and a version byte, followed by one record for every line of the log:
+//! the event type, the time since the previous event as zigzag varint, the subject and the fields of the event.
+//! Event types, subjects and strings like weapons are dictionary encoded, the first time one is used it's
+//! written in full, after that only its index is written.
+
+use crate::parser::{LineLexer, LogParser};
+use crate::raw_event::RawSubject;
+use crate::{
+    Class, Error, EventHandler, GameEvent, HandlerOutput, LineSplit, ParseOptions, RawEventType,
+    Result, SubjectId, Team,
+};
+use ahash::AHashMap;
+use chrono::{DateTime, NaiveDateTime};
+use enum_iterator::all;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::Write as _;
+use std::hash::Hash;
+use std::io::Write;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr, SocketAddr};
+
+const MAGIC: &[u8; 4] = b"TFLC";
+const VERSION: u8 = 1;
+
+/// Write the events of a log into a cache
+///
+/// Like when parsing the log, malformed lines result in an error unless they are the last line of the log
+/// or truncated by combining logs into a single file.
+pub fn write_cache<W: Write>(log: &str, mut output: W) -> Result<()> {
+    let mut writer = CacheWriter::default();
+    let mut lexer = LineLexer::new(&ParseOptions::default());
+    let locate = |e: Error, line: &str| e.located(log, line);
+    for line in LineSplit::new(log) {
+        let Some(raw) = lexer.raw_event(line, locate)? else {
+            continue;
+        };
+        let date = NaiveDateTime::try_from(raw.date).map_err(|e| locate(e.into(), line))?;
+        let event = if GameEvent::is_supported(raw.ty) {
+            match lexer.event(&raw, line, locate) {
+                Some(event) => Some(event),
+                None => continue,
+            }
+        } else {
+            None
+        };
+        writer.event(
+            date.and_utc().timestamp(),
+            raw.ty,
+            raw.subject,
+            event.as_ref(),
+        );
+    }
+
+    output.write_all(MAGIC)?;
+    output.write_all(&[VERSION])?;
+    output.write_all(&writer.buffer)?;
+    Ok(())
+}
+
+/// Open a cache created by [`write_cache`]
+pub fn read_cache(data: &[u8]) -> Result<EventCache<'_>> {
+    let data = data.strip_prefix(MAGIC).ok_or(Error::MalformedCache)?;
+    let (&version, data) = data.split_first().ok_or(Error::MalformedCache)?;
+    if version != VERSION {
+        return Err(Error::CacheVersion(version));
+    }
+    Ok(EventCache { data })
+}
+
+/// The events of a single log, read from a cache
+pub struct EventCache<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> EventCache<'a> {
+    pub fn events(&self) -> CachedEvents<'a> {
+        CachedEvents {
+            reader: CacheReader::new(self.data),
+        }
+    }
+
+    /// Replay the events into a handler, giving the same output as parsing the log it was created from
+    pub fn replay<Handler: EventHandler>(&self) -> Result<HandlerOutput<Handler>> {
+        self.replay_with_options::<Handler>(&ParseOptions::default())
+    }
+
+    pub fn replay_with_options<Handler: EventHandler>(
+        &self,
+        options: &ParseOptions,
+    ) -> Result<HandlerOutput<Handler>> {
+        let options = options.with_lookahead(|ty| {
+            self.events()
+                .map_while(Result::ok)
+                .find(|event| event.ty == ty)
+                .map(|event| event.date)
+        });
+
+        let mut parser = LogParser::<Handler>::new(&options);
+        for event in self.events() {
+            parser.handle_cached(&event?)?;
+        }
+        Ok(parser.finish())
+    }
+}
+
+/// An event from the cache
+#[derive(Debug)]
+pub struct CachedEvent<'a> {
+    pub date: NaiveDateTime,
+    pub ty: RawEventType,
+    pub subject: RawSubject<'a>,
+    /// The parsed event, for event types that are supported by the parser
+    pub event: Option<GameEvent<'a>>,
+    /// Id of the subject, resolved once for every subject in the cache
+    pub(crate) subject_id: Option<SubjectId>,
+}
+
+/// Iterator over the events in a cache, stops after the first error
+pub struct CachedEvents<'a> {
+    reader: CacheReader<'a>,
+}
+
+impl<'a> Iterator for CachedEvents<'a> {
+    type Item = Result<CachedEvent<'a>>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.reader.data.is_empty() {
+            return None;
+        }
+        let event = self.reader.event();
+        if event.is_none() {
+            self.reader.data = &[];
+        }
+        Some(event.ok_or(Error::MalformedCache))
+    }
+}
+
+/// Values that can be written to the cache as part of an event
+pub(crate) trait Encode<'a>: Sized {
+    fn encode(&self, writer: &mut CacheWriter<'a>);
+
+    /// Decode a value, returns `None` if the cache is malformed
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self>;
+}
+
+/// Write the index of a dictionary entry, returning whether the entry is new and has to be written in full
+fn dictionary_index<K: Hash + Eq>(
+    buffer: &mut Vec<u8>,
+    dictionary: &mut AHashMap<K, u32>,
+    key: K,
+) -> bool {
+    let next = dictionary.len() as u32;
+    let index = *dictionary.entry(key).or_insert(next);
+    write_varint(buffer, index as u64);
+    index == next
+}
+
+fn write_varint(buffer: &mut Vec<u8>, mut value: u64) {
+    while value >= 0x80 {
+        buffer.push(value as u8 | 0x80);
+        value >>= 7;
+    }
+    buffer.push(value as u8);
+}
+
+#[derive(Default)]
+pub(crate) struct CacheWriter<'a> {
+    buffer: Vec<u8>,
+    strings: AHashMap<&'a str, u32>,
+    subjects: AHashMap<RawSubject<'a>, u32>,
+    types: AHashMap<RawEventType, u32>,
+    time: i64,
+}
+
+impl<'a> CacheWriter<'a> {
+    fn event(
+        &mut self,
+        time: i64,
+        ty: RawEventType,
+        subject: RawSubject<'a>,
+        event: Option<&GameEvent<'a>>,
+    ) {
+        if dictionary_index(&mut self.buffer, &mut self.types, ty) {
+            self.bytes(format!("{:?}", ty).as_bytes());
+        }
+        self.signed(time - self.time);
+        self.time = time;
+        subject.encode(self);
+        if let Some(event) = event {
+            event.encode(self);
+        }
+    }
+
+    fn byte(&mut self, value: u8) {
+        self.buffer.push(value);
+    }
+
+    fn varint(&mut self, value: u64) {
+        write_varint(&mut self.buffer, value);
+    }
+
+    fn signed(&mut self, value: i64) {
+        self.varint(((value << 1) ^ (value >> 63)) as u64);
+    }
+
+    /// Write length prefixed bytes
+    fn bytes(&mut self, value: &[u8]) {
+        self.varint(value.len() as u64);
+        self.buffer.extend_from_slice(value);
+    }
+}
+
+pub(crate) struct CacheReader<'a> {
+    data: &'a [u8],
+    strings: Vec<&'a str>,
+    subjects: Vec<RawSubject<'a>>,
+    subject_ids: Vec<Option<SubjectId>>,
+    types: Vec<RawEventType>,
+    time: i64,
+}
+
+impl<'a> CacheReader<'a> {
+    fn new(data: &'a [u8]) -> Self {
+        CacheReader {
+            data,
+            strings: Vec::with_capacity(256),
+            subjects: Vec::with_capacity(32),
+            subject_ids: Vec::with_capacity(32),
+            types: Vec::with_capacity(32),
+            time: 0,
+        }
+    }
+
+    fn event(&mut self) -> Option<CachedEvent<'a>> {
+        let index = self.varint()? as usize;
+        if index == self.types.len() {
+            let name = self.str()?;
+            let mut buffer = String::new();
+            let ty = all::<RawEventType>().find(|ty| {
+                buffer.clear();
+                let _ = write!(buffer, "{:?}", ty);
+                buffer == name
+            })?;
+            self.types.push(ty);
+        }
+        let ty = *self.types.get(index)?;
+
+        self.time = self.time.checked_add(self.signed()?)?;
+        let date = DateTime::from_timestamp(self.time, 0)?.naive_utc();
+        let index = self.varint()? as usize;
+        if index == self.subjects.len() {
+            let subject = self.subject()?;
+            self.subjects.push(subject);
+            self.subject_ids.push(subject.id().ok());
+        }
+        let subject = *self.subjects.get(index)?;
+        let subject_id = self.subject_ids[index];
+        let event = if GameEvent::is_supported(ty) {
+            Some(GameEvent::decode(ty, self)?)
+        } else {
+            None
+        };
+        Some(CachedEvent {
+            date,
+            ty,
+            subject,
+            event,
+            subject_id,
+        })
+    }
+
+    /// Read a subject that is written in full
+    fn subject(&mut self) -> Option<RawSubject<'a>> {
+        Some(match self.byte()? {
+            0 => RawSubject::Player(self.str()?),
+            1 => RawSubject::Team(Team::decode(self)?),
+            2 => RawSubject::System(self.str()?),
+            3 => RawSubject::Console,
+            4 => RawSubject::World,
+            _ => return None,
+        })
+    }
+
+    fn byte(&mut self) -> Option<u8> {
+        let (&byte, data) = self.data.split_first()?;
+        self.data = data;
+        Some(byte)
+    }
+
+    fn varint(&mut self) -> Option<u64> {
+        let mut value = 0;
+        let mut shift = 0;
+        loop {
+            let byte = self.byte()?;
+            value |= ((byte & 0x7f) as u64) << shift;
+            if byte < 0x80 {
+                return Some(value);
+            }
+            shift += 7;
+            if shift > 63 {
+                return None;
+            }
+        }
+    }
+
+    fn signed(&mut self) -> Option<i64> {
+        let value = self.varint()?;
+        Some((value >> 1) as i64 ^ -((value & 1) as i64))
+    }
+
+    fn array<const N: usize>(&mut self) -> Option<[u8; N]> {
+        if self.data.len() < N {
+            return None;
+        }
+        let (bytes, data) = self.data.split_at(N);
+        self.data = data;
+        Some(bytes.try_into().expect("length checked"))
+    }
+
+    /// Read length prefixed bytes
+    fn bytes(&mut self) -> Option<&'a [u8]> {
+        let len = self.varint()? as usize;
+        if self.data.len() < len {
+            return None;
+        }
+        let (bytes, data) = self.data.split_at(len);
+        self.data = data;
+        Some(bytes)
+    }
+
+    fn str(&mut self) -> Option<&'a str> {
+        std::str::from_utf8(self.bytes()?).ok()
+    }
+}
+
+impl<'a> Encode<'a> for &'a str {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        if dictionary_index(&mut writer.buffer, &mut writer.strings, *self) {
+            writer.bytes(self.as_bytes());
+        }
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        let index = reader.varint()? as usize;
+        if index == reader.strings.len() {
+            let value = reader.str()?;
+            reader.strings.push(value);
+        }
+        reader.strings.get(index).copied()
+    }
+}
+
+impl<'a> Encode<'a> for RawSubject<'a> {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        if !dictionary_index(&mut writer.buffer, &mut writer.subjects, *self) {
+            return;
+        }
+        match self {
+            RawSubject::Player(player) => {
+                writer.byte(0);
+                writer.bytes(player.as_bytes());
+            }
+            RawSubject::Team(team) => {
+                writer.byte(1);
+                team.encode(writer);
+            }
+            RawSubject::System(system) => {
+                writer.byte(2);
+                writer.bytes(system.as_bytes());
+            }
+            RawSubject::Console => writer.byte(3),
+            RawSubject::World => writer.byte(4),
+        }
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        let index = reader.varint()? as usize;
+        if index == reader.subjects.len() {
+            let subject = reader.subject()?;
+            reader.subjects.push(subject);
+            reader.subject_ids.push(subject.id().ok());
+        }
+        reader.subjects.get(index).copied()
+    }
+}
+
+impl<'a> Encode<'a> for u8 {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        writer.byte(*self);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        reader.byte()
+    }
+}
+
+impl<'a> Encode<'a> for u32 {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        writer.varint(*self as u64);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        u32::try_from(reader.varint()?).ok()
+    }
+}
+
+impl<'a> Encode<'a> for i32 {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        writer.signed(*self as i64);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        i32::try_from(reader.signed()?).ok()
+    }
+}
+
+impl<'a> Encode<'a> for i64 {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        writer.signed(*self);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        reader.signed()
+    }
+}
+
+impl<'a> Encode<'a> for f32 {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        writer.buffer.extend_from_slice(&self.to_le_bytes());
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        Some(f32::from_le_bytes(reader.array()?))
+    }
+}
+
+impl<'a> Encode<'a> for Class {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        let index = all::<Class>()
+            .position(|class| class == *self)
+            .expect("all classes are in the sequence");
+        writer.byte(index as u8);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        let index = reader.byte()?;
+        all::<Class>().nth(index as usize)
+    }
+}
+
+impl<'a> Encode<'a> for Team {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        writer.byte(match self {
+            Team::Red => 0,
+            Team::Blue => 1,
+            Team::Spectator => 2,
+        });
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        match reader.byte()? {
+            0 => Some(Team::Red),
+            1 => Some(Team::Blue),
+            2 => Some(Team::Spectator),
+            _ => None,
+        }
+    }
+}
+
+impl<'a> Encode<'a> for SocketAddr {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        match self.ip() {
+            IpAddr::V4(ip) => {
+                writer.byte(4);
+                writer.buffer.extend_from_slice(&ip.octets());
+            }
+            IpAddr::V6(ip) => {
+                writer.byte(6);
+                writer.buffer.extend_from_slice(&ip.octets());
+            }
+        }
+        writer.varint(self.port() as u64);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        let ip = match reader.byte()? {
+            4 => IpAddr::V4(Ipv4Addr::from(reader.array::<4>()?)),
+            6 => IpAddr::V6(Ipv6Addr::from(reader.array::<16>()?)),
+            _ => return None,
+        };
+        let port = u16::try_from(reader.varint()?).ok()?;
+        Some(SocketAddr::new(ip, port))
+    }
+}
+
+impl<'a, T: Encode<'a>> Encode<'a> for Option<T> {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        match self {
+            Some(value) => {
+                writer.byte(1);
+                value.encode(writer);
+            }
+            None => writer.byte(0),
+        }
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        match reader.byte()? {
+            0 => Some(None),
+            1 => Some(Some(T::decode(reader)?)),
+            _ => None,
+        }
+    }
+}
+
+impl<'a, T: Encode<'a>> Encode<'a> for Vec<T> {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        writer.varint(self.len() as u64);
+        for value in self {
+            value.encode(writer);
+        }
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        let len = reader.varint()? as usize;
+        // every value takes at least one byte
+        let mut values = Vec::with_capacity(len.min(reader.data.len()));
+        for _ in 0..len {
+            values.push(T::decode(reader)?);
+        }
+        Some(values)
+    }
+}
+
+impl<'a, A: Encode<'a>, B: Encode<'a>> Encode<'a> for (A, B) {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        self.0.encode(writer);
+        self.1.encode(writer);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        Some((A::decode(reader)?, B::decode(reader)?))
+    }
+}
+
+impl<'a, A: Encode<'a>, B: Encode<'a>, C: Encode<'a>> Encode<'a> for (A, B, C) {
+    fn encode(&self, writer: &mut CacheWriter<'a>) {
+        self.0.encode(writer);
+        self.1.encode(writer);
+        self.2.encode(writer);
+    }
+
+    fn decode(reader: &mut CacheReader<'a>) -> Option<Self> {
+        Some((A::decode(reader)?, B::decode(reader)?, C::decode(reader)?))
+    }
+}
+
+#[test]
+fn test_cache_replay() {
+    use crate::{parse_with_options, LogHandler, TimeOrigin};
+
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let mut cache = Vec::new();
+    write_cache(&log, &mut cache).unwrap();
+    assert!(cache.len() < log.len() / 4);
+
+    let cache = read_cache(&cache).unwrap();
+    let events = LineSplit::new(&log)
+        .filter(|line| crate::RawEvent::parse(line).is_ok())
+        .count();
+    assert_eq!(events, cache.events().count());
+
+    let options = ParseOptions {
+        skip_warmup: true,
+        live_only: true,
+        time_origin: TimeOrigin::FirstRound,
+        ..ParseOptions::default()
+    };
+    for options in [ParseOptions::default(), options] {
+        let expected = parse_with_options::<LogHandler>(&log, &options).unwrap();
+        let replayed = cache.replay_with_options::<LogHandler>(&options).unwrap();
+        assert!(expected == replayed);
+    }
+}
+
+#[test]
+fn test_cache_errors() {
+    let log = concat!(
+        "L 08/06/2018 - 21:13:57: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo<3><[U:1:4]><Blue>\" (damage \"abc\")\n",
+        "L 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" changed role to \"scout\"\n",
+    );
+    assert!(write_cache(log, Vec::new()).is_err());
+
+    let mut cache = Vec::new();
+    write_cache(&log[..log.len() / 2], &mut cache).unwrap();
+    cache[4] = VERSION + 1;
+    assert!(matches!(read_cache(&cache), Err(Error::CacheVersion(_))));
+    assert!(matches!(read_cache(b"TFLC"), Err(Error::MalformedCache)));
+}
+
+#[test]
+fn test_cache_combined_log() {
+    use crate::{parse, LogHandler};
+
+    let log = concat!(
+        "L 08/06/2018 - 21:13:57: \"makxbi<27><[U:1:40364391]><Red>\" killed \"foo<3><[U:1:4]><Blue>\" with \"scatterL 08/06/2018 - 22:00:00: Log file started\n",
+        "L 08/06/2018 - 22:00:01: \"makxbi<27><[U:1:40364391]><Red>\" changed role to \"scout\"\n",
+        "L 08/06/2018 - 22:00:02: \"makxbi<27><[U:1:40364391]><Red>\" killed \"foo<3><[U:1:4]><Blue>\" with \"scattergun\" (attacker_position \"1 2 3\") (victim_position \"4 5 6\")\n",
+    );
+    let expected = parse(log).unwrap();
+    let mut cache = Vec::new();
+    write_cache(log, &mut cache).unwrap();
+    let replayed = read_cache(&cache).unwrap().replay::<LogHandler>().unwrap();
+    assert!(expected == replayed);
+}
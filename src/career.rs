@@ -0,0 +1,265 @@
+//! Career statistics of players over an archive of logs
+//!
+//! The index is stored as json and can be updated when new logs are added to the archive.
+//! Logs are identified by a hash of their content, so a log that is copied or renamed within the archive
+//! is only counted once.
+
+use crate::bytes::log_start;
+use crate::module::{
+    ClassStats, ClassStatsHandler, HealSpread, MatchResultHandler, MedicStats, MedicStatsBuilder,
+    PlayerHandler, PlayerInfoHandler,
+};
+use crate::read::{content_hash, is_log};
+use crate::{parse_bytes_with_handler, read_log_bytes, Error, Merge, SteamId3, Team};
+use chrono::NaiveDateTime;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{self, File};
+use std::io::{BufReader, BufWriter, Write};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+crate::handler!(CareerHandler {
+    class_stats: ClassStatsHandler,
+    heal_spread: PlayerHandler::<HealSpread>,
+    medic_stats: PlayerHandler::<MedicStatsBuilder>,
+    info: PlayerInfoHandler,
+    result: MatchResultHandler,
+});
+
+/// Statistics of a player over all logs in the index
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct PlayerCareer {
+    /// The name of the player in their most recent log
+    pub name: String,
+    pub logs: u32,
+    pub wins: u32,
+    pub losses: u32,
+    pub draws: u32,
+    /// Start of the first log the player played in
+    pub first_match: Option<NaiveDateTime>,
+    /// Start of the most recent log the player played in
+    pub last_match: Option<NaiveDateTime>,
+    pub class_stats: ClassStats,
+    pub healing: u32,
+    pub medic_stats: MedicStats,
+}
+
+impl PlayerCareer {
+    pub fn kills(&self) -> u32 {
+        self.class_stats.kills.total()
+    }
+
+    pub fn deaths(&self) -> u32 {
+        self.class_stats.deaths.total()
+    }
+
+    pub fn assists(&self) -> u32 {
+        self.class_stats.assists.total()
+    }
+
+    pub fn damage(&self) -> u32 {
+        self.class_stats.damage.total()
+    }
+}
+
+/// The name of the most recent log is kept, regardless of the order the logs are merged in
+impl Merge for PlayerCareer {
+    fn merge(&mut self, other: Self) {
+        if other.last_match >= self.last_match {
+            self.name = other.name;
+        }
+        self.logs.merge(other.logs);
+        self.wins.merge(other.wins);
+        self.losses.merge(other.losses);
+        self.draws.merge(other.draws);
+        self.first_match = match (self.first_match, other.first_match) {
+            (Some(first), Some(other)) => Some(first.min(other)),
+            (first, other) => first.or(other),
+        };
+        self.last_match = self.last_match.max(other.last_match);
+        self.class_stats.merge(other.class_stats);
+        self.healing.merge(other.healing);
+        self.medic_stats.merge(other.medic_stats);
+    }
+}
+
+/// Logs added when updating the index from a directory
+#[derive(Debug, Default)]
+pub struct CareerUpdate {
+    /// Number of logs added to the index
+    pub added: usize,
+    /// Number of logs that were already in the index
+    pub skipped: usize,
+    /// Logs that failed to read or parse, these are retried on the next update
+    pub failed: Vec<(PathBuf, Error)>,
+}
+
+/// Index of player careers by steam id
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct CareerIndex {
+    /// Names of the logs by their content hash, a log that was copied or renamed has multiple names
+    logs: BTreeMap<String, BTreeSet<String>>,
+    players: BTreeMap<SteamId3, PlayerCareer>,
+}
+
+impl CareerIndex {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Load an index that was saved before
+    pub fn load(path: impl AsRef<Path>) -> Result<Self, Error> {
+        let reader = BufReader::new(File::open(path)?);
+        serde_json::from_reader(reader).map_err(Error::MalformedIndex)
+    }
+
+    /// Save the index as json
+    ///
+    /// The index is written to a temporary file first, so an existing index isn't lost if writing fails.
+    pub fn save(&self, path: impl AsRef<Path>) -> Result<(), Error> {
+        let path = path.as_ref();
+        let temp = path.with_extension("tmp");
+        let mut writer = BufWriter::new(File::create(&temp)?);
+        serde_json::to_writer(&mut writer, self).map_err(Error::Json)?;
+        writer.flush()?;
+        drop(writer);
+        fs::rename(temp, path)?;
+        Ok(())
+    }
+
+    /// Whether a log with this name is in the index
+    pub fn contains(&self, name: &str) -> bool {
+        self.logs.values().any(|names| names.contains(name))
+    }
+
+    /// Names of the logs in the index, including the names of logs with the same content
+    pub fn logs(&self) -> impl Iterator<Item = &str> {
+        self.logs.values().flatten().map(String::as_str)
+    }
+
+    pub fn players(&self) -> &BTreeMap<SteamId3, PlayerCareer> {
+        &self.players
+    }
+
+    pub fn player(&self, steam_id: &SteamId3) -> Option<&PlayerCareer> {
+        self.players.get(steam_id)
+    }
+
+    /// Add a log to the index, returns `false` if a log with the same content is already in the index
+    ///
+    /// Spectators are not included in the index.
+    pub fn add_log(&mut self, name: &str, log: &[u8]) -> Result<bool, Error> {
+        let hash = content_hash(log);
+        let added = !self.logs.contains_key(&hash);
+        if added {
+            let careers = log_careers(log)?;
+            Merge::merge(&mut self.players, careers);
+        }
+        // the name is kept for logs with known content, so they don't have to be read again on the next update
+        self.logs.entry(hash).or_default().insert(name.into());
+        Ok(added)
+    }
+
+    /// Add all logs in a directory that aren't in the index yet
+    ///
+    /// The directory is searched recursively for `.log` files, including compressed `.log.gz`, `.log.bz2` and `.log.zip` files.
+    pub fn update_dir(&mut self, path: impl AsRef<Path>) -> CareerUpdate {
+        let root = path.as_ref();
+        let mut update = CareerUpdate::default();
+        let known: BTreeSet<String> = self.logs().map(String::from).collect();
+        let paths = WalkDir::new(root)
+            .sort_by_file_name()
+            .into_iter()
+            .flatten()
+            .filter(|entry| entry.file_type().is_file() && is_log(entry.path()))
+            .map(|entry| entry.into_path());
+        for path in paths {
+            let name = path.strip_prefix(root).unwrap_or(&path).to_string_lossy();
+            let name = name.replace('\\', "/");
+            if known.contains(&name) {
+                update.skipped += 1;
+                continue;
+            }
+            match read_log_bytes(&path).and_then(|log| self.add_log(&name, &log)) {
+                Ok(true) => update.added += 1,
+                Ok(false) => update.skipped += 1,
+                Err(e) => update.failed.push((path, e)),
+            }
+        }
+        update
+    }
+}
+
+/// The careers of all players in a single log
+fn log_careers(log: &[u8]) -> Result<BTreeMap<SteamId3, PlayerCareer>, Error> {
+    let (global, players) = parse_bytes_with_handler::<CareerHandler>(log)?;
+    let date = log_start(log);
+    let winner = global.result.winner();
+
+    Ok(players
+        .into_iter()
+        .filter(|(_, player)| player.info.team != Team::Spectator)
+        .map(|(steam_id, player)| {
+            let team = player.info.team;
+            let career = PlayerCareer {
+                name: player.info.name,
+                logs: 1,
+                wins: (winner == Some(team)) as u32,
+                losses: matches!(winner, Some(winner) if winner != team) as u32,
+                draws: winner.is_none() as u32,
+                first_match: date,
+                last_match: date,
+                healing: player.heal_spread.total(),
+                class_stats: player.class_stats,
+                medic_stats: player.medic_stats,
+            };
+            (steam_id, career)
+        })
+        .collect())
+}
+
+#[test]
+fn test_career_index() {
+    use crate::parse_bytes;
+
+    let dir = std::env::temp_dir().join(format!("tf-log-parser-career-{}", std::process::id()));
+    fs::create_dir_all(&dir).unwrap();
+    fs::copy("tests/data/log_6s.log.gz", dir.join("log_6s.log.gz")).unwrap();
+
+    let mut index = CareerIndex::new();
+    let update = index.update_dir(&dir);
+    assert_eq!((1, 0), (update.added, update.skipped));
+    assert!(update.failed.is_empty());
+
+    // only new logs are added when updating, the same log under another name is only counted once
+    fs::copy("tests/data/log_6s.log.gz", dir.join("copy.log.gz")).unwrap();
+    let index_path = dir.join("index.json");
+    index.save(&index_path).unwrap();
+    let mut index = CareerIndex::load(&index_path).unwrap();
+    let update = index.update_dir(&dir);
+    assert_eq!((0, 2), (update.added, update.skipped));
+    assert_eq!(
+        vec!["copy.log.gz", "log_6s.log.gz"],
+        index.logs().collect::<Vec<_>>()
+    );
+    let update = index.update_dir(&dir);
+    assert_eq!((0, 2), (update.added, update.skipped));
+
+    let log = read_log_bytes("tests/data/log_6s.log.gz").unwrap();
+    let (_, players) = parse_bytes(&log).unwrap();
+    assert!(index.players().values().any(|player| player.wins > 0));
+    assert!(index.players().values().any(|player| player.losses > 0));
+    for (steam_id, career) in index.players() {
+        assert_eq!(1, career.logs);
+        assert_eq!(career.logs, career.wins + career.losses + career.draws);
+        assert!(career.first_match.is_some());
+        assert_eq!(career.first_match, career.last_match);
+        assert_eq!(
+            players[steam_id].class_stats.damage.total(),
+            career.damage()
+        );
+    }
+
+    fs::remove_dir_all(dir).unwrap();
+}
@@ -0,0 +1,216 @@
+use crate::parser::LogParser;
+use crate::{
+    first_event_time, Error, EventHandler, HandlerOutput, LineSplit, LogHandler, ParseOptions,
+    RawEvent, Result,
+};
+use chrono::{Duration, NaiveDateTime};
+use std::collections::HashSet;
+use std::convert::TryInto;
+
+/// Parse multiple logs of a single match, in order, as if they were one log
+///
+/// This can be used for matches that were split by a map change or server crash.
+/// Logs that were already combined into a single file can be parsed directly, see [`ParseOptions::combined_log_gap`].
+pub fn combine(logs: &[&str]) -> Result<HandlerOutput<LogHandler>, Error> {
+    combine_with_handler::<LogHandler>(logs)
+}
+
+pub fn combine_with_handler<Handler: EventHandler>(
+    logs: &[&str],
+) -> Result<HandlerOutput<Handler>, Error> {
+    combine_with_options::<Handler>(logs, &ParseOptions::default())
+}
+
+/// Parse multiple logs of a single match, in order, as if they were one log
+///
+/// The time between the logs is not counted towards the match time, so the match time of the first event
+/// of a log continues from the last event of the previous log.
+///
+/// Since logs are truncated when the server stops logging, the last line of every log is allowed to be truncated.
+/// Logs that overlap in time or that contain lines that are also in the previous log are rejected.
+pub fn combine_with_options<Handler: EventHandler>(
+    logs: &[&str],
+    options: &ParseOptions,
+) -> Result<HandlerOutput<Handler>, Error> {
+    let spans: Vec<_> = logs.iter().map(|log| LogSpan::new(log)).collect();
+
+    // gap between each log and the previous one, and the total gap before each log
+    let mut gaps = Vec::with_capacity(logs.len());
+    let mut total_gaps = Vec::with_capacity(logs.len());
+    let mut total_gap = Duration::zero();
+    let mut previous: Option<(&str, &LogSpan)> = None;
+    for (index, (log, span)) in logs.iter().zip(spans.iter()).enumerate() {
+        let gap = match (previous, span) {
+            (Some((previous_log, previous_span)), LogSpan::Events { first, .. }) => {
+                let LogSpan::Events { last, .. } = previous_span else {
+                    unreachable!("only logs with events are tracked as previous")
+                };
+                if first < last {
+                    return Err(Error::OverlappingLogs { index });
+                }
+                if first == last && has_duplicate_lines(previous_log, log, *first) {
+                    return Err(Error::DuplicateLines { index });
+                }
+                *first - *last
+            }
+            _ => Duration::zero(),
+        };
+        total_gap += gap;
+        gaps.push(gap);
+        total_gaps.push(total_gap);
+        if let LogSpan::Events { .. } = span {
+            previous = Some((log, span));
+        }
+    }
+
+    // look ahead for the time origin and tournament start, the origin is expressed in the time of the first log
+    let options = options.with_lookahead(|ty| {
+        logs.iter()
+            .zip(total_gaps.iter())
+            .find_map(|(log, gap)| Some(first_event_time(LineSplit::new(log), ty)? - *gap))
+    });
+
+    let mut parser = LogParser::<Handler>::new(&options);
+    for (index, (log, gap)) in logs.iter().zip(gaps).enumerate() {
+        if index > 0 {
+            parser.next_log(gap);
+        }
+        for line in LineSplit::new(log) {
+            parser.handle_line(line, |e, line| e.located(log, line))?;
+        }
+    }
+    Ok(parser.finish())
+}
+
+/// The time of the first and last event in a log
+enum LogSpan {
+    Empty,
+    Events {
+        first: NaiveDateTime,
+        last: NaiveDateTime,
+    },
+}
+
+impl LogSpan {
+    fn new(log: &str) -> Self {
+        let mut dates = LineSplit::new(log)
+            .flat_map(RawEvent::parse)
+            .filter_map(|event| event.date.try_into().ok());
+        match dates.next() {
+            Some(first) => LogSpan::Events {
+                first,
+                last: dates.last().unwrap_or(first),
+            },
+            None => LogSpan::Empty,
+        }
+    }
+}
+
+/// Check if the lines at the start of `next` also appear at the end of `previous`
+///
+/// Only the lines happening at `date`, the end of `previous` and start of `next`, are compared.
+fn has_duplicate_lines(previous: &str, next: &str, date: NaiveDateTime) -> bool {
+    let lines_at = |log| {
+        LineSplit::new(log).filter(move |line| {
+            RawEvent::parse(line)
+                .ok()
+                .and_then(|event| event.date.try_into().ok())
+                == Some(date)
+        })
+    };
+    let previous_lines: HashSet<&str> = lines_at(previous).collect();
+    lines_at(next).any(|line| previous_lines.contains(line))
+}
+
+#[test]
+fn test_combine_split_log() {
+    use crate::module::ChatMessages;
+
+    let log = crate::read_log("tests/data/log_2892242.log.gz").unwrap();
+    let (full, full_players) = crate::parse(&log).unwrap();
+
+    // split the log halfway, truncating the last line of the first part
+    let split = log[log.len() / 2..].find("\nL ").unwrap() + log.len() / 2;
+    let first = &log[..split - 10];
+    let second = &log[split + 1..];
+    let (combined, combined_players) = combine(&[first, second]).unwrap();
+
+    assert_eq!(full_players.len(), combined_players.len());
+    assert_eq!(full.chat.len(), combined.chat.len());
+
+    // move the second part a week ahead, the match time should continue from the first part
+    let shifted: String = second
+        .split('\n')
+        .map(|line| {
+            let date = line
+                .get(2..23)
+                .and_then(|date| NaiveDateTime::parse_from_str(date, "%m/%d/%Y - %H:%M:%S").ok());
+            match date {
+                Some(date) if line.starts_with("L ") => format!(
+                    "L {}{}",
+                    (date + Duration::days(7)).format("%m/%d/%Y - %H:%M:%S"),
+                    &line[23..]
+                ),
+                _ => line.to_string(),
+            }
+        })
+        .collect::<Vec<_>>()
+        .join("\n");
+    let (chat, _) = combine_with_handler::<ChatMessages>(&[first, &shifted]).unwrap();
+    let last = combined.chat.last().unwrap();
+    assert_eq!(last.time, chat.last().unwrap().time);
+    assert!(last.time > 60);
+}
+
+#[test]
+fn test_combined_single_file() {
+    let log = crate::read_log("tests/data/log_2892242.log.gz").unwrap();
+    let (expected, _) = crate::parse(&log).unwrap();
+
+    // a truncated damage line that runs into the next line
+    let damage = log.find(r#"(damage ""#).unwrap() + r#"(damage "7"#.len();
+    let end = damage + log[damage..].find('\n').unwrap();
+    let glued = format!("{}{}", &log[..damage], &log[end + 1..]);
+    let (combined, _) = crate::parse(&glued).unwrap();
+    assert_eq!(expected.chat.len(), combined.chat.len());
+
+    // a truncated line followed by a log that starts a day later
+    // truncated in the middle of the kill target
+    let kill = log.find(r#"" killed ""#).unwrap() + 12;
+    let split = log[kill..].find("\nL ").unwrap() + kill;
+    let next_day = log[split + 1..].replace("L 04/11/2021", "L 04/12/2021");
+    let appended = format!("{}\n{}", &log[..kill], next_day);
+    assert!(crate::parse(&appended).is_ok());
+    let options = ParseOptions {
+        combined_log_gap: None,
+        ..ParseOptions::default()
+    };
+    assert!(crate::parse_with_options::<LogHandler>(&appended, &options).is_err());
+}
+
+#[test]
+fn test_combine_reject_overlap() {
+    let log = crate::read_log("tests/data/log_2892242.log.gz").unwrap();
+    let split = log[log.len() / 2..].find("\nL ").unwrap() + log.len() / 2;
+
+    assert!(matches!(
+        combine(&[&log, &log]),
+        Err(Error::OverlappingLogs { index: 1 })
+    ));
+    assert!(matches!(
+        combine(&[&log[..split], &log[..split]]),
+        Err(Error::OverlappingLogs { index: 1 })
+    ));
+
+    let single = concat!(
+        "L 08/06/2018 - 21:13:57: \"makxbi<27><[U:1:40364391]><Red>\" say \"gl\"\n",
+        "L 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" say \"hf\"\n",
+    );
+    let next = "L 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" say \"hf\"\n";
+    assert!(matches!(
+        combine(&[single, next]),
+        Err(Error::DuplicateLines { index: 1 })
+    ));
+    let next = "L 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" say \"gg\"\n";
+    assert_eq!(3, combine(&[single, next]).unwrap().0.chat.len());
+}
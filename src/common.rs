@@ -1,20 +1,22 @@
 use crate::event::EventFieldFromStr;
 use crate::parsing::find_between_end;
 use crate::raw_event::{split_player_subject, RawSubject};
-use crate::Result;
+use crate::{Merge, Result};
 use ahash::AHasher;
 use enum_iterator::{all, Sequence};
+use serde::de::{Error as _, MapAccess, Visitor};
 use serde::ser::SerializeMap;
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::cmp::Ordering;
 use std::convert::TryFrom;
 use std::fmt::{Debug, Display, Formatter};
 use std::hash::{Hash, Hasher};
+use std::marker::PhantomData;
 use std::ops::{Index, IndexMut};
 use std::str::FromStr;
 use steamid_ng::{AccountType, Instance, SteamID, Universe};
 
-#[derive(Serialize, Copy, Clone, Eq, PartialEq, Debug, Ord, PartialOrd, Hash)]
+#[derive(Serialize, Deserialize, Copy, Clone, Eq, PartialEq, Debug, Ord, PartialOrd, Hash)]
 pub enum Team {
     Red,
     Blue,
@@ -58,7 +60,9 @@ impl FromStr for Team {
     }
 }
 
-#[derive(Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Sequence, Serialize)]
+#[derive(
+    Debug, Copy, Clone, Eq, PartialEq, Ord, PartialOrd, Hash, Sequence, Serialize, Deserialize,
+)]
 #[serde(rename_all = "lowercase")]
 pub enum Class {
     Scout,
@@ -119,6 +123,28 @@ impl FromStr for Class {
 
 pub struct ClassMap<T>([T; 10]);
 
+impl<T> ClassMap<T> {
+    /// Iterate over the values for all classes
+    pub fn iter(&self) -> impl Iterator<Item = (Class, &T)> {
+        all::<Class>().map(move |class| (class, &self[class]))
+    }
+}
+
+impl<T: Copy + Into<u32>> ClassMap<T> {
+    /// The sum of the values for all classes
+    pub fn total(&self) -> u32 {
+        self.0.iter().map(|value| (*value).into()).sum()
+    }
+}
+
+impl<T: Merge> Merge for ClassMap<T> {
+    fn merge(&mut self, other: Self) {
+        for (value, other) in self.0.iter_mut().zip(other.0) {
+            value.merge(other);
+        }
+    }
+}
+
 impl<T> Index<Class> for ClassMap<T> {
     type Output = T;
 
@@ -174,6 +200,42 @@ where
     }
 }
 
+impl<'de, T> Deserialize<'de> for ClassMap<T>
+where
+    T: Deserialize<'de> + Default,
+{
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        struct ClassMapVisitor<T>(PhantomData<T>);
+
+        impl<'de, T> Visitor<'de> for ClassMapVisitor<T>
+        where
+            T: Deserialize<'de> + Default,
+        {
+            type Value = ClassMap<T>;
+
+            fn expecting(&self, formatter: &mut Formatter<'_>) -> std::fmt::Result {
+                formatter.write_str("a map of classes")
+            }
+
+            fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+            where
+                A: MapAccess<'de>,
+            {
+                let mut class_map = ClassMap::default();
+                while let Some((class, value)) = map.next_entry::<Class, T>()? {
+                    class_map[class] = value;
+                }
+                Ok(class_map)
+            }
+        }
+
+        deserializer.deserialize_map(ClassMapVisitor(PhantomData))
+    }
+}
+
 impl<T: Default> Default for ClassMap<T> {
     fn default() -> Self {
         ClassMap(<[T; 10]>::default())
@@ -186,6 +248,12 @@ impl<T: Debug> Debug for ClassMap<T> {
     }
 }
 
+impl<T: Clone> Clone for ClassMap<T> {
+    fn clone(&self) -> Self {
+        ClassMap(self.0.clone())
+    }
+}
+
 impl<T: PartialEq> PartialEq for ClassMap<T> {
     fn eq(&self, other: &Self) -> bool {
         self.0.eq(&other.0)
@@ -199,7 +267,7 @@ impl<T> From<ClassMap<T>> for [T; 10] {
 }
 
 /// Optimized subject id
-#[derive(Copy, Clone, Eq, PartialEq, Debug, Ord, PartialOrd, Hash)]
+#[derive(Copy, Clone, Eq, PartialEq, Debug, Ord, PartialOrd, Hash, Serialize, Deserialize)]
 pub enum SubjectId {
     Player(u32),
     Team(Team),
@@ -222,6 +290,19 @@ impl SubjectId {
             _ => None,
         }
     }
+
+    /// Placeholder steam id for bots, formatted as `[I:0:<user id>]`
+    pub fn bot_steam_id(&self) -> Option<SteamID> {
+        match self {
+            SubjectId::Bot(user_id) => Some(SteamID::new(
+                *user_id as u32,
+                Instance::All,
+                AccountType::Invalid,
+                Universe::Invalid,
+            )),
+            _ => None,
+        }
+    }
 }
 
 fn hash_player_str(s: &str) -> u32 {
@@ -342,7 +423,7 @@ impl TryFrom<&RawSubject<'_>> for SubjectData {
 }
 
 /// Steam id formatted as steamid3 when serialized
-#[derive(Debug, Hash, Eq, PartialEq)]
+#[derive(Debug, Clone, Copy, Hash, Eq, PartialEq)]
 pub struct SteamId3(pub SteamID);
 
 impl PartialOrd<Self> for SteamId3 {
@@ -371,3 +452,15 @@ impl Serialize for SteamId3 {
         self.0.steam3().serialize(serializer)
     }
 }
+
+impl<'de> Deserialize<'de> for SteamId3 {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let steam3 = String::deserialize(deserializer)?;
+        SteamID::from_steam3(&steam3)
+            .map(SteamId3)
+            .map_err(|_| D::Error::custom(format!("invalid steam3 id: {}", steam3)))
+    }
+}
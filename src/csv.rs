@@ -0,0 +1,269 @@
+//! Export per-player outputs as flat CSV tables
+//!
+//! Every row starts with the log it's from and the steam id of the player, so the rows of many logs
+//! can be appended into a single file. Outputs that are split by class get one row per player and class.
+
+use crate::module::{ClassStats, HealSpread, MedicStats, PlayerInfo};
+use crate::{Class, Error, LogHandlerPerSubjectOutput, SteamId3, Team};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::marker::PhantomData;
+
+/// Output that can be written as rows of a CSV table
+pub trait CsvRows {
+    type Row: Serialize;
+
+    /// The rows for a single player
+    fn rows(&self, log: &str, steam_id: &SteamId3) -> Vec<Self::Row>;
+}
+
+/// Writes the per-player output of logs as CSV
+///
+/// The header is written before the first row, the columns are the fields of the `Row` of the output.
+pub struct CsvWriter<W: Write, T: CsvRows> {
+    writer: ::csv::Writer<W>,
+    output: PhantomData<T>,
+}
+
+impl<W: Write, T: CsvRows> CsvWriter<W, T> {
+    pub fn new(writer: W) -> Self {
+        CsvWriter {
+            writer: ::csv::Writer::from_writer(writer),
+            output: PhantomData,
+        }
+    }
+
+    /// Write the rows for all players of a log
+    ///
+    /// `log` is used for the `log` column and can be the log id or the filename.
+    pub fn write_log(&mut self, log: &str, players: &BTreeMap<SteamId3, T>) -> Result<(), Error> {
+        for (steam_id, output) in players {
+            for row in output.rows(log, steam_id) {
+                self.writer.serialize(row)?;
+            }
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> Result<(), Error> {
+        Ok(self.writer.flush()?)
+    }
+
+    /// Flush the writer and return the underlying writer
+    pub fn into_inner(self) -> Result<W, Error> {
+        self.writer
+            .into_inner()
+            .map_err(|e| Error::Io(e.into_error()))
+    }
+}
+
+/// Totals for a single player from the [`LogHandler`](crate::LogHandler) output
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct SummaryRow {
+    pub log: String,
+    pub steam_id: SteamId3,
+    pub kills: u32,
+    pub assists: u32,
+    pub deaths: u32,
+    pub damage: u32,
+    pub healing: u32,
+    pub charges: u32,
+    pub drops: u32,
+}
+
+impl SummaryRow {
+    pub fn new(log: &str, steam_id: &SteamId3, output: &LogHandlerPerSubjectOutput) -> Self {
+        SummaryRow {
+            log: log.into(),
+            steam_id: *steam_id,
+            kills: output.class_stats.kills.total(),
+            assists: output.class_stats.assists.total(),
+            deaths: output.class_stats.deaths.total(),
+            damage: output.class_stats.damage.total(),
+            healing: output.heal_spread.total(),
+            charges: output.medic_stats.charge_count,
+            drops: output.medic_stats.drops,
+        }
+    }
+}
+
+impl CsvRows for LogHandlerPerSubjectOutput {
+    type Row = SummaryRow;
+
+    fn rows(&self, log: &str, steam_id: &SteamId3) -> Vec<Self::Row> {
+        vec![SummaryRow::new(log, steam_id, self)]
+    }
+}
+
+/// Stats for a single player against a single class, classes without any stats are skipped
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct ClassStatsRow {
+    pub log: String,
+    pub steam_id: SteamId3,
+    pub class: Class,
+    pub kills: u32,
+    pub assists: u32,
+    pub deaths: u32,
+    pub damage: u32,
+}
+
+impl CsvRows for ClassStats {
+    type Row = ClassStatsRow;
+
+    fn rows(&self, log: &str, steam_id: &SteamId3) -> Vec<Self::Row> {
+        self.kills
+            .iter()
+            .map(|(class, kills)| ClassStatsRow {
+                log: log.into(),
+                steam_id: *steam_id,
+                class,
+                kills: *kills,
+                assists: self.assists[class],
+                deaths: self.deaths[class],
+                damage: self.damage[class],
+            })
+            .filter(|row| row.kills > 0 || row.assists > 0 || row.deaths > 0 || row.damage > 0)
+            .collect()
+    }
+}
+
+/// Medic stats for a single player, players that didn't play medic are skipped
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MedicStatsRow {
+    pub log: String,
+    pub steam_id: SteamId3,
+    pub advantages_lost: u32,
+    pub biggest_advantage_lost: f32,
+    pub near_full_charge_death: u32,
+    pub deaths_after_uber: u32,
+    pub avg_time_before_healing: f32,
+    pub avg_time_to_build: f32,
+    pub avg_time_to_use: f32,
+    pub avg_uber_length: f32,
+    pub charge_count: u32,
+    pub drops: u32,
+}
+
+impl CsvRows for MedicStats {
+    type Row = MedicStatsRow;
+
+    fn rows(&self, log: &str, steam_id: &SteamId3) -> Vec<Self::Row> {
+        if *self == MedicStats::default() {
+            return Vec::new();
+        }
+        vec![MedicStatsRow {
+            log: log.into(),
+            steam_id: *steam_id,
+            advantages_lost: self.advantages_lost,
+            biggest_advantage_lost: self.biggest_advantage_lost,
+            near_full_charge_death: self.near_full_charge_death,
+            deaths_after_uber: self.deaths_after_uber,
+            avg_time_before_healing: self.avg_time_before_healing(),
+            avg_time_to_build: self.avg_time_to_build(),
+            avg_time_to_use: self.avg_time_to_use(),
+            avg_uber_length: self.avg_uber_length(),
+            charge_count: self.charge_count,
+            drops: self.drops,
+        }]
+    }
+}
+
+/// Healing from a single player to a single target
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct HealSpreadRow {
+    pub log: String,
+    pub steam_id: SteamId3,
+    pub target: SteamId3,
+    pub healing: u32,
+}
+
+impl CsvRows for HealSpread {
+    type Row = HealSpreadRow;
+
+    fn rows(&self, log: &str, steam_id: &SteamId3) -> Vec<Self::Row> {
+        self.iter()
+            .map(|(target, healing)| HealSpreadRow {
+                log: log.into(),
+                steam_id: *steam_id,
+                target: *target,
+                healing: *healing,
+            })
+            .collect()
+    }
+}
+
+/// Name, team and played classes of a player, with the classes separated by spaces
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct PlayerInfoRow {
+    pub log: String,
+    pub steam_id: SteamId3,
+    pub name: String,
+    pub team: Team,
+    pub classes: String,
+}
+
+impl CsvRows for PlayerInfo {
+    type Row = PlayerInfoRow;
+
+    fn rows(&self, log: &str, steam_id: &SteamId3) -> Vec<Self::Row> {
+        let classes: Vec<_> = self.classes.iter().map(|class| class.as_str()).collect();
+        vec![PlayerInfoRow {
+            log: log.into(),
+            steam_id: *steam_id,
+            name: self.name.clone(),
+            team: self.team,
+            classes: classes.join(" "),
+        }]
+    }
+}
+
+#[test]
+fn test_csv_rows() {
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (_, players) = crate::parse(&log).unwrap();
+
+    let mut writer = CsvWriter::new(Vec::new());
+    writer.write_log("2892242", &players).unwrap();
+    writer.write_log("again", &players).unwrap();
+    let summary = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    let mut lines = summary.lines();
+    assert_eq!(
+        Some("log,steam_id,kills,assists,deaths,damage,healing,charges,drops"),
+        lines.next()
+    );
+    assert_eq!(players.len() * 2, lines.count());
+
+    let class_stats: BTreeMap<_, _> = players
+        .into_iter()
+        .map(|(steam_id, player)| (steam_id, player.class_stats))
+        .collect();
+    let mut writer = CsvWriter::new(Vec::new());
+    writer.write_log("2892242", &class_stats).unwrap();
+    let classes = String::from_utf8(writer.into_inner().unwrap()).unwrap();
+    let mut lines = classes.lines();
+    assert_eq!(
+        Some("log,steam_id,class,kills,assists,deaths,damage"),
+        lines.next()
+    );
+    let (steam_id, stats) = class_stats
+        .iter()
+        .find(|(steam_id, stats)| !stats.rows("", steam_id).is_empty())
+        .unwrap();
+    let row = &stats.rows("2892242", steam_id)[0];
+    let expected = format!(
+        "2892242,{},{},{},{},{},{}",
+        steam_id.0.steam3(),
+        row.class.as_str(),
+        row.kills,
+        row.assists,
+        row.deaths,
+        row.damage
+    );
+    assert_eq!(Some(expected.as_str()), lines.next());
+    let rows: usize = class_stats
+        .iter()
+        .map(|(steam_id, stats)| stats.rows("2892242", steam_id).len())
+        .sum();
+    assert_eq!(rows, lines.count() + 1);
+}
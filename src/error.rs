@@ -1,6 +1,11 @@
 use crate::event::GameEventError;
 use crate::SubjectError;
+use memchr::memchr_iter;
+use miette::{
+    Diagnostic, LabeledSpan, MietteError, MietteSpanContents, SourceCode, SourceSpan, SpanContents,
+};
 use std::num::ParseIntError;
+use std::ops::Range;
 use thiserror::Error;
 
 #[derive(Error, Debug)]
@@ -15,6 +20,77 @@ pub enum Error {
     Subject(Box<SubjectError>),
     #[error("{0}")]
     MalformedEvent(Box<GameEventError>),
+    #[error("{0}")]
+    Located(Box<LocatedError>),
+    #[error("Failed to read log: {0}")]
+    Io(#[from] std::io::Error),
+    #[cfg(feature = "csv")]
+    #[error("Failed to write csv: {0}")]
+    Csv(#[from] ::csv::Error),
+    #[cfg(feature = "sqlite")]
+    #[error("Database error: {0}")]
+    Sqlite(#[from] rusqlite::Error),
+    #[cfg(feature = "arrow")]
+    #[error("Failed to build record batch: {0}")]
+    Arrow(#[from] arrow_schema::ArrowError),
+    #[cfg(feature = "arrow")]
+    #[error("Failed to write parquet: {0}")]
+    Parquet(#[from] parquet::errors::ParquetError),
+    #[error("Failed to write json: {0}")]
+    Json(serde_json::Error),
+    #[error("Malformed event cache")]
+    MalformedCache,
+    #[error("Unsupported event cache version {0}")]
+    CacheVersion(u8),
+    #[error("Invalid rating tau {0}, tau must be larger than 0")]
+    InvalidTau(f64),
+    #[error("Malformed career index: {0}")]
+    MalformedIndex(serde_json::Error),
+    #[error("Panicked while parsing log: {0}")]
+    Panic(String),
+    #[error("Log {index} starts before the end of the previous log")]
+    OverlappingLogs { index: usize },
+    #[error("Log {index} contains lines that are also in the previous log")]
+    DuplicateLines { index: usize },
+}
+
+impl Error {
+    /// Attach the location of `line` inside `log` to the error
+    ///
+    /// `line` is expected to be a slice of `log`, errors for lines from outside the log are left as-is
+    pub(crate) fn located(self, log: &str, line: &str) -> Self {
+        match LineLocation::new(log, line) {
+            Some(location) => {
+                let text = &log[location.span.clone()];
+                self.located_at(text, location)
+            }
+            None => self,
+        }
+    }
+
+    /// Attach a location to the error, `text` is the full text of the line including the leading `L `
+    pub(crate) fn located_at(self, text: &str, location: LineLocation) -> Self {
+        match self {
+            Error::Incomplete | Error::Skip | Error::Located(_) => self,
+            error => Error::Located(Box::new(LocatedError::new(error, text, location))),
+        }
+    }
+
+    /// The underlying error, without location information
+    pub fn inner(&self) -> &Error {
+        match self {
+            Error::Located(located) => &located.error,
+            error => error,
+        }
+    }
+
+    /// The location in the log where the error occurred, if known
+    pub fn location(&self) -> Option<&LineLocation> {
+        match self {
+            Error::Located(located) => Some(&located.location),
+            _ => None,
+        }
+    }
 }
 
 impl From<SubjectError> for Error {
@@ -35,6 +111,22 @@ impl From<ParseIntError> for Error {
     }
 }
 
+impl Diagnostic for Error {
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        match self {
+            Error::Located(located) => located.source_code(),
+            _ => None,
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        match self {
+            Error::Located(located) => located.labels(),
+            _ => None,
+        }
+    }
+}
+
 pub type Result<O, E = Error> = std::result::Result<O, E>;
 
 #[doc(hidden)]
@@ -52,3 +144,143 @@ impl<T> ResultExt for Result<T> {
         })
     }
 }
+
+/// Location of a line in a log file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LineLocation {
+    /// Line number of the start of the line, starting at 1
+    pub line: usize,
+    /// Byte range of the line in the log, including the leading `L `
+    pub span: Range<usize>,
+}
+
+impl LineLocation {
+    /// Find the location of `line` inside `log`, returns `None` if `line` isn't a slice of `log`
+    pub fn new(log: &str, line: &str) -> Option<Self> {
+        let start = (line.as_ptr() as usize).checked_sub(log.as_ptr() as usize)?;
+        let end = start + line.len();
+        log.get(start..end)?;
+        let start = if log[..start].ends_with("L ") {
+            start - 2
+        } else {
+            start
+        };
+        Some(LineLocation {
+            line: memchr_iter(b'\n', &log.as_bytes()[..start]).count() + 1,
+            span: start..end,
+        })
+    }
+}
+
+/// Error with the location in the log where it occurred
+#[derive(Error, Debug)]
+#[error("{error} on line {}", location.line)]
+pub struct LocatedError {
+    pub error: Error,
+    pub location: LineLocation,
+    snippet: LogSnippet,
+    label: SourceSpan,
+}
+
+impl LocatedError {
+    fn new(error: Error, text: &str, location: LineLocation) -> Self {
+        // point at the event parameters if we know which part of the line failed to parse
+        let label_start = match &error {
+            Error::MalformedEvent(event_error) => match event_error.as_ref() {
+                GameEventError::Error { params, .. } if text.ends_with(params.as_str()) => {
+                    text.len() - params.len()
+                }
+                _ => 0,
+            },
+            _ => 0,
+        };
+        LocatedError {
+            snippet: LogSnippet {
+                line: location.line - 1,
+                text: text.to_string(),
+            },
+            label: (label_start..text.len()).into(),
+            error,
+            location,
+        }
+    }
+
+    fn label_text(&self) -> &'static str {
+        match &self.error {
+            Error::MalformedEvent(event_error) => match event_error.as_ref() {
+                GameEventError::Error { .. } => "failed to parse event parameters",
+                GameEventError::Truncated(_) => "truncated event body",
+            },
+            Error::Subject(_) => "invalid subject",
+            _ => "malformed line",
+        }
+    }
+}
+
+impl Diagnostic for LocatedError {
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        Some(&self.snippet)
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        Some(Box::new(std::iter::once(LabeledSpan::new_with_span(
+            Some(self.label_text().to_string()),
+            self.label,
+        ))))
+    }
+}
+
+/// The text of a single line from the log, reported with its original line number
+#[derive(Debug)]
+struct LogSnippet {
+    line: usize,
+    text: String,
+}
+
+impl SourceCode for LogSnippet {
+    fn read_span<'a>(
+        &'a self,
+        span: &SourceSpan,
+        context_lines_before: usize,
+        context_lines_after: usize,
+    ) -> Result<Box<dyn SpanContents<'a> + 'a>, MietteError> {
+        let contents = self
+            .text
+            .read_span(span, context_lines_before, context_lines_after)?;
+        Ok(Box::new(MietteSpanContents::new(
+            contents.data(),
+            *contents.span(),
+            contents.line() + self.line,
+            contents.column(),
+            contents.line_count(),
+        )))
+    }
+}
+
+#[test]
+fn test_line_location() {
+    let log = "L 08/06/2018 - 21:13:57: foo\nL 08/06/2018 - 21:13:58: bar\nbaz\nL 08/06/2018 - 21:13:59: qux";
+    let lines: Vec<_> = crate::LineSplit::new(log).collect();
+    assert_eq!(
+        Some(LineLocation {
+            line: 1,
+            span: 0..28
+        }),
+        LineLocation::new(log, lines[0])
+    );
+    assert_eq!(
+        Some(LineLocation {
+            line: 2,
+            span: 29..61
+        }),
+        LineLocation::new(log, lines[1])
+    );
+    assert_eq!(
+        Some(LineLocation {
+            line: 4,
+            span: 62..90
+        }),
+        LineLocation::new(log, lines[2])
+    );
+    assert_eq!(None, LineLocation::new(log, "foo"));
+}
@@ -1,29 +1,30 @@
 use crate::event::{param_parse_with, parse_field, ParamIter};
 use crate::raw_event::RawSubject;
-use crate::{Error, Event, Result};
+use crate::{Encode, Error, Event, IntoOwned, Result};
+use serde::{Deserialize, Serialize};
 
 use crate::parsing::{skip, take_until};
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct RoundWinEvent<'a> {
     #[event(name = "winner")]
     pub team: Option<&'a str>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct RoundLengthEvent {
     #[event(name = "seconds")]
     pub length: Option<f32>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct LogFileStartedEvent<'a> {
     pub file: Option<&'a str>,
     pub game: Option<&'a str>,
     pub version: Option<&'a str>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, IntoOwned, Encode)]
 pub struct TournamentModeStartedEvent<'a> {
     pub blue: &'a str,
     pub red: &'a str,
@@ -39,7 +40,7 @@ impl<'a> Event<'a> for TournamentModeStartedEvent<'a> {
     }
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct CaptureBlockedEvent<'a> {
     pub cp: Option<u8>,
     #[event(name = "cpname")]
@@ -47,7 +48,7 @@ pub struct CaptureBlockedEvent<'a> {
     pub position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug)]
+#[derive(Debug, IntoOwned, Encode)]
 pub struct PointCapturedEvent<'a> {
     pub cp: Option<u8>,
     pub cp_name: Option<&'a str>,
@@ -103,19 +104,19 @@ impl<'a> Event<'a> for PointCapturedEvent<'a> {
     }
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct CurrentScoreEvent {
     pub score: u8,
     #[event(name = "with")]
     pub players: u8,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct GameOverEvent<'a> {
     pub reason: &'a str,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct FinalScoreEvent {
     pub score: u8,
     #[event(name = "with")]
@@ -1,8 +1,9 @@
 use crate::event::{parse_field, ParamIter};
 use crate::raw_event::RawSubject;
-use crate::{Event, Result};
+use crate::{Encode, Event, IntoOwned, Result};
+use serde::{Deserialize, Serialize};
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct HealedEvent<'a> {
     #[event(name = "against")]
     pub target: Option<RawSubject<'a>>,
@@ -11,33 +12,33 @@ pub struct HealedEvent<'a> {
     pub amount: u32,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct ChargeDeployedEvent<'a> {
     pub medigun: Option<&'a str>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct ChargeEndedEvent {
     pub duration: Option<f32>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct AdvantageLostEvent {
     pub time: Option<f32>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct FirstHealEvent {
     pub time: Option<f32>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct MedicDeathEvent {
     #[event(name = "ubercharge")]
     pub charge: Option<u32>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct MedicDeathExEvent {
     pub charge_percentage: Option<u8>,
 }
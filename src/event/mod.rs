@@ -2,13 +2,19 @@ mod game;
 mod medic;
 mod player;
 
+use crate::common::{Class, Team};
 use crate::event::game::{RoundLengthEvent, RoundWinEvent};
 use crate::parsing::{skip, skip_matches, split_once, split_subject_end};
 use crate::raw_event::{against_subject_parser, RawSubject};
-use crate::{Error, Events, IResult, RawEvent, RawEventType, Result, SubjectId};
+use crate::{
+    Error, Events, IResult, MatchState, RawEvent, RawEventType, Result, SubjectError, SubjectId,
+};
+use chrono::NaiveDateTime;
 pub use game::*;
 pub use medic::*;
 pub use player::*;
+use serde::{Deserialize, Serialize};
+use std::convert::TryInto;
 use std::net::SocketAddr;
 use std::num::NonZeroU32;
 use std::str::FromStr;
@@ -51,6 +57,31 @@ fn parse_event<'a, T: Event<'a>>(input: &'a str) -> Result<T> {
 pub struct EventMeta {
     pub time: u32,
     pub subject: SubjectId,
+    pub state: MatchState,
+}
+
+/// An event that doesn't borrow from the log, with the time and subject of the event resolved
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct OwnedEvent {
+    pub date: NaiveDateTime,
+    pub subject: SubjectId,
+    #[serde(flatten)]
+    pub event: OwnedGameEvent,
+}
+
+impl OwnedEvent {
+    pub fn new(raw: &RawEvent, event: GameEvent) -> Result<Self> {
+        Ok(OwnedEvent {
+            date: raw.date.try_into()?,
+            subject: raw.subject.id()?,
+            event: event.into_owned()?,
+        })
+    }
+
+    /// Parse a raw event into an owned event
+    pub fn parse(raw: &RawEvent) -> Result<Self> {
+        OwnedEvent::new(raw, GameEvent::parse(raw)?)
+    }
 }
 
 #[derive(Debug, Events)]
@@ -104,7 +135,7 @@ pub enum GameEvent<'a> {
     LogFileClosed,
 }
 
-#[derive(Debug)]
+#[derive(Debug, crate::IntoOwned, crate::Encode)]
 pub struct UnparsedEvent<'a> {
     pub params: &'a str,
 }
@@ -115,6 +146,81 @@ impl<'a> Event<'a> for UnparsedEvent<'a> {
     }
 }
 
+/// Conversion of events into a version that doesn't borrow from the log
+pub trait IntoOwned {
+    type Owned;
+
+    fn into_owned(self) -> Result<Self::Owned, SubjectError>;
+}
+
+macro_rules! impl_owned {
+    ($($ty:ty),*) => {
+        $(
+            impl IntoOwned for $ty {
+                type Owned = Self;
+
+                fn into_owned(self) -> Result<Self, SubjectError> {
+                    Ok(self)
+                }
+            }
+        )*
+    };
+}
+
+impl_owned!(u8, u16, u32, i32, i64, f32, NonZeroU32, SocketAddr, Class, Team);
+
+impl IntoOwned for &str {
+    type Owned = String;
+
+    fn into_owned(self) -> Result<String, SubjectError> {
+        Ok(self.to_string())
+    }
+}
+
+impl IntoOwned for RawSubject<'_> {
+    type Owned = SubjectId;
+
+    fn into_owned(self) -> Result<SubjectId, SubjectError> {
+        self.id()
+    }
+}
+
+impl<T: IntoOwned> IntoOwned for Option<T> {
+    type Owned = Option<T::Owned>;
+
+    fn into_owned(self) -> Result<Self::Owned, SubjectError> {
+        self.map(T::into_owned).transpose()
+    }
+}
+
+impl<T: IntoOwned> IntoOwned for Vec<T> {
+    type Owned = Vec<T::Owned>;
+
+    fn into_owned(self) -> Result<Self::Owned, SubjectError> {
+        self.into_iter().map(T::into_owned).collect()
+    }
+}
+
+impl<A: IntoOwned, B: IntoOwned> IntoOwned for (A, B) {
+    type Owned = (A::Owned, B::Owned);
+
+    fn into_owned(self) -> Result<Self::Owned, SubjectError> {
+        Ok((self.0.into_owned()?, self.1.into_owned()?))
+    }
+}
+
+impl<A: IntoOwned, B: IntoOwned, C: IntoOwned> IntoOwned for (A, B, C) {
+    type Owned = (A::Owned, B::Owned, C::Owned);
+
+    fn into_owned(self) -> Result<Self::Owned, SubjectError> {
+        Ok((
+            self.0.into_owned()?,
+            self.1.into_owned()?,
+            self.2.into_owned()?,
+        ))
+    }
+}
+
 pub struct ParamIter<'a> {
     input: &'a str,
 }
@@ -142,7 +248,7 @@ fn param_pair_parse(input: &str) -> IResult<'_, (&str, &str)> {
     let input = skip(input, 1)?;
 
     // hack to handle quotes in names
-    let (value, input) = if key == "against" || key == "objectowner" {
+    let (value, input) = if key == "against" || key == "objectowner" || key.starts_with("player") {
         split_subject_end(input, 1)?
     } else {
         split_once(input, b'"', 1)?
@@ -288,3 +394,25 @@ impl<'a> EventField<'a> for RawSubject<'a> {
 pub fn parse_field<'a, T: EventField<'a>>(input: &'a str) -> Result<T> {
     T::parse_field(input)
 }
+
+#[test]
+fn test_owned_event_roundtrip() {
+    use crate::LineSplit;
+
+    let input = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+
+    let events: Vec<_> = LineSplit::new(&input)
+        .filter_map(|line| RawEvent::parse(line).ok())
+        .filter(|raw| GameEvent::is_supported(raw.ty))
+        .map(|raw| OwnedEvent::parse(&raw).unwrap())
+        .collect();
+    assert!(events
+        .iter()
+        .any(|event| matches!(event.event, OwnedGameEvent::Killed(_))));
+
+    for event in events {
+        let json = serde_json::to_string(&event).unwrap();
+        let parsed: OwnedEvent = serde_json::from_str(&json).unwrap();
+        assert_eq!(event, parsed, "{}", json);
+    }
+}
@@ -1,20 +1,21 @@
 use crate::common::{Class, Team};
 use crate::event::{param_parse_with, parse_field, quoted, ParamIter};
 use crate::raw_event::{subject_parser, RawSubject};
-use crate::{Error, Event, Result};
+use crate::{Encode, Error, Event, IntoOwned, Result};
+use serde::{Deserialize, Serialize};
 use std::net::SocketAddr;
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct ShotFiredEvent<'a> {
     pub weapon: Option<&'a str>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct ShotHitEvent<'a> {
     pub weapon: Option<&'a str>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct DamageEvent<'a> {
     #[event(name = "against")]
     #[event(default)]
@@ -25,11 +26,10 @@ pub struct DamageEvent<'a> {
     pub weapon: Option<&'a str>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct KillEvent<'a> {
     #[event(unnamed)]
     #[event(subject)]
-    #[event(skip_after = 1)]
     pub target: RawSubject<'a>,
     #[event(name = "with")]
     pub weapon: &'a str,
@@ -37,7 +37,19 @@ pub struct KillEvent<'a> {
     pub victim_position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[test]
+fn test_kill_weapon() {
+    use crate::{GameEvent, RawEvent};
+
+    let raw = RawEvent::parse(r#"08/06/2018 - 21:13:57: "makxbi<27><[U:1:40364391]><Red>" killed "=(eGO)= manskirt<37><[U:1:114365027]><Blue>" with "smg" (attacker_position "-2437 -864 130") (victim_position "-2623 -1023 128")"#).unwrap();
+    let GameEvent::Killed(event) = GameEvent::parse(&raw).unwrap() else {
+        panic!("not a kill event");
+    };
+    assert_eq!("smg", event.weapon);
+    assert_eq!(Some((-2623, -1023, 128)), event.victim_position);
+}
+
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct KillAssistEvent<'a> {
     #[event(name = "against")]
     pub target: RawSubject<'a>,
@@ -45,75 +57,75 @@ pub struct KillAssistEvent<'a> {
     pub victim_position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct SpawnEvent {
     #[event(name = "as")]
     pub class: Option<Class>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct RoleChangeEvent {
     #[event(name = "to")]
     pub class: Option<Class>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct ConnectedEvent {
     #[event(name = "address")]
     pub address: SocketAddr,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Clone, PartialEq, Event, IntoOwned, Encode, Serialize, Deserialize)]
 pub struct JoinedTeamEvent {
     pub team: Team,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct CommittedSuicideEvent<'a> {
     #[event(name = "with")]
     pub weapon: &'a str,
     pub attacker_position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct PickedUpEvent<'a> {
     pub item: &'a str,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct DominationEvent<'a> {
     pub against: RawSubject<'a>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct RevengeEvent<'a> {
     pub against: RawSubject<'a>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct DisconnectEvent<'a> {
     pub reason: Option<&'a str>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct BuiltObjectEvent<'a> {
     pub object: Option<&'a str>,
     pub position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct BuiltCarryEvent<'a> {
     pub object: Option<&'a str>,
     pub position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct DropObjectEvent<'a> {
     pub object: Option<&'a str>,
     pub position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct KilledObjectEvent<'a> {
     pub object: Option<&'a str>,
     pub weapon: Option<&'a str>,
@@ -122,14 +134,14 @@ pub struct KilledObjectEvent<'a> {
     pub attacker_position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct ObjectDetonatedEvent<'a> {
     pub object: Option<&'a str>,
     #[event(name = "attacker_position")]
     pub position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct ExtinguishedEvent<'a> {
     pub against: RawSubject<'a>,
     pub with: &'a str,
@@ -137,13 +149,13 @@ pub struct ExtinguishedEvent<'a> {
     pub victim_position: Option<(i32, i32, i32)>,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct SayEvent<'a> {
     #[event(unnamed)]
     pub text: &'a str,
 }
 
-#[derive(Debug, Event)]
+#[derive(Debug, Event, IntoOwned, Encode)]
 pub struct SayTeamEvent<'a> {
     #[event(unnamed)]
     pub text: &'a str,
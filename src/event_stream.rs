@@ -0,0 +1,298 @@
+//! Parse logs from async sources as a [`Stream`] of events
+
+use crate::stream::LineBuffer;
+use crate::{
+    Error, EventHandler, GameEvent, HandlerOutput, OwnedEvent, ParseOptions, RawEvent, Result,
+    StreamParser,
+};
+use futures::stream::{self, Stream, StreamExt};
+use std::io;
+use std::pin::Pin;
+use std::sync::Arc;
+use std::task::{Context, Poll};
+use std::time::Duration;
+use tokio::io::{AsyncBufRead, AsyncBufReadExt};
+use tokio::sync::{mpsc, watch};
+use tokio::time::MissedTickBehavior;
+
+/// A stream of log lines, without the leading `L `
+pub struct LineStream(Pin<Box<dyn Stream<Item = io::Result<String>> + Send>>);
+
+impl LineStream {
+    /// Read the lines of a log from an async reader
+    ///
+    /// This yields the same lines as [`LineSplit`](crate::LineSplit), any invalid utf8 is replaced per line.
+    pub fn from_reader<R: AsyncBufRead + Unpin + Send + 'static>(reader: R) -> Self {
+        let state = (
+            reader,
+            LineBuffer::default(),
+            Vec::with_capacity(256),
+            false,
+        );
+        LineStream(Box::pin(stream::unfold(
+            state,
+            |(mut reader, mut buffer, mut raw, done)| async move {
+                if done {
+                    return None;
+                }
+                loop {
+                    raw.clear();
+                    let line = match reader.read_until(b'\n', &mut raw).await {
+                        Ok(0) => {
+                            let (line, _) = buffer.flush()?;
+                            return Some((Ok(line), (reader, buffer, raw, true)));
+                        }
+                        Ok(_) => buffer.push(&raw),
+                        Err(e) => return Some((Err(e), (reader, buffer, raw, true))),
+                    };
+                    if let Some((line, _)) = line {
+                        return Some((Ok(line), (reader, buffer, raw, false)));
+                    }
+                }
+            },
+        )))
+    }
+
+    /// Receive complete log lines from a channel, the leading `L ` is optional
+    pub fn from_channel(receiver: mpsc::Receiver<String>) -> Self {
+        LineStream(Box::pin(stream::unfold(
+            receiver,
+            |mut receiver| async move {
+                let line = receiver.recv().await?;
+                let line = match line.strip_prefix("L ") {
+                    Some(stripped) => stripped.to_string(),
+                    None => line,
+                };
+                Some((Ok(line), receiver))
+            },
+        )))
+    }
+}
+
+impl Stream for LineStream {
+    type Item = io::Result<String>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.0.as_mut().poll_next(cx)
+    }
+}
+
+/// A stream of the events in a log
+///
+/// Lines that can't be parsed are yielded as errors, after which the stream continues with the next line.
+pub struct EventStream {
+    lines: LineStream,
+}
+
+impl EventStream {
+    pub fn new(lines: LineStream) -> Self {
+        EventStream { lines }
+    }
+}
+
+/// Parse a log line, returns `None` for lines that don't contain a supported event
+fn parse_owned(line: &str) -> Result<Option<OwnedEvent>> {
+    let raw = match RawEvent::parse(line) {
+        Ok(raw) => raw,
+        Err(Error::Skip) => return Ok(None),
+        Err(e) => return Err(e),
+    };
+    if !GameEvent::is_supported(raw.ty) {
+        return Ok(None);
+    }
+    OwnedEvent::parse(&raw).map(Some)
+}
+
+impl Stream for EventStream {
+    type Item = Result<OwnedEvent>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        loop {
+            let line = match self.lines.poll_next_unpin(cx) {
+                Poll::Ready(Some(Ok(line))) => line,
+                Poll::Ready(Some(Err(e))) => return Poll::Ready(Some(Err(e.into()))),
+                Poll::Ready(None) => return Poll::Ready(None),
+                Poll::Pending => return Poll::Pending,
+            };
+            match parse_owned(&line) {
+                Ok(Some(event)) => return Poll::Ready(Some(Ok(event))),
+                Ok(None) => continue,
+                Err(e) => return Poll::Ready(Some(Err(e))),
+            }
+        }
+    }
+}
+
+/// Snapshot of the handler output for the part of the log received so far
+pub type Snapshot<Handler> = Arc<HandlerOutput<Handler>>;
+
+type ErrorCallback = Box<dyn Fn(Error) + Send + Sync>;
+
+/// Runs a handler over a live log, publishing snapshots of the output at a fixed interval
+///
+/// Lines that can't be parsed are reported to the error callback and skipped.
+pub struct LiveHandler<Handler: EventHandler> {
+    interval: Duration,
+    parser: StreamParser<Handler>,
+    sender: watch::Sender<Option<Snapshot<Handler>>>,
+    on_error: Option<ErrorCallback>,
+}
+
+impl<Handler: EventHandler + Clone> LiveHandler<Handler>
+where
+    Handler::PerSubjectData: Clone,
+{
+    pub fn new(options: &ParseOptions, interval: Duration) -> Self {
+        LiveHandler {
+            interval,
+            parser: StreamParser::new(options),
+            sender: watch::channel(None).0,
+            on_error: None,
+        }
+    }
+
+    /// Set a callback that is called for every line that can't be parsed
+    pub fn on_error<F: Fn(Error) + Send + Sync + 'static>(mut self, callback: F) -> Self {
+        self.on_error = Some(Box::new(callback));
+        self
+    }
+
+    /// Receive the published snapshots, this is `None` until the first snapshot is published
+    pub fn subscribe(&self) -> watch::Receiver<Option<Snapshot<Handler>>> {
+        self.sender.subscribe()
+    }
+
+    /// Get the output for the lines received so far
+    pub fn snapshot(&self) -> HandlerOutput<Handler> {
+        self.parser.snapshot()
+    }
+
+    fn report(&self, error: Error) {
+        if let Some(on_error) = &self.on_error {
+            on_error(error);
+        }
+    }
+
+    fn publish(&self) {
+        self.sender.send_replace(Some(Arc::new(self.snapshot())));
+    }
+
+    /// Process all lines from the stream, publishing a snapshot every interval if new lines were received
+    ///
+    /// Once the stream ends, a final snapshot is published and the full output is returned.
+    /// Only errors reading from the stream stop the handler early.
+    pub async fn run(mut self, mut lines: LineStream) -> Result<HandlerOutput<Handler>> {
+        let mut interval = tokio::time::interval(self.interval);
+        interval.set_missed_tick_behavior(MissedTickBehavior::Delay);
+        let mut changed = false;
+
+        loop {
+            tokio::select! {
+                line = lines.next() => match line {
+                    Some(line) => {
+                        if let Err(e) = self.parser.push_line(&line?) {
+                            self.report(e);
+                        }
+                        changed = true;
+                    }
+                    None => break,
+                },
+                _ = interval.tick() => {
+                    if changed {
+                        self.publish();
+                        changed = false;
+                    }
+                }
+            }
+        }
+
+        self.publish();
+        self.parser.finish()
+    }
+}
+
+#[tokio::test]
+async fn test_event_stream() {
+    use crate::LineSplit;
+
+    let input = crate::read_log("tests/data/log_2892242.log.gz").unwrap();
+    let expected = LineSplit::new(&input)
+        .filter_map(|line| RawEvent::parse(line).ok())
+        .filter(|raw| GameEvent::is_supported(raw.ty))
+        .count();
+
+    let reader = tokio::io::BufReader::new(io::Cursor::new(input.into_bytes()));
+    let events: Vec<_> = EventStream::new(LineStream::from_reader(reader))
+        .map(|event| event.unwrap())
+        .collect()
+        .await;
+
+    assert_eq!(expected, events.len());
+    assert!(matches!(
+        events[0].event,
+        crate::OwnedGameEvent::LogFileStarted(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_live_handler() {
+    use crate::{parse, LineSplit, LogHandler};
+
+    let input = crate::read_log("tests/data/log_6s.log.gz").unwrap();
+    let handler = LiveHandler::<LogHandler>::new(&ParseOptions::default(), Duration::from_secs(1));
+    let mut snapshots = handler.subscribe();
+    assert!(snapshots.borrow().is_none());
+
+    let (sender, receiver) = mpsc::channel(16);
+    let lines: Vec<_> = LineSplit::new(&input).map(String::from).collect();
+    tokio::spawn(async move {
+        for line in lines {
+            sender.send(line).await.unwrap();
+        }
+    });
+
+    let output = handler
+        .run(LineStream::from_channel(receiver))
+        .await
+        .unwrap();
+    let expected = serde_json::to_value(parse(&input).unwrap()).unwrap();
+    assert_eq!(expected, serde_json::to_value(&output).unwrap());
+
+    snapshots.changed().await.unwrap();
+    let snapshot = snapshots.borrow().clone().unwrap();
+    assert_eq!(expected, serde_json::to_value(&*snapshot).unwrap());
+}
+
+#[tokio::test]
+async fn test_live_handler_malformed_line() {
+    use crate::LogHandler;
+    use std::sync::Mutex;
+
+    let errors = Arc::new(Mutex::new(Vec::new()));
+    let reported = errors.clone();
+    let handler = LiveHandler::<LogHandler>::new(&ParseOptions::default(), Duration::from_secs(1))
+        .on_error(move |e| reported.lock().unwrap().push(e.to_string()));
+
+    let (sender, receiver) = mpsc::channel(16);
+    tokio::spawn(async move {
+        for line in [
+            "08/06/2018 - 21:13:57: \"makxbi<27><[U:1:40364391]><Red>\" say \"first\"",
+            "08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo<3><[U:1:4]><Blue>\" (damage \"abc\")",
+            "08/06/2018 - 21:13:59: \"makxbi<27><[U:1:40364391]><Red>\" say \"second\"",
+        ] {
+            sender.send(line.to_string()).await.unwrap();
+        }
+    });
+
+    let (global, _) = handler
+        .run(LineStream::from_channel(receiver))
+        .await
+        .unwrap();
+    let messages: Vec<_> = global
+        .chat
+        .iter()
+        .map(|message| message.message.as_str())
+        .collect();
+    assert_eq!(vec!["first", "second"], messages);
+    assert_eq!(1, errors.lock().unwrap().len());
+}
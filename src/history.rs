@@ -0,0 +1,234 @@
+//! Head-to-head and teammate history of players over many logs
+//!
+//! Teams are the last team a player joined in each log, spectators are ignored.
+//! Kills and damage between players that ended a log on the same team are kept separate
+//! from the head-to-head stats, since they happened before one of them switched teams.
+
+use crate::module::{MatchResultHandler, PlayerHandler, PlayerInfoHandler, Versus};
+use crate::{parse_bytes_with_handler, Error, HandlerOutput, Merge, SteamId3, Team};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+
+crate::handler!(HistoryHandler {
+    versus: PlayerHandler::<Versus>,
+    info: PlayerInfoHandler,
+    result: MatchResultHandler,
+});
+
+/// History of a player with a single other player, from the perspective of the first player
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct PairRecord {
+    pub matches_against: u32,
+    pub wins_against: u32,
+    pub losses_against: u32,
+    /// Kills on the other player in matches against them
+    pub kills: u32,
+    pub damage: u32,
+    pub matches_together: u32,
+    pub wins_together: u32,
+    pub losses_together: u32,
+    /// Kills on the other player in matches together, from before one of them switched teams
+    pub kills_before_switch: u32,
+    pub damage_before_switch: u32,
+}
+
+impl Merge for PairRecord {
+    fn merge(&mut self, other: Self) {
+        self.matches_against.merge(other.matches_against);
+        self.wins_against.merge(other.wins_against);
+        self.losses_against.merge(other.losses_against);
+        self.kills.merge(other.kills);
+        self.damage.merge(other.damage);
+        self.matches_together.merge(other.matches_together);
+        self.wins_together.merge(other.wins_together);
+        self.losses_together.merge(other.losses_together);
+        self.kills_before_switch.merge(other.kills_before_switch);
+        self.damage_before_switch.merge(other.damage_before_switch);
+    }
+}
+
+/// How a player fared against another player
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct HeadToHead {
+    /// Matches played on opposing teams
+    pub matches: u32,
+    pub wins: u32,
+    pub losses: u32,
+    /// Kills on the other player
+    pub kills: u32,
+    /// Deaths to the other player
+    pub deaths: u32,
+    pub damage_dealt: u32,
+    pub damage_taken: u32,
+}
+
+impl HeadToHead {
+    pub fn draws(&self) -> u32 {
+        self.matches - self.wins - self.losses
+    }
+}
+
+/// How two players did on the same team
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct Teammates {
+    pub matches: u32,
+    pub wins: u32,
+    pub losses: u32,
+}
+
+impl Teammates {
+    pub fn draws(&self) -> u32 {
+        self.matches - self.wins - self.losses
+    }
+
+    /// Fraction of the matches together that were won, `None` if they never played together
+    pub fn win_rate(&self) -> Option<f64> {
+        (self.matches > 0).then(|| self.wins as f64 / self.matches as f64)
+    }
+}
+
+/// History of every pair of players that played in the same log
+#[derive(Debug, Default, Serialize, Deserialize, PartialEq)]
+pub struct PlayerHistory {
+    players: BTreeMap<SteamId3, BTreeMap<SteamId3, PairRecord>>,
+}
+
+impl PlayerHistory {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a log and add it to the history
+    pub fn add_log(&mut self, log: &[u8]) -> Result<(), Error> {
+        self.add(parse_bytes_with_handler::<HistoryHandler>(log)?);
+        Ok(())
+    }
+
+    /// Add the output of a parsed log to the history
+    pub fn add(&mut self, (global, players): HandlerOutput<HistoryHandler>) {
+        let winner = global.result.winner();
+        let players: Vec<_> = players
+            .into_iter()
+            .filter(|(_, player)| player.info.team != Team::Spectator)
+            .collect();
+
+        for (steam_id, player) in &players {
+            let team = player.info.team;
+            let won = winner == Some(team);
+            let lost = matches!(winner, Some(winner) if winner != team);
+            let mut records = BTreeMap::new();
+            for (other_id, other) in &players {
+                if other_id == steam_id {
+                    continue;
+                }
+                let versus = player.versus.get(other_id).copied().unwrap_or_default();
+                let record = if other.info.team == team {
+                    PairRecord {
+                        matches_together: 1,
+                        wins_together: won as u32,
+                        losses_together: lost as u32,
+                        kills_before_switch: versus.kills,
+                        damage_before_switch: versus.damage,
+                        ..PairRecord::default()
+                    }
+                } else {
+                    PairRecord {
+                        matches_against: 1,
+                        wins_against: won as u32,
+                        losses_against: lost as u32,
+                        kills: versus.kills,
+                        damage: versus.damage,
+                        ..PairRecord::default()
+                    }
+                };
+                records.insert(*other_id, record);
+            }
+            Merge::merge(self.players.entry(*steam_id).or_default(), records);
+        }
+    }
+
+    /// The history of a player with everyone they played with or against
+    pub fn records(&self, player: &SteamId3) -> impl Iterator<Item = (&SteamId3, &PairRecord)> {
+        self.players.get(player).into_iter().flatten()
+    }
+
+    fn record(&self, player: &SteamId3, other: &SteamId3) -> PairRecord {
+        self.players
+            .get(player)
+            .and_then(|records| records.get(other))
+            .copied()
+            .unwrap_or_default()
+    }
+
+    /// How `player` fared against `opponent`
+    pub fn head_to_head(&self, player: &SteamId3, opponent: &SteamId3) -> HeadToHead {
+        let record = self.record(player, opponent);
+        let reverse = self.record(opponent, player);
+        HeadToHead {
+            matches: record.matches_against,
+            wins: record.wins_against,
+            losses: record.losses_against,
+            kills: record.kills,
+            deaths: reverse.kills,
+            damage_dealt: record.damage,
+            damage_taken: reverse.damage,
+        }
+    }
+
+    /// How two players did on the same team
+    pub fn teammates(&self, player: &SteamId3, teammate: &SteamId3) -> Teammates {
+        let record = self.record(player, teammate);
+        Teammates {
+            matches: record.matches_together,
+            wins: record.wins_together,
+            losses: record.losses_together,
+        }
+    }
+}
+
+impl Merge for PlayerHistory {
+    fn merge(&mut self, other: Self) {
+        Merge::merge(&mut self.players, other.players);
+    }
+}
+
+#[test]
+fn test_player_history() {
+    use steamid_ng::SteamID;
+
+    let log = crate::read_log_bytes("tests/data/log_2892242.log.gz").unwrap();
+    let mut history = PlayerHistory::new();
+    history.add_log(&log).unwrap();
+    history.add_log(&log).unwrap();
+
+    // blue won this log
+    let liko = SteamId3(SteamID::from_steam3("[U:1:34394789]").unwrap());
+    let nata = SteamId3(SteamID::from_steam3("[U:1:155060312]").unwrap());
+    let menex = SteamId3(SteamID::from_steam3("[U:1:111667300]").unwrap());
+
+    let together = history.teammates(&liko, &nata);
+    assert_eq!(
+        (2, 2, 0),
+        (together.matches, together.wins, together.losses)
+    );
+    assert_eq!(Some(1.0), together.win_rate());
+    assert_eq!(together, history.teammates(&nata, &liko));
+    assert_eq!(Teammates::default(), history.teammates(&liko, &menex));
+
+    let versus = history.head_to_head(&liko, &menex);
+    assert_eq!((2, 2, 0), (versus.matches, versus.wins, versus.losses));
+    assert!(versus.damage_dealt > 0);
+    let reverse = history.head_to_head(&menex, &liko);
+    assert_eq!((2, 0, 2), (reverse.matches, reverse.wins, reverse.losses));
+    assert_eq!(versus.kills, reverse.deaths);
+    assert_eq!(versus.damage_dealt, reverse.damage_taken);
+    assert_eq!(0, history.record(&liko, &nata).kills);
+
+    // the kills on each opponent add up to the total kills on players
+    let (_, players) = crate::parse_bytes(&log).unwrap();
+    let kills: u32 = history
+        .records(&liko)
+        .map(|(_, record)| record.kills + record.kills_before_switch)
+        .sum();
+    assert_eq!(players[&liko].class_stats.kills.total() * 2, kills);
+}
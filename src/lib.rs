@@ -1,127 +1,152 @@
-pub use crate::common::{SteamId3, SubjectData, SubjectError, SubjectId};
+pub use crate::common::{Class, ClassMap, SteamId3, SubjectData, SubjectError, SubjectId, Team};
 pub use crate::module::EventHandler;
 use crate::module::{
     ChatMessages, ClassStatsHandler, HealSpread, MedicStatsBuilder, PlayerHandler,
 };
 pub use crate::subjectmap::SubjectMap;
-use chrono::{Duration, NaiveDate, NaiveDateTime};
+pub use bytes::{
+    parse_bytes, parse_bytes_with_handler, parse_bytes_with_options, raw_events_bytes,
+    validate_bytes, LineSplitBytes, RawLine,
+};
+pub use cache::{read_cache, write_cache, CachedEvent, CachedEvents, EventCache};
+use chrono::NaiveDateTime;
+pub use combine::{combine, combine_with_handler, combine_with_options};
 pub(crate) use error::ResultExt;
-pub use error::{Error, IResult, Result};
-pub use event::{Event, EventMeta, GameEvent};
+pub use error::{Error, IResult, LineLocation, LocatedError, Result};
+pub use event::{Event, EventMeta, GameEvent, IntoOwned, OwnedEvent, OwnedGameEvent};
 use memchr::memmem::{find_iter, FindIter};
+pub use merge::{Aggregator, Merge};
+pub use options::{ParseOptions, TimeOrigin};
+use parser::LogParser;
 pub use raw_event::{RawEvent, RawEventType};
+pub use read::{decode_log, decompress_log, read_log, read_log_bytes};
+pub use segment::{segment_matches, MatchSegment};
+pub use state::{MatchState, MatchStateTracker};
 use std::collections::BTreeMap;
-use std::convert::TryInto;
-pub(crate) use tf_log_parser_derive::{Event, Events};
+use std::convert::{TryFrom, TryInto};
+pub use stream::{parse_reader, LineReader, StreamParser};
+pub(crate) use tf_log_parser_derive::{Encode, Event, Events, IntoOwned};
+pub use writer::LogWriter;
 
+pub mod anonymize;
+#[cfg(feature = "arrow")]
+pub mod arrow;
+#[cfg(feature = "batch")]
+pub mod batch;
+mod bytes;
+mod cache;
+pub mod career;
+mod combine;
 mod common;
+#[cfg(feature = "csv")]
+pub mod csv;
 pub mod event;
 #[macro_use]
 pub mod module;
 mod error;
+#[cfg(feature = "tokio")]
+pub mod event_stream;
+pub mod history;
+#[cfg(feature = "live")]
+pub mod live;
+mod merge;
+mod options;
+mod parser;
 pub(crate) mod parsing;
+pub mod rating;
 pub mod raw_event;
+mod read;
+mod segment;
+#[cfg(feature = "sqlite")]
+pub mod sqlite;
+mod state;
+mod stream;
 mod subjectmap;
+mod writer;
 
-pub fn parse(
-    log: &str,
-) -> Result<
-    (
-        <LogHandler as EventHandler>::GlobalOutput,
-        BTreeMap<SteamId3, <LogHandler as EventHandler>::PerSubjectOutput>,
-    ),
-    Error,
-> {
+/// Global and per-player output of an event handler
+pub type HandlerOutput<Handler> = (
+    <Handler as EventHandler>::GlobalOutput,
+    BTreeMap<SteamId3, <Handler as EventHandler>::PerSubjectOutput>,
+);
+
+pub fn parse(log: &str) -> Result<HandlerOutput<LogHandler>, Error> {
     parse_with_handler::<LogHandler>(log)
 }
 
 pub fn raw_events<'a>(log: &'a str) -> impl Iterator<Item = Result<RawEvent<'a>>> + 'a {
-    LineSplit::new(log).map(RawEvent::parse)
+    LineSplit::new(log).map(move |line| RawEvent::parse(line).map_err(|e| e.located(log, line)))
 }
 
-pub fn parse_with_handler<Handler: EventHandler>(
-    log: &str,
-) -> Result<
-    (
-        Handler::GlobalOutput,
-        BTreeMap<SteamId3, Handler::PerSubjectOutput>,
-    ),
-    Error,
-> {
-    let mut events = raw_events(log);
-
-    let mut handler = Handler::default();
-
-    let mut start_time: Option<NaiveDateTime> = None;
-    let mut subjects = SubjectMap::<Handler::PerSubjectData>::with_capacity(32);
-
-    while let Some(event_res) = events.next() {
-        let raw_event = match event_res {
-            Ok(raw_event) => raw_event,
-            Err(Error::Incomplete) if events.next().is_none() => break,
-            Err(Error::Skip) => continue,
-            Err(e) => return Err(e),
-        };
-        let should_handle = Handler::does_handle(raw_event.ty);
-        if should_handle || start_time.is_none() {
-            if should_handle {
-                let event = match GameEvent::parse(&raw_event) {
-                    Ok(event) => event,
-                    Err(e) => {
-                        let old_date: NaiveDateTime = raw_event
-                            .date
-                            .try_into()
-                            .unwrap_or_else(|_| NaiveDateTime::from_timestamp(0, 0));
-
-                        // truncated newline during log combining, ignore error
-                        if contains_line_start(raw_event.params, &old_date.date()) {
-                            continue;
-                        }
-
-                        let Some(next) = events.next() else {
-                            // log is truncated
-                            break;
-                        };
-
-                        if let Ok(next) = next {
-                            let new_date: NaiveDateTime = next
-                                .date
-                                .try_into()
-                                .unwrap_or_else(|_| NaiveDateTime::from_timestamp(0, 0));
-
-                            // truncated lines during log combining, ignore error
-                            if new_date.signed_duration_since(old_date) > Duration::seconds(60) {
-                                continue;
-                            }
-                        }
-
-                        return Err(e.into());
-                    }
-                };
-                handler.process(&raw_event, &event, &mut start_time, &mut subjects)?;
-            }
+/// Check all lines of a log, returning every error instead of stopping at the first one
+///
+/// Lines that are supported by the parser have their subject and event parsed,
+/// the last line of the log is allowed to be truncated.
+pub fn validate(log: &str) -> Vec<Error> {
+    let mut lines = LineSplit::new(log).peekable();
+    let mut errors = Vec::new();
+    while let Some(line) = lines.next() {
+        let last = lines.peek().is_none();
+        errors.extend(
+            validate_line(line, last)
+                .into_iter()
+                .map(|e| e.located(log, line)),
+        );
+    }
+    errors
+}
+
+/// Check a single line of a log, the last line is allowed to be truncated
+pub(crate) fn validate_line(line: &str, last: bool) -> Vec<Error> {
+    let raw = match RawEvent::parse(line) {
+        Ok(raw) => raw,
+        Err(Error::Skip) => return Vec::new(),
+        Err(Error::Incomplete) if last => return Vec::new(),
+        Err(e) => return vec![e],
+    };
+    let mut errors = Vec::new();
+    if let Err(e) = SubjectData::try_from(&raw.subject) {
+        errors.push(Error::from(e));
+    }
+    if GameEvent::is_supported(raw.ty) {
+        match GameEvent::parse(&raw) {
+            Err(_) if last => {}
+            Err(e) => errors.push(Error::from(e)),
+            Ok(_) => {}
         }
     }
+    errors
+}
 
-    let just_subjects = subjects.to_just_subjects();
-    let per_player = subjects
-        .into_iter()
-        .filter_map(|(id, subject, data)| Some((id.steam_id()?, subject, data)))
-        .map(|(steam_id, subject, data)| {
-            (
-                SteamId3(steam_id),
-                handler.finish_per_subject(&subject, data),
-            )
-        })
-        .collect();
-    let global = handler.finish_global(&just_subjects);
+pub fn parse_with_handler<Handler: EventHandler>(
+    log: &str,
+) -> Result<HandlerOutput<Handler>, Error> {
+    parse_with_options::<Handler>(log, &ParseOptions::default())
+}
+
+pub fn parse_with_options<Handler: EventHandler>(
+    log: &str,
+    options: &ParseOptions,
+) -> Result<HandlerOutput<Handler>, Error> {
+    // since we have the full log available, we can look ahead for the time origin and tournament start
+    let options = options.with_lookahead(|ty| first_event_time(LineSplit::new(log), ty));
 
-    Ok((global, per_player))
+    let mut parser = LogParser::<Handler>::new(&options);
+    for line in LineSplit::new(log) {
+        parser.handle_line(line, |e, line| e.located(log, line))?;
+    }
+    Ok(parser.finish())
 }
 
-fn contains_line_start(line: &str, date: &NaiveDate) -> bool {
-    let expected_start = format!("L {}", date.format("%m/%d/%Y"));
-    line.contains(&expected_start)
+/// Find the time of the first event of a specific type
+fn first_event_time<'a>(
+    lines: impl Iterator<Item = &'a str>,
+    ty: RawEventType,
+) -> Option<NaiveDateTime> {
+    lines
+        .flat_map(RawEvent::parse)
+        .find(|event| event.ty == ty)
+        .and_then(|event| event.date.try_into().ok())
 }
 
 handler!(LogHandler {
@@ -132,21 +157,28 @@ handler!(LogHandler {
 });
 
 pub struct LineSplit<'a> {
+    full: &'a str,
     input: &'a str,
     start: usize,
     iter: FindIter<'a, 'static>,
 }
 
 impl<'a> LineSplit<'a> {
-    pub fn new(input: &'a str) -> Self {
+    pub fn new(full: &'a str) -> Self {
         // skip first delimiter, and any byte order mark
-        let (_, input) = input.split_once("L ").unwrap_or_default();
+        let (_, input) = full.split_once("L ").unwrap_or_default();
         LineSplit {
+            full,
             input,
             start: 0,
             iter: find_iter(input.as_bytes(), b"\nL "),
         }
     }
+
+    /// Get the line number and byte span of a line returned by this iterator
+    pub fn location(&self, line: &str) -> Option<LineLocation> {
+        LineLocation::new(self.full, line)
+    }
 }
 
 impl<'a> Iterator for LineSplit<'a> {
@@ -171,14 +203,7 @@ impl<'a> Iterator for LineSplit<'a> {
 
 #[test]
 fn test_split() {
-    use flate2::read::GzDecoder;
-    use std::fs::File;
-    use std::io::Read;
-
-    let mut input = String::new();
-    GzDecoder::new(File::open("tests/data/log_2892242.log.gz").expect("failed to open"))
-        .read_to_string(&mut input)
-        .expect("failed to read");
+    let input = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
 
     let split: Vec<_> = LineSplit::new(&input).collect();
     let expected: Vec<_> = input
@@ -189,3 +214,69 @@ fn test_split() {
     assert_eq!(expected.len(), split.len());
     assert_eq!(expected, split);
 }
+
+#[test]
+fn test_error_location() {
+    let log = concat!(
+        "L 08/06/2018 - 21:13:57: \"makxbi<27><[U:1:40364391]><Red>\" changed role to \"sniper\"\n",
+        "L 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo<3><[U:1:4]><Blue>\" (damage \"abc\")\n",
+        "L 08/06/2018 - 21:13:59: \"makxbi<27><[U:1:40364391]><Red>\" changed role to \"scout\"\n",
+    );
+    let err = parse(log).err().expect("expected parse to fail");
+    assert!(matches!(err.inner(), Error::MalformedEvent(_)));
+    assert_eq!(
+        Some(&LineLocation {
+            line: 2,
+            span: 84..208
+        }),
+        err.location()
+    );
+}
+
+#[test]
+fn test_validate() {
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    assert!(validate(&log).is_empty());
+
+    let log = concat!(
+        "L 08/06/2018 - 21:13:57: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo<3><[U:1:4]><Blue>\" (damage \"abc\")\n",
+        "L 08/06/2018 - 21:13:58: \"makxbi<27><[U:1:40364391]><Red>\" changed role to \"scout\"\n",
+        "L 08/06/2018 - 21:13:59: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo<3><[U:1:4]><Blue>\" (damage \"abc\")\n",
+        "L 08/06/2018 - 21:14:00: \"makxbi<27><[U:1:40364391]><Red>\" triggered \"damage\" against \"foo",
+    );
+    let lines: Vec<_> = validate(log)
+        .iter()
+        .map(|error| error.location().unwrap().line)
+        .collect();
+    assert_eq!(vec![1, 3], lines);
+}
+
+#[test]
+fn test_parse_options() {
+    use crate::module::ChatMessages;
+
+    let log = concat!(
+        "L 08/06/2018 - 21:00:00: \"makxbi<27><[U:1:40364391]><Red>\" say \"warmup\"\n",
+        "L 08/06/2018 - 21:01:00: Tournament mode started\n",
+        "Blue Team: BLU\n",
+        "Red Team: RED\n",
+        "L 08/06/2018 - 21:01:10: World triggered \"Round_Start\"\n",
+        "L 08/06/2018 - 21:01:20: \"makxbi<27><[U:1:40364391]><Red>\" say \"gl hf\"\n",
+    );
+
+    let (chat, _) = parse_with_handler::<ChatMessages>(log).unwrap();
+    let times: Vec<_> = chat.iter().map(|message| message.time).collect();
+    assert_eq!(vec![0, 80], times);
+
+    let options = ParseOptions {
+        skip_warmup: true,
+        time_origin: TimeOrigin::FirstRound,
+        ..ParseOptions::default()
+    };
+    let (chat, _) = parse_with_options::<ChatMessages>(log, &options).unwrap();
+    let messages: Vec<_> = chat
+        .iter()
+        .map(|message| (message.time, message.message.as_str()))
+        .collect();
+    assert_eq!(vec![(10, "gl hf")], messages);
+}
@@ -0,0 +1,250 @@
+//! Receive logs from live servers using the udp log protocol (`logaddress_add`)
+
+use crate::{Error, EventHandler, HandlerOutput, ParseOptions, RawEvent, StreamParser};
+use std::borrow::Cow;
+use std::collections::HashMap;
+use std::io;
+use std::net::{SocketAddr, ToSocketAddrs, UdpSocket};
+use thiserror::Error;
+
+const HEADER: [u8; 4] = [0xFF; 4];
+const TYPE_NO_SECRET: u8 = b'R';
+const TYPE_SECRET: u8 = b'S';
+
+#[derive(Debug, Error, PartialEq)]
+pub enum PacketError {
+    #[error("Packet too short")]
+    TooShort,
+    #[error("Invalid packet header")]
+    InvalidHeader,
+    #[error("Unknown packet type {0:#x}")]
+    UnknownType(u8),
+    #[error("Packet contains no log line")]
+    MissingLine,
+    #[error("Invalid log secret")]
+    InvalidSecret,
+}
+
+/// A single log line received from a server
+#[derive(Debug, PartialEq)]
+pub struct LogPacket<'a> {
+    /// The `sv_logsecret` of the server, if set
+    pub secret: Option<&'a str>,
+    /// The log line, without the leading `L `
+    pub line: Cow<'a, str>,
+}
+
+impl<'a> LogPacket<'a> {
+    pub fn parse(data: &'a [u8]) -> Result<Self, PacketError> {
+        if data.len() < HEADER.len() + 1 {
+            return Err(PacketError::TooShort);
+        }
+        let (header, data) = data.split_at(HEADER.len());
+        if header != HEADER {
+            return Err(PacketError::InvalidHeader);
+        }
+        let body = &data[1..];
+        let line_start = memchr::memmem::find(body, b"L ").ok_or(PacketError::MissingLine)?;
+        let secret = match data[0] {
+            TYPE_NO_SECRET => None,
+            TYPE_SECRET => Some(
+                std::str::from_utf8(&body[..line_start]).map_err(|_| PacketError::InvalidSecret)?,
+            ),
+            ty => return Err(PacketError::UnknownType(ty)),
+        };
+        let line = &body[line_start + 2..];
+        let end = line
+            .iter()
+            .rposition(|byte| !matches!(byte, b'\0' | b'\n' | b'\r'))
+            .map(|pos| pos + 1)
+            .unwrap_or(0);
+        Ok(LogPacket {
+            secret,
+            line: String::from_utf8_lossy(&line[..end]),
+        })
+    }
+
+    /// Encode a log line into a packet as it would be sent by the server
+    pub fn encode(line: &str, secret: Option<&str>) -> Vec<u8> {
+        let mut packet = Vec::with_capacity(line.len() + 16);
+        packet.extend_from_slice(&HEADER);
+        match secret {
+            Some(secret) => {
+                packet.push(TYPE_SECRET);
+                packet.extend_from_slice(secret.as_bytes());
+            }
+            None => packet.push(TYPE_NO_SECRET),
+        }
+        packet.extend_from_slice(b"L ");
+        packet.extend_from_slice(line.as_bytes());
+        packet.extend_from_slice(b"\n\0");
+        packet
+    }
+
+    pub fn raw_event(&self) -> crate::Result<RawEvent<'_>> {
+        RawEvent::parse(&self.line)
+    }
+}
+
+#[derive(Debug, Error)]
+pub enum LiveError {
+    #[error("Failed to receive log packet: {0}")]
+    Io(#[from] io::Error),
+    #[error("Malformed packet from {server}: {error}")]
+    Packet {
+        server: SocketAddr,
+        error: PacketError,
+    },
+    #[error("Failed to parse log from {server}: {error}")]
+    Parse { server: SocketAddr, error: Error },
+}
+
+/// Receives logs from one or more servers over udp and feeds them into a handler per server
+pub struct LiveReceiver<Handler: EventHandler> {
+    socket: UdpSocket,
+    secret: Option<String>,
+    options: ParseOptions,
+    servers: HashMap<SocketAddr, StreamParser<Handler>>,
+    buffer: Vec<u8>,
+}
+
+impl<Handler: EventHandler> LiveReceiver<Handler> {
+    /// Listen for log packets on the provided address
+    pub fn bind<A: ToSocketAddrs>(addr: A, options: &ParseOptions) -> io::Result<Self> {
+        Ok(LiveReceiver {
+            socket: UdpSocket::bind(addr)?,
+            secret: None,
+            options: options.clone(),
+            servers: HashMap::new(),
+            buffer: vec![0; 65536],
+        })
+    }
+
+    /// Only accept packets that contain the provided `sv_logsecret`
+    pub fn with_secret(mut self, secret: impl Into<String>) -> Self {
+        self.secret = Some(secret.into());
+        self
+    }
+
+    pub fn local_addr(&self) -> io::Result<SocketAddr> {
+        self.socket.local_addr()
+    }
+
+    /// The underlying socket, for configuring timeouts or non-blocking mode
+    pub fn socket(&self) -> &UdpSocket {
+        &self.socket
+    }
+
+    /// Wait for the next packet and process it, returning the address of the server that sent it
+    pub fn receive(&mut self) -> Result<SocketAddr, LiveError> {
+        let (len, source) = self.socket.recv_from(&mut self.buffer)?;
+        let data = std::mem::take(&mut self.buffer);
+        let result = self.handle_packet(source, &data[..len]);
+        self.buffer = data;
+        result.map(|_| source)
+    }
+
+    /// Process a packet received from a server
+    pub fn handle_packet(&mut self, source: SocketAddr, data: &[u8]) -> Result<(), LiveError> {
+        let packet = LogPacket::parse(data).map_err(|error| LiveError::Packet {
+            server: source,
+            error,
+        })?;
+        if packet.secret != self.secret.as_deref() {
+            return Err(LiveError::Packet {
+                server: source,
+                error: PacketError::InvalidSecret,
+            });
+        }
+
+        let options = &self.options;
+        self.servers
+            .entry(source)
+            .or_insert_with(|| StreamParser::new(options))
+            .push_line(&packet.line)
+            .map_err(|error| LiveError::Parse {
+                server: source,
+                error,
+            })
+    }
+
+    /// Addresses of all servers that logs have been received from
+    pub fn servers(&self) -> impl Iterator<Item = &SocketAddr> {
+        self.servers.keys()
+    }
+
+    /// Stop tracking a server and get the output for all logs received from it
+    pub fn finish(&mut self, server: SocketAddr) -> Option<Result<HandlerOutput<Handler>, Error>> {
+        self.servers.remove(&server).map(StreamParser::finish)
+    }
+}
+
+#[test]
+fn test_parse_packet() {
+    assert_eq!(
+        Ok(LogPacket {
+            secret: None,
+            line: r#"08/06/2018 - 21:13:57: "makxbi<27><[U:1:40364391]><Red>" changed role to "sniper""#.into()
+        }),
+        LogPacket::parse(b"\xFF\xFF\xFF\xFFRL 08/06/2018 - 21:13:57: \"makxbi<27><[U:1:40364391]><Red>\" changed role to \"sniper\"\n\0")
+    );
+    assert_eq!(
+        Ok(LogPacket {
+            secret: Some("1234"),
+            line: "08/06/2018 - 21:13:57: World triggered \"Round_Start\"".into()
+        }),
+        LogPacket::parse(
+            b"\xFF\xFF\xFF\xFFS1234L 08/06/2018 - 21:13:57: World triggered \"Round_Start\"\n\0"
+        )
+    );
+    assert_eq!(
+        Err(PacketError::InvalidHeader),
+        LogPacket::parse(b"\xFF\xFF\xFF\x00RL 08/06/2018 - 21:13:57: foo")
+    );
+    assert_eq!(
+        Err(PacketError::UnknownType(b'X')),
+        LogPacket::parse(b"\xFF\xFF\xFF\xFFXL 08/06/2018 - 21:13:57: foo")
+    );
+}
+
+#[test]
+fn test_receive_replay() {
+    use crate::{parse, LineSplit, LogHandler};
+
+    let input = crate::read_log("tests/data/log_6s.log.gz").expect("failed to read");
+
+    let mut receiver = LiveReceiver::<LogHandler>::bind("127.0.0.1:0", &ParseOptions::default())
+        .unwrap()
+        .with_secret("1234");
+    let sender = UdpSocket::bind("127.0.0.1:0").unwrap();
+    sender.connect(receiver.local_addr().unwrap()).unwrap();
+
+    sender
+        .send(&LogPacket::encode(
+            "08/06/2018 - 21:13:57: foo",
+            Some("4321"),
+        ))
+        .unwrap();
+    assert!(matches!(
+        receiver.receive(),
+        Err(LiveError::Packet {
+            error: PacketError::InvalidSecret,
+            ..
+        })
+    ));
+
+    for line in LineSplit::new(&input) {
+        sender.send(&LogPacket::encode(line, Some("1234"))).unwrap();
+        assert_eq!(sender.local_addr().unwrap(), receiver.receive().unwrap());
+    }
+
+    let expected = parse(&input).unwrap();
+    let received = receiver
+        .finish(sender.local_addr().unwrap())
+        .unwrap()
+        .unwrap();
+    assert_eq!(
+        serde_json::to_value(&expected).unwrap(),
+        serde_json::to_value(&received).unwrap()
+    );
+}
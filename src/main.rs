@@ -1,17 +1,731 @@
-use main_error::MainError;
-use std::env::args;
+use chrono::NaiveDateTime;
+use clap::{Args, Parser, Subcommand, ValueEnum};
+use miette::{miette, IntoDiagnostic, Report, Result, WrapErr};
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+use std::collections::BTreeMap;
+use std::convert::{TryFrom, TryInto};
 use std::fs;
-use std::io::stdout;
-use tf_log_parser::parse;
+use std::io::{stdin, stdout, BufWriter, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use steamid_ng::SteamID;
+use tf_log_parser::anonymize::Anonymizer;
+use tf_log_parser::career::CareerIndex;
+use tf_log_parser::csv::{CsvRows, CsvWriter, SummaryRow};
+use tf_log_parser::module::{ChatType, LobbySettingsHandler, PlayerInfo, PlayerInfoHandler};
+use tf_log_parser::{
+    combine, decompress_log, handler, parse_bytes, parse_bytes_with_handler, raw_events_bytes,
+    read_log, read_log_bytes, validate_bytes, Error, EventHandler, GameEvent, LogHandler,
+    OwnedGameEvent, RawEvent, SteamId3, SubjectData, Team,
+};
 
-fn main() -> Result<(), MainError> {
-    let path = args().nth(1).expect("No path provided");
-    let content = fs::read(path)?;
-    let content = String::from_utf8_lossy(&content);
+/// TF2 log parser
+///
+/// Without a command, the full statistics of the logs are output as JSON.
+#[derive(Parser)]
+#[command(
+    version,
+    args_conflicts_with_subcommands = true,
+    arg_required_else_help = true
+)]
+struct Cli {
+    #[command(subcommand)]
+    command: Option<Command>,
+    #[command(flatten)]
+    inputs: Inputs,
+}
+
+#[derive(Subcommand)]
+enum Command {
+    /// Show the scoreboard of logs
+    Summary(Inputs),
+    /// Output the full statistics of logs
+    Stats {
+        #[arg(long, value_enum, default_value_t = Format::Json)]
+        format: Format,
+        /// The per-player statistics to output as csv, defaults to the totals for each player
+        #[arg(long, value_enum)]
+        table: Option<Table>,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Show the chat of logs
+    Chat(Inputs),
+    /// Export the events of logs as JSON lines, optionally filtered by event type or player
+    Events {
+        /// Only export events of this type
+        #[arg(long = "type", value_name = "EVENT TYPE")]
+        types: Vec<String>,
+        /// Only export events from this player, by steam id or name
+        #[arg(long = "player", value_name = "STEAM ID OR NAME")]
+        players: Vec<String>,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+    /// Check logs for lines that can't be parsed, exits with an error if any are found
+    Validate(Inputs),
+    /// Output the lobby settings of logs
+    Lobby(Inputs),
+    /// Parse a match that is split over multiple logs, in order
+    Combine {
+        #[arg(required = true)]
+        logs: Vec<PathBuf>,
+    },
+    /// Anonymize a log by replacing player names and steam ids and stripping ip addresses
+    Anonymize {
+        /// Secret used to derive the replacement identities, anyone with the salt can recover the original steam ids
+        #[arg(long)]
+        salt: String,
+        /// Json file with a fixed identity for players
+        #[arg(long)]
+        mapping: Option<PathBuf>,
+        /// Remove the content of all chat messages
+        #[arg(long)]
+        scrub_chat: bool,
+        /// Log file to read, reads from stdin if not given or `-`
+        log: Option<PathBuf>,
+    },
+    /// Build the career statistics of all players in a directory of logs
+    Career {
+        /// Json file to keep the index in, only logs that aren't in the index yet are parsed
+        #[arg(long)]
+        index: Option<PathBuf>,
+        /// Only output the career of this player, by steam id
+        #[arg(long, value_name = "STEAM ID")]
+        player: Option<String>,
+        /// Directory to search for logs
+        dir: PathBuf,
+    },
+    /// Import logs into a sqlite database, logs that were already imported are skipped
+    #[cfg(feature = "sqlite")]
+    Import {
+        /// The database file, created if it doesn't exist
+        #[arg(long)]
+        database: PathBuf,
+        #[command(flatten)]
+        inputs: Inputs,
+    },
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Format {
+    Json,
+    Csv,
+}
+
+#[derive(Copy, Clone, ValueEnum)]
+enum Table {
+    /// Name, team, classes and totals for each player, excluding spectators
+    Summary,
+    /// Name, team and classes of each player
+    Players,
+    /// Stats for each player against each class
+    Classes,
+    /// Medic stats for each medic
+    Medic,
+    /// Healing from each player to each target
+    HealSpread,
+}
+
+#[derive(Args)]
+struct Inputs {
+    /// Log files to read, reads from stdin if no files are given or for `-`
+    logs: Vec<PathBuf>,
+}
+
+impl Inputs {
+    fn paths(&self) -> Vec<&Path> {
+        if self.logs.is_empty() {
+            vec![Path::new("-")]
+        } else {
+            self.logs.iter().map(PathBuf::as_path).collect()
+        }
+    }
+
+    fn is_multiple(&self) -> bool {
+        self.logs.len() > 1
+    }
+
+    /// Read all inputs, in order
+    fn read(&self) -> impl Iterator<Item = Result<Input>> + '_ {
+        self.paths().into_iter().map(Input::read)
+    }
+}
+
+/// The content of an input log
+struct Input {
+    name: String,
+    content: Vec<u8>,
+}
+
+impl Input {
+    fn read(path: &Path) -> Result<Self> {
+        if path == Path::new("-") {
+            let mut data = Vec::new();
+            stdin().lock().read_to_end(&mut data).into_diagnostic()?;
+            Ok(Input {
+                name: "<stdin>".into(),
+                content: decompress_log(data)?,
+            })
+        } else {
+            let name = path.display().to_string();
+            let content =
+                read_log_bytes(path).wrap_err_with(|| format!("failed to read {}", name))?;
+            Ok(Input { name, content })
+        }
+    }
+}
+
+fn main() -> Result<ExitCode> {
+    let cli = Cli::parse();
+    let Some(command) = cli.command else {
+        stats(&cli.inputs, Format::Json, None)?;
+        return Ok(ExitCode::SUCCESS);
+    };
+    match command {
+        Command::Summary(inputs) => summary(&inputs)?,
+        Command::Stats {
+            format,
+            table,
+            inputs,
+        } => stats(&inputs, format, table)?,
+        Command::Chat(inputs) => chat(&inputs)?,
+        Command::Events {
+            types,
+            players,
+            inputs,
+        } => events(&inputs, &EventFilter { types, players })?,
+        Command::Validate(inputs) => return validate_logs(&inputs),
+        Command::Lobby(inputs) => lobby(&inputs)?,
+        Command::Combine { logs } => combine_logs(&logs)?,
+        Command::Anonymize {
+            salt,
+            mapping,
+            scrub_chat,
+            log,
+        } => anonymize(salt, mapping, scrub_chat, log)?,
+        Command::Career { index, player, dir } => career(index, player, &dir)?,
+        #[cfg(feature = "sqlite")]
+        Command::Import { database, inputs } => import(&database, &inputs)?,
+    }
+    Ok(ExitCode::SUCCESS)
+}
+
+handler!(ScoreboardHandler {
+    stats: LogHandler,
+    info: PlayerInfoHandler,
+});
+
+/// Output for a list of logs, serialized as a map by log name if there are multiple logs
+struct Outputs<T> {
+    multiple: bool,
+    outputs: Vec<(String, T)>,
+}
+
+impl<T: Serialize> Serialize for Outputs<T> {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        match self.outputs.as_slice() {
+            [(_, output)] if !self.multiple => output.serialize(serializer),
+            outputs => {
+                let mut map = serializer.serialize_map(Some(outputs.len()))?;
+                for (name, output) in outputs {
+                    map.serialize_entry(name, output)?;
+                }
+                map.end()
+            }
+        }
+    }
+}
+
+impl<T: Serialize> Outputs<T> {
+    fn collect<F: Fn(&Input) -> Result<T>>(inputs: &Inputs, f: F) -> Result<Self> {
+        let outputs = inputs
+            .read()
+            .map(|input| {
+                let input = input?;
+                let output =
+                    f(&input).wrap_err_with(|| format!("failed to parse {}", input.name))?;
+                Ok((input.name, output))
+            })
+            .collect::<Result<_>>()?;
+        Ok(Outputs {
+            multiple: inputs.is_multiple(),
+            outputs,
+        })
+    }
+
+    fn write_json(&self) -> Result<()> {
+        let mut out = stdout().lock();
+        serde_json::to_writer_pretty(&mut out, self).into_diagnostic()?;
+        writeln!(out).into_diagnostic()
+    }
+}
+
+/// Name, team, classes and totals for a single player, as written by `stats --format csv`
+#[derive(Serialize)]
+pub struct PlayerRow {
+    log: String,
+    steam_id: SteamId3,
+    name: String,
+    team: Team,
+    classes: String,
+    kills: u32,
+    assists: u32,
+    deaths: u32,
+    damage: u32,
+    healing: u32,
+    charges: u32,
+    drops: u32,
+}
+
+impl CsvRows for ScoreboardHandlerPerSubjectOutput {
+    type Row = PlayerRow;
+
+    fn rows(&self, log: &str, steam_id: &SteamId3) -> Vec<Self::Row> {
+        if self.info.team == Team::Spectator {
+            return Vec::new();
+        }
+        let summary = SummaryRow::new(log, steam_id, &self.stats);
+        let classes: Vec<_> = self
+            .info
+            .classes
+            .iter()
+            .map(|class| class.as_str())
+            .collect();
+        vec![PlayerRow {
+            log: summary.log,
+            steam_id: summary.steam_id,
+            name: self.info.name.clone(),
+            team: self.info.team,
+            classes: classes.join(" "),
+            kills: summary.kills,
+            assists: summary.assists,
+            deaths: summary.deaths,
+            damage: summary.damage,
+            healing: summary.healing,
+            charges: summary.charges,
+            drops: summary.drops,
+        }]
+    }
+}
+
+/// A single player in the scoreboard
+struct ScoreboardRow {
+    info: PlayerInfo,
+    summary: SummaryRow,
+}
+
+fn scoreboard(input: &Input) -> Result<Vec<ScoreboardRow>> {
+    let (_, players) = parse_bytes_with_handler::<ScoreboardHandler>(&input.content)?;
+    let mut rows: Vec<_> = players
+        .into_iter()
+        .filter(|(_, player)| player.info.team != Team::Spectator)
+        .map(|(steam_id, player)| ScoreboardRow {
+            summary: SummaryRow::new(&input.name, &steam_id, &player.stats),
+            info: player.info,
+        })
+        .collect();
+    rows.sort_by(|a, b| {
+        a.info
+            .team
+            .cmp(&b.info.team)
+            .then(b.summary.kills.cmp(&a.summary.kills))
+    });
+    Ok(rows)
+}
+
+fn summary(inputs: &Inputs) -> Result<()> {
+    let mut out = BufWriter::new(stdout().lock());
+    for (index, input) in inputs.read().enumerate() {
+        let input = input?;
+        let rows =
+            scoreboard(&input).wrap_err_with(|| format!("failed to parse {}", input.name))?;
+        if inputs.is_multiple() {
+            if index > 0 {
+                writeln!(out).into_diagnostic()?;
+            }
+            writeln!(out, "{}", input.name).into_diagnostic()?;
+        }
+        write_scoreboard(&mut out, &rows).into_diagnostic()?;
+    }
+    out.flush().into_diagnostic()
+}
+
+fn write_scoreboard(out: &mut impl Write, rows: &[ScoreboardRow]) -> std::io::Result<()> {
+    const HEADERS: [&str; 10] = [
+        "Team", "Name", "Classes", "K", "A", "D", "DA", "Heal", "Ubers", "Drops",
+    ];
+    // the first columns are text and aligned to the left, the rest are numbers aligned to the right
+    const TEXT_COLUMNS: usize = 3;
 
-    let log = parse(&content)?;
+    let cells: Vec<[String; 10]> = rows
+        .iter()
+        .map(|row| {
+            [
+                row.info.team.as_str().to_string(),
+                row.info.name.clone(),
+                row.info
+                    .classes
+                    .iter()
+                    .map(|class| class.as_str())
+                    .collect::<Vec<_>>()
+                    .join(" "),
+                row.summary.kills.to_string(),
+                row.summary.assists.to_string(),
+                row.summary.deaths.to_string(),
+                row.summary.damage.to_string(),
+                row.summary.healing.to_string(),
+                row.summary.charges.to_string(),
+                row.summary.drops.to_string(),
+            ]
+        })
+        .collect();
+    let mut widths = HEADERS.map(str::len);
+    for row in &cells {
+        for (width, cell) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let headers = HEADERS.map(String::from);
+    for row in std::iter::once(&headers).chain(cells.iter()) {
+        let line: Vec<_> = row
+            .iter()
+            .zip(widths.iter())
+            .enumerate()
+            .map(|(column, (cell, width))| {
+                let padding = " ".repeat(width - cell.chars().count());
+                if column < TEXT_COLUMNS {
+                    format!("{}{}", cell, padding)
+                } else {
+                    format!("{}{}", padding, cell)
+                }
+            })
+            .collect();
+        writeln!(out, "{}", line.join("  ").trim_end())?;
+    }
+    Ok(())
+}
+
+fn stats(inputs: &Inputs, format: Format, table: Option<Table>) -> Result<()> {
+    match (format, table.unwrap_or(Table::Summary)) {
+        (Format::Json, _) if table.is_some() => {
+            Err(miette!("--table can only be used with --format csv"))
+        }
+        (Format::Json, _) => {
+            Outputs::collect(inputs, |input| Ok(parse_bytes(&input.content)?))?.write_json()
+        }
+        (Format::Csv, Table::Summary) => {
+            write_csv::<ScoreboardHandler, _, _>(inputs, |player| player)
+        }
+        (Format::Csv, Table::Players) => {
+            write_csv::<PlayerInfoHandler, _, _>(inputs, |player| player)
+        }
+        (Format::Csv, Table::Classes) => {
+            write_csv::<LogHandler, _, _>(inputs, |player| player.class_stats)
+        }
+        (Format::Csv, Table::Medic) => {
+            write_csv::<LogHandler, _, _>(inputs, |player| player.medic_stats)
+        }
+        (Format::Csv, Table::HealSpread) => {
+            write_csv::<LogHandler, _, _>(inputs, |player| player.heal_spread)
+        }
+    }
+}
+
+/// Write the per-player output of all inputs as a single csv table
+fn write_csv<H, T, F>(inputs: &Inputs, f: F) -> Result<()>
+where
+    H: EventHandler,
+    T: CsvRows,
+    F: Fn(H::PerSubjectOutput) -> T,
+{
+    let mut writer = CsvWriter::new(stdout().lock());
+    for input in inputs.read() {
+        let input = input?;
+        let (_, players) = parse_bytes_with_handler::<H>(&input.content)
+            .wrap_err_with(|| format!("failed to parse {}", input.name))?;
+        let players: BTreeMap<_, _> = players
+            .into_iter()
+            .map(|(steam_id, player)| (steam_id, f(player)))
+            .collect();
+        writer.write_log(&input.name, &players)?;
+    }
+    Ok(writer.flush()?)
+}
+
+fn chat(inputs: &Inputs) -> Result<()> {
+    let mut out = BufWriter::new(stdout().lock());
+    for (index, input) in inputs.read().enumerate() {
+        let input = input?;
+        let (log, _) = parse_bytes(&input.content)
+            .wrap_err_with(|| format!("failed to parse {}", input.name))?;
+        if inputs.is_multiple() {
+            if index > 0 {
+                writeln!(out).into_diagnostic()?;
+            }
+            writeln!(out, "{}", input.name).into_diagnostic()?;
+        }
+        for message in log.chat {
+            let team = match message.chat_type {
+                ChatType::All => "",
+                ChatType::Team => " (team)",
+            };
+            writeln!(
+                out,
+                "[{:02}:{:02}] {}{}: {}",
+                message.time / 60,
+                message.time % 60,
+                message.name,
+                team,
+                message.message
+            )
+            .into_diagnostic()?;
+        }
+    }
+    out.flush().into_diagnostic()
+}
+
+fn validate_logs(inputs: &Inputs) -> Result<ExitCode> {
+    let mut valid = true;
+    for input in inputs.read() {
+        let input = input?;
+        let errors = validate_bytes(&input.content);
+        if errors.is_empty() {
+            println!("{}: ok", input.name);
+        } else {
+            valid = false;
+            println!("{}: {} invalid lines", input.name, errors.len());
+        }
+        for error in errors {
+            eprintln!("{:?}", Report::new(error).wrap_err(input.name.clone()));
+        }
+    }
+    Ok(if valid {
+        ExitCode::SUCCESS
+    } else {
+        ExitCode::FAILURE
+    })
+}
+
+fn lobby(inputs: &Inputs) -> Result<()> {
+    Outputs::collect(inputs, |input| {
+        let (settings, _) = parse_bytes_with_handler::<LobbySettingsHandler>(&input.content)?;
+        Ok(settings)
+    })?
+    .write_json()
+}
+
+fn combine_logs(paths: &[PathBuf]) -> Result<()> {
+    let contents = paths
+        .iter()
+        .map(|path| Ok(read_log(path)?))
+        .collect::<Result<Vec<_>>>()?;
+    let logs: Vec<&str> = contents.iter().map(String::as_str).collect();
+    let log = combine(&logs)?;
+
+    serde_json::to_writer_pretty(stdout().lock(), &log).into_diagnostic()
+}
+
+/// Filters for the events to export, an empty filter matches all events
+#[derive(Default)]
+struct EventFilter {
+    types: Vec<String>,
+    players: Vec<String>,
+}
+
+impl EventFilter {
+    fn matches_type(&self, raw: &RawEvent) -> bool {
+        self.types.is_empty()
+            || self
+                .types
+                .iter()
+                .any(|ty| ty.eq_ignore_ascii_case(&format!("{:?}", raw.ty)))
+    }
+
+    fn matches_subject(&self, subject: &EventSubject) -> bool {
+        self.players.is_empty()
+            || self.players.iter().any(|player| {
+                subject.steam3.as_deref() == Some(player.as_str())
+                    || subject.name.as_deref() == Some(player.as_str())
+            })
+    }
+}
+
+#[derive(Serialize)]
+struct EventSubject {
+    steam3: Option<String>,
+    name: Option<String>,
+    team: Option<Team>,
+}
+
+impl From<SubjectData> for EventSubject {
+    fn from(subject: SubjectData) -> Self {
+        let (steam3, name, team) = match subject {
+            SubjectData::Player {
+                name,
+                steam_id,
+                team,
+                ..
+            } => (Some(steam_id.steam3()), Some(name), Some(team)),
+            SubjectData::Bot { name, team, .. } => (None, Some(name), Some(team)),
+            SubjectData::Team(team) => (None, None, Some(team)),
+            SubjectData::System(name) | SubjectData::MalformedPlayer(name) => {
+                (None, Some(name), None)
+            }
+            SubjectData::Console => (None, Some("Console".into()), None),
+            SubjectData::World => (None, Some("World".into()), None),
+        };
+        EventSubject { steam3, name, team }
+    }
+}
+
+/// A single line of the exported events
+#[derive(Serialize)]
+struct EventLine<'a> {
+    /// The log the event is from, only set when exporting multiple logs
+    #[serde(skip_serializing_if = "Option::is_none")]
+    log: Option<&'a str>,
+    /// Seconds since the first event in the log
+    time: i64,
+    date: NaiveDateTime,
+    subject: EventSubject,
+    #[serde(flatten)]
+    event: OwnedGameEvent,
+}
+
+fn events(inputs: &Inputs, filter: &EventFilter) -> Result<()> {
+    let mut out = BufWriter::new(stdout().lock());
+    for input in inputs.read() {
+        let input = input?;
+        let log = inputs.is_multiple().then_some(input.name.as_str());
+
+        let mut start: Option<NaiveDateTime> = None;
+        for line in raw_events_bytes(&input.content) {
+            let raw = match line.parse() {
+                Ok(raw) => raw,
+                Err(Error::Skip | Error::Incomplete) => continue,
+                Err(e) => {
+                    return Err(line.locate(&input.content, e))
+                        .wrap_err_with(|| format!("failed to parse {}", input.name))
+                }
+            };
+            let date: NaiveDateTime = raw.date.try_into().into_diagnostic()?;
+            let start = *start.get_or_insert(date);
+
+            if !GameEvent::is_supported(raw.ty) || !filter.matches_type(&raw) {
+                continue;
+            }
+            let subject = match SubjectData::try_from(&raw.subject) {
+                Ok(subject) => EventSubject::from(subject),
+                Err(e) => {
+                    eprintln!("skipping event with invalid subject: {}", e);
+                    continue;
+                }
+            };
+            if !filter.matches_subject(&subject) {
+                continue;
+            }
+            let event = match GameEvent::parse(&raw)
+                .map_err(Error::from)
+                .and_then(|event| Ok(event.into_owned()?))
+            {
+                Ok(event) => event,
+                Err(e) => {
+                    eprintln!("skipping malformed event: {}", e);
+                    continue;
+                }
+            };
+
+            let line = EventLine {
+                log,
+                time: date.signed_duration_since(start).num_seconds(),
+                date,
+                subject,
+                event,
+            };
+            serde_json::to_writer(&mut out, &line).into_diagnostic()?;
+            writeln!(out).into_diagnostic()?;
+        }
+    }
+    out.flush().into_diagnostic()
+}
+
+fn anonymize(
+    salt: String,
+    mapping: Option<PathBuf>,
+    scrub_chat: bool,
+    path: Option<PathBuf>,
+) -> Result<()> {
+    let input = Input::read(path.as_deref().unwrap_or_else(|| Path::new("-")))?;
+    let mut anonymizer = Anonymizer::new(salt).scrub_chat(scrub_chat);
+    if let Some(mapping) = mapping {
+        let mapping = fs::read_to_string(mapping).into_diagnostic()?;
+        anonymizer = anonymizer.with_mapping_json(&mapping).into_diagnostic()?;
+    }
+
+    let mut out = BufWriter::new(stdout().lock());
+    out.write_all(&anonymizer.anonymize_bytes(&input.content))
+        .into_diagnostic()?;
+    out.flush().into_diagnostic()
+}
+
+fn career(index_path: Option<PathBuf>, player: Option<String>, dir: &Path) -> Result<()> {
+    let mut index = match &index_path {
+        Some(path) if path.exists() => CareerIndex::load(path)?,
+        _ => CareerIndex::new(),
+    };
+
+    let update = index.update_dir(dir);
+    eprintln!(
+        "{} logs added, {} already indexed, {} failed",
+        update.added,
+        update.skipped,
+        update.failed.len()
+    );
+    for (path, error) in update.failed {
+        eprintln!(
+            "{:?}",
+            Report::new(error).wrap_err(path.display().to_string())
+        );
+    }
+    if let Some(path) = index_path {
+        index.save(path)?;
+    }
+
+    let mut out = stdout().lock();
+    match player {
+        Some(player) => {
+            let steam_id = SteamID::try_from(player.as_str())
+                .map_err(|_| miette!("invalid steam id {}", player))?;
+            let career = index
+                .player(&SteamId3(steam_id))
+                .ok_or_else(|| miette!("{} is not in the index", player))?;
+            serde_json::to_writer_pretty(&mut out, career).into_diagnostic()?;
+        }
+        None => serde_json::to_writer_pretty(&mut out, &index).into_diagnostic()?,
+    }
+    writeln!(out).into_diagnostic()
+}
 
-    serde_json::to_writer_pretty(stdout().lock(), &log).unwrap();
+#[cfg(feature = "sqlite")]
+fn import(database: &Path, inputs: &Inputs) -> Result<()> {
+    use tf_log_parser::sqlite::{Database, Import};
 
+    let mut database = Database::open(database)?;
+    for input in inputs.read() {
+        let input = input?;
+        match database
+            .import(&input.name, &input.content)
+            .wrap_err_with(|| format!("failed to import {}", input.name))?
+        {
+            Import::Imported(id) => println!("{}: imported as log {}", input.name, id),
+            Import::Existing(id) => println!("{}: already imported as log {}", input.name, id),
+        }
+    }
     Ok(())
 }
@@ -0,0 +1,200 @@
+use crate::{EventHandler, HandlerOutput, LogHandler, SteamId3};
+use std::collections::BTreeMap;
+use std::iter::FromIterator;
+
+/// Output that can be combined with the output of another log
+///
+/// Counts and totals are added up, outputs that describe a single log like lobby settings are replaced by the
+/// output of the later log.
+pub trait Merge {
+    /// Merge the output of a later log into this output
+    fn merge(&mut self, other: Self);
+}
+
+macro_rules! impl_merge_saturating {
+    ($($ty:ty),*) => {
+        $(
+            impl Merge for $ty {
+                fn merge(&mut self, other: Self) {
+                    *self = self.saturating_add(other);
+                }
+            }
+        )*
+    };
+}
+
+impl_merge_saturating!(u8, u16, u32, u64);
+
+impl Merge for f32 {
+    fn merge(&mut self, other: Self) {
+        *self += other;
+    }
+}
+
+impl Merge for () {
+    fn merge(&mut self, _other: Self) {}
+}
+
+impl<T> Merge for Vec<T> {
+    fn merge(&mut self, mut other: Self) {
+        self.append(&mut other);
+    }
+}
+
+impl<K: Ord, V: Merge> Merge for BTreeMap<K, V> {
+    fn merge(&mut self, other: Self) {
+        for (key, value) in other {
+            match self.get_mut(&key) {
+                Some(existing) => existing.merge(value),
+                None => {
+                    self.insert(key, value);
+                }
+            }
+        }
+    }
+}
+
+impl<T: Merge> Merge for Option<T> {
+    fn merge(&mut self, other: Self) {
+        match (self.as_mut(), other) {
+            (Some(existing), Some(other)) => existing.merge(other),
+            (None, other) => *self = other,
+            (_, None) => {}
+        }
+    }
+}
+
+/// Errors are only kept if there is no successful output to merge with
+impl<T: Merge, E> Merge for Result<T, E> {
+    fn merge(&mut self, other: Self) {
+        match (self.as_mut(), other) {
+            (Ok(existing), Ok(other)) => existing.merge(other),
+            (Ok(_), Err(_)) => {}
+            (Err(_), other) => *self = other,
+        }
+    }
+}
+
+impl<A: Merge, B: Merge> Merge for (A, B) {
+    fn merge(&mut self, other: Self) {
+        self.0.merge(other.0);
+        self.1.merge(other.1);
+    }
+}
+
+/// Totals of the output of many logs, with the per-player output merged by steam id
+///
+/// ```no_run
+/// # use tf_log_parser::{Aggregator, parse, read_log};
+/// let mut season: Aggregator = Aggregator::default();
+/// for path in ["week1.log", "week2.log"] {
+///     season.add(parse(&read_log(path)?)?);
+/// }
+/// let (_, players) = season.finish();
+/// # Ok::<(), tf_log_parser::Error>(())
+/// ```
+pub struct Aggregator<Handler: EventHandler = LogHandler> {
+    logs: usize,
+    global: Handler::GlobalOutput,
+    players: BTreeMap<SteamId3, Handler::PerSubjectOutput>,
+}
+
+impl<Handler: EventHandler> Default for Aggregator<Handler>
+where
+    Handler::GlobalOutput: Default,
+{
+    fn default() -> Self {
+        Aggregator {
+            logs: 0,
+            global: Handler::GlobalOutput::default(),
+            players: BTreeMap::new(),
+        }
+    }
+}
+
+impl<Handler: EventHandler> Aggregator<Handler>
+where
+    Handler::GlobalOutput: Merge,
+    Handler::PerSubjectOutput: Merge,
+{
+    /// Add the output of the next log
+    pub fn add(&mut self, (global, players): HandlerOutput<Handler>) {
+        self.logs += 1;
+        self.global.merge(global);
+        Merge::merge(&mut self.players, players);
+    }
+
+    /// The number of logs that have been added
+    pub fn logs(&self) -> usize {
+        self.logs
+    }
+
+    pub fn global(&self) -> &Handler::GlobalOutput {
+        &self.global
+    }
+
+    pub fn players(&self) -> &BTreeMap<SteamId3, Handler::PerSubjectOutput> {
+        &self.players
+    }
+
+    pub fn finish(self) -> HandlerOutput<Handler> {
+        (self.global, self.players)
+    }
+}
+
+impl<Handler: EventHandler> Extend<HandlerOutput<Handler>> for Aggregator<Handler>
+where
+    Handler::GlobalOutput: Merge,
+    Handler::PerSubjectOutput: Merge,
+{
+    fn extend<I: IntoIterator<Item = HandlerOutput<Handler>>>(&mut self, outputs: I) {
+        for output in outputs {
+            self.add(output);
+        }
+    }
+}
+
+impl<Handler: EventHandler> FromIterator<HandlerOutput<Handler>> for Aggregator<Handler>
+where
+    Handler::GlobalOutput: Merge + Default,
+    Handler::PerSubjectOutput: Merge,
+{
+    fn from_iter<I: IntoIterator<Item = HandlerOutput<Handler>>>(outputs: I) -> Self {
+        let mut aggregator = Aggregator::default();
+        aggregator.extend(outputs);
+        aggregator
+    }
+}
+
+#[test]
+fn test_aggregate() {
+    use crate::{parse, Class};
+
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (global, players) = parse(&log).unwrap();
+
+    let season: Aggregator = (0..3).map(|_| parse(&log).unwrap()).collect();
+    assert_eq!(3, season.logs());
+    assert_eq!(global.chat.len() * 3, season.global().chat.len());
+    assert_eq!(players.len(), season.players().len());
+    for (steam_id, player) in &players {
+        let total = &season.players()[steam_id];
+        assert_eq!(
+            player.class_stats.damage.total() * 3,
+            total.class_stats.damage.total()
+        );
+        assert_eq!(
+            player.class_stats.kills[Class::Scout] * 3,
+            total.class_stats.kills[Class::Scout]
+        );
+        assert_eq!(player.heal_spread.total() * 3, total.heal_spread.total());
+        assert_eq!(
+            player.medic_stats.charge_count * 3,
+            total.medic_stats.charge_count
+        );
+        assert!(
+            (player.medic_stats.avg_uber_length() - total.medic_stats.avg_uber_length()).abs()
+                < 0.001
+        );
+    }
+}
@@ -3,9 +3,10 @@ use crate::event::GameEvent;
 use crate::module::GlobalData;
 use crate::raw_event::RawEventType;
 use crate::{EventMeta, SubjectMap};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use steamid_ng::SteamID;
 
+#[derive(Clone)]
 struct BareChatMessage {
     pub time: u32,
     pub subject: SubjectId,
@@ -13,7 +14,7 @@ struct BareChatMessage {
     pub chat_type: ChatType,
 }
 
-#[derive(Serialize, PartialEq)]
+#[derive(Serialize, Deserialize, PartialEq)]
 pub struct ChatMessage {
     pub time: u32,
     pub name: String,
@@ -38,13 +39,13 @@ impl ChatMessage {
     }
 }
 
-#[derive(Serialize, PartialEq)]
+#[derive(Clone, Serialize, Deserialize, PartialEq)]
 pub enum ChatType {
     All,
     Team,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ChatMessages(Vec<BareChatMessage>);
 
 impl GlobalData for ChatMessages {
@@ -2,28 +2,38 @@ use crate::common::{Class, ClassMap, SubjectId};
 use crate::event::{DamageEvent, GameEvent, RoleChangeEvent, SpawnEvent};
 use crate::module::EventHandler;
 use crate::raw_event::{RawEventType, RawSubject};
-use crate::{EventMeta, SubjectData, SubjectMap};
-use serde::Serialize;
+use crate::{EventMeta, Merge, ParseOptions, SubjectData, SubjectMap};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 
-#[derive(Debug, Serialize, Default, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, PartialEq)]
 pub struct ClassStats {
-    pub kills: ClassMap<u16>,
-    pub deaths: ClassMap<u16>,
-    pub assists: ClassMap<u16>,
-    pub damage: ClassMap<u16>,
+    pub kills: ClassMap<u32>,
+    pub deaths: ClassMap<u32>,
+    pub assists: ClassMap<u32>,
+    pub damage: ClassMap<u32>,
 }
 
-#[derive(Default)]
+impl Merge for ClassStats {
+    fn merge(&mut self, other: Self) {
+        self.kills.merge(other.kills);
+        self.deaths.merge(other.deaths);
+        self.assists.merge(other.assists);
+        self.damage.merge(other.damage);
+    }
+}
+
+#[derive(Default, Clone)]
 pub struct ClassStatsHandler {
     active: bool,
+    outside_rounds: bool,
     data: BTreeMap<SubjectId, ClassStatData>,
 }
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ClassStatData {
     class: Class,
-    deaths: ClassMap<u16>,
+    deaths: ClassMap<u32>,
 }
 
 impl ClassStatsHandler {
@@ -38,6 +48,10 @@ impl ClassStatsHandler {
     fn data_mut(&mut self, id: SubjectId) -> &mut ClassStatData {
         self.data.entry(id).or_default()
     }
+
+    fn counting(&self) -> bool {
+        self.active || self.outside_rounds
+    }
 }
 
 impl EventHandler for ClassStatsHandler {
@@ -45,6 +59,13 @@ impl EventHandler for ClassStatsHandler {
     type PerSubjectData = ClassStats;
     type PerSubjectOutput = ClassStats;
 
+    fn with_options(options: &ParseOptions) -> Self {
+        ClassStatsHandler {
+            outside_rounds: options.class_stats_outside_rounds,
+            ..ClassStatsHandler::default()
+        }
+    }
+
     fn does_handle(ty: RawEventType) -> bool {
         matches!(
             ty,
@@ -76,7 +97,7 @@ impl EventHandler for ClassStatsHandler {
             GameEvent::RoundWin(_) => {
                 self.active = false;
             }
-            GameEvent::Killed(kill) if self.active => {
+            GameEvent::Killed(kill) if self.counting() => {
                 if let Ok(target) = kill.target.id() {
                     let subject_class = self.data.get(&subject).map(|data| data.class);
                     let target_data = self.data_mut(target);
@@ -88,7 +109,7 @@ impl EventHandler for ClassStatsHandler {
                         subject_data.kills[target_data.class].saturating_add(1);
                 }
             }
-            GameEvent::KillAssist(assist) if self.active => {
+            GameEvent::KillAssist(assist) if self.counting() => {
                 if let Some(target_class) = self.get_class(&assist.target) {
                     subject_data.assists[target_class] =
                         subject_data.assists[target_class].saturating_add(1);
@@ -98,10 +119,10 @@ impl EventHandler for ClassStatsHandler {
                 damage: Some(damage),
                 target,
                 ..
-            }) if self.active && damage > &0 && damage < &1500 => {
+            }) if self.counting() && damage > &0 && damage < &1500 => {
                 if let Some(target_class) = self.get_class(target) {
                     subject_data.damage[target_class] =
-                        subject_data.damage[target_class].saturating_add(*damage as u16);
+                        subject_data.damage[target_class].saturating_add(*damage as u32);
                 }
             }
             _ => {}
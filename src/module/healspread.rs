@@ -2,14 +2,32 @@ use crate::common::{SteamId3, SubjectId};
 use crate::event::GameEvent;
 use crate::module::PlayerSpecificData;
 use crate::raw_event::RawEventType;
-use crate::EventMeta;
-use serde::Serialize;
+use crate::{EventMeta, Merge};
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::convert::TryFrom;
 
-#[derive(Default, Serialize, PartialEq)]
+#[derive(Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct HealSpread(BTreeMap<SteamId3, u32>);
 
+impl HealSpread {
+    /// Iterate over the amount healed per target
+    pub fn iter(&self) -> impl Iterator<Item = (&SteamId3, &u32)> {
+        self.0.iter()
+    }
+
+    /// The total amount healed
+    pub fn total(&self) -> u32 {
+        self.0.values().sum()
+    }
+}
+
+impl Merge for HealSpread {
+    fn merge(&mut self, other: Self) {
+        Merge::merge(&mut self.0, other.0);
+    }
+}
+
 impl PlayerSpecificData for HealSpread {
     type Output = HealSpread;
 
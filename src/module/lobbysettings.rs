@@ -2,15 +2,15 @@ use crate::common::SubjectId;
 use crate::event::GameEvent;
 use crate::module::GlobalData;
 use crate::raw_event::RawEventType;
-use crate::{EventMeta, SubjectMap};
+use crate::{EventMeta, Merge, SubjectMap};
 use chrono::{DateTime, FixedOffset, NaiveDateTime, TimeZone, Utc};
-use serde::{Serialize, Serializer};
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
 use std::num::ParseIntError;
 use std::str::{FromStr, ParseBoolError};
 use steamid_ng::SteamID;
 use thiserror::Error;
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum GameType {
     Sixes,
     Highlander,
@@ -28,7 +28,7 @@ impl FromStr for GameType {
     }
 }
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub enum Location {
     Europe,
     NorthAmerica,
@@ -46,7 +46,7 @@ impl FromStr for Location {
     }
 }
 
-#[derive(Debug, Default, Serialize, PartialEq)]
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
 pub struct LobbyLeader {
     name: String,
     steam_id: SteamID,
@@ -71,7 +71,7 @@ impl FromStr for LobbyLeader {
     }
 }
 
-#[derive(Debug, Serialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
 pub struct Settings {
     id: u32,
     leader: LobbyLeader,
@@ -88,6 +88,13 @@ pub struct Settings {
     server: String,
 }
 
+/// Settings describe a single lobby, so the settings of the later log are kept
+impl Merge for Settings {
+    fn merge(&mut self, other: Self) {
+        *self = other;
+    }
+}
+
 impl Default for Settings {
     fn default() -> Self {
         Self::with_id(0)
@@ -114,7 +121,7 @@ impl Settings {
     }
 }
 
-#[derive(Debug, Error, PartialEq)]
+#[derive(Debug, Clone, Error, PartialEq)]
 pub enum LobbySettingsError {
     #[error("Malformed lobby id: {0}")]
     InvalidLobbyId(String),
@@ -126,12 +133,27 @@ pub enum LobbySettingsError {
     UnknownTimezone(String),
     #[error("Malformed leader: {0}")]
     MalformedLeader(String),
+    /// A setting that failed to parse as a bool, number or date, with the message of the parse error
     #[error("{0}")]
-    InvalidBool(#[from] ParseBoolError),
-    #[error("{0}")]
-    InvalidInt(#[from] ParseIntError),
-    #[error("{0}")]
-    InvalidDate(#[from] chrono::ParseError),
+    Other(String),
+}
+
+impl From<ParseBoolError> for LobbySettingsError {
+    fn from(e: ParseBoolError) -> Self {
+        LobbySettingsError::Other(e.to_string())
+    }
+}
+
+impl From<ParseIntError> for LobbySettingsError {
+    fn from(e: ParseIntError) -> Self {
+        LobbySettingsError::Other(e.to_string())
+    }
+}
+
+impl From<chrono::ParseError> for LobbySettingsError {
+    fn from(e: chrono::ParseError) -> Self {
+        LobbySettingsError::Other(e.to_string())
+    }
 }
 
 impl Serialize for LobbySettingsError {
@@ -143,6 +165,57 @@ impl Serialize for LobbySettingsError {
     }
 }
 
+impl LobbySettingsError {
+    /// Recreate an error from its message
+    fn from_message(message: &str) -> Self {
+        let value = |prefix: &str| message.strip_prefix(prefix).map(String::from);
+        value("Malformed lobby id: ")
+            .map(LobbySettingsError::InvalidLobbyId)
+            .or_else(|| value("Unknown game type: ").map(LobbySettingsError::UnknownGameType))
+            .or_else(|| value("Unknown location: ").map(LobbySettingsError::UnknownLocation))
+            .or_else(|| {
+                value("Unknown timezone in date: ").map(LobbySettingsError::UnknownTimezone)
+            })
+            .or_else(|| value("Malformed leader: ").map(LobbySettingsError::MalformedLeader))
+            .unwrap_or_else(|| LobbySettingsError::Other(message.into()))
+    }
+}
+
+impl<'de> Deserialize<'de> for LobbySettingsError {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        let message = String::deserialize(deserializer)?;
+        Ok(LobbySettingsError::from_message(&message))
+    }
+}
+
+#[test]
+fn test_error_round_trip() {
+    let errors = vec![
+        LobbySettingsError::InvalidLobbyId("foo".into()),
+        LobbySettingsError::UnknownGameType("4v4".into()),
+        LobbySettingsError::UnknownLocation("Asia".into()),
+        LobbySettingsError::UnknownTimezone("Sun Jan 01 00:00:00 UTC 2023".into()),
+        LobbySettingsError::MalformedLeader("bar (baz".into()),
+        "yes".parse::<bool>().unwrap_err().into(),
+        "-".parse::<u32>().unwrap_err().into(),
+        NaiveDateTime::parse_from_str("2020", "%Y")
+            .unwrap_err()
+            .into(),
+    ];
+    for error in errors {
+        let json = serde_json::to_string(&error).unwrap();
+        assert_eq!(error, serde_json::from_str(&json).unwrap(), "{}", json);
+    }
+
+    let settings: Option<Result<Settings, LobbySettingsError>> = Some(Ok(Settings::with_id(1)));
+    let json = serde_json::to_string(&settings).unwrap();
+    assert_eq!(settings, serde_json::from_str(&json).unwrap());
+}
+
+#[derive(Clone)]
 pub enum LobbySettingsHandler {
     NotAvailable,
     Active(Settings),
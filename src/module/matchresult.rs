@@ -0,0 +1,95 @@
+use crate::common::{SubjectId, Team};
+use crate::event::GameEvent;
+use crate::module::GlobalData;
+use crate::raw_event::RawEventType;
+use crate::{EventMeta, Merge, SubjectMap};
+use serde::{Deserialize, Serialize};
+
+/// Score of both teams at the end of the log
+///
+/// Uses the final scores reported by the server, or the number of rounds won if the log has no final scores.
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct MatchResult {
+    pub red: u8,
+    pub blue: u8,
+}
+
+impl MatchResult {
+    /// The team with the highest score, `None` for a draw
+    pub fn winner(&self) -> Option<Team> {
+        match self.red.cmp(&self.blue) {
+            std::cmp::Ordering::Greater => Some(Team::Red),
+            std::cmp::Ordering::Less => Some(Team::Blue),
+            std::cmp::Ordering::Equal => None,
+        }
+    }
+
+    pub fn score(&self, team: Team) -> u8 {
+        match team {
+            Team::Red => self.red,
+            Team::Blue => self.blue,
+            Team::Spectator => 0,
+        }
+    }
+}
+
+/// A result describes a single match, so the result of the later log is kept
+impl Merge for MatchResult {
+    fn merge(&mut self, other: Self) {
+        *self = other;
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct MatchResultHandler {
+    rounds: MatchResult,
+    final_score: Option<MatchResult>,
+}
+
+impl GlobalData for MatchResultHandler {
+    type Output = MatchResult;
+
+    fn does_handle(ty: RawEventType) -> bool {
+        matches!(ty, RawEventType::RoundWin | RawEventType::FinalScore)
+    }
+
+    fn handle_event(&mut self, _meta: &EventMeta, subject: SubjectId, event: &GameEvent) {
+        match event {
+            GameEvent::RoundWin(win) => match win.team.and_then(|team| team.parse().ok()) {
+                Some(Team::Red) => self.rounds.red = self.rounds.red.saturating_add(1),
+                Some(Team::Blue) => self.rounds.blue = self.rounds.blue.saturating_add(1),
+                _ => {}
+            },
+            GameEvent::FinalScore(score) => {
+                let final_score = self.final_score.get_or_insert_with(MatchResult::default);
+                match subject {
+                    SubjectId::Team(Team::Red) => final_score.red = score.score,
+                    SubjectId::Team(Team::Blue) => final_score.blue = score.score,
+                    _ => {}
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self, _subjects: &SubjectMap) -> Self::Output {
+        self.final_score.unwrap_or(self.rounds)
+    }
+}
+
+#[test]
+fn test_match_result() {
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (result, _) = crate::parse_with_handler::<MatchResultHandler>(&log).unwrap();
+    assert_eq!(MatchResult { red: 2, blue: 5 }, result);
+    assert_eq!(Some(Team::Blue), result.winner());
+
+    // without final scores the rounds won are counted
+    let without_final: String = log
+        .lines()
+        .filter(|line| !line.contains("final score"))
+        .map(|line| format!("{}\n", line))
+        .collect();
+    let (result, _) = crate::parse_with_handler::<MatchResultHandler>(&without_final).unwrap();
+    assert_eq!(MatchResult { red: 2, blue: 6 }, result);
+}
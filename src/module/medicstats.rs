@@ -2,10 +2,11 @@ use crate::common::SubjectId;
 use crate::event::GameEvent;
 use crate::module::PlayerSpecificData;
 use crate::raw_event::RawEventType;
-use crate::EventMeta;
-use serde::Serialize;
+use crate::{EventMeta, Merge};
+use serde::ser::SerializeStruct;
+use serde::{Deserialize, Serialize, Serializer};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct MedicStatsBuilder {
     advantages_lost: u32,
     biggest_advantage_lost: f32,
@@ -23,18 +24,98 @@ pub struct MedicStatsBuilder {
     drops: u32,
 }
 
-#[derive(Debug, Serialize, Default, PartialEq)]
+/// Medic stats of a player
+///
+/// The averages are calculated from the totals and counts, so the stats of multiple logs can be merged.
+///
+/// The serialized averages are ignored when deserializing.
+#[derive(Debug, Default, PartialEq, Deserialize)]
 pub struct MedicStats {
-    advantages_lost: u32,
-    biggest_advantage_lost: f32,
-    near_full_charge_death: u32,
-    deaths_after_uber: u32,
-    avg_time_before_healing: f32,
-    avg_time_to_build: f32,
-    avg_time_to_use: f32,
-    avg_uber_length: f32,
-    charge_count: u32,
-    drops: u32,
+    pub advantages_lost: u32,
+    pub biggest_advantage_lost: f32,
+    pub near_full_charge_death: u32,
+    pub deaths_after_uber: u32,
+    pub total_time_before_healing: f32,
+    pub start_healing_count: u32,
+    pub total_time_to_build: u32,
+    pub uber_build_count: u32,
+    pub total_time_to_use: f32,
+    pub total_uber_length: f32,
+    pub charge_count: u32,
+    pub drops: u32,
+}
+
+fn average(total: f32, count: u32) -> f32 {
+    if count == 0 {
+        0.0
+    } else {
+        total / count as f32
+    }
+}
+
+impl MedicStats {
+    pub fn avg_time_before_healing(&self) -> f32 {
+        average(self.total_time_before_healing, self.start_healing_count)
+    }
+
+    pub fn avg_time_to_build(&self) -> f32 {
+        average(self.total_time_to_build as f32, self.uber_build_count)
+    }
+
+    pub fn avg_time_to_use(&self) -> f32 {
+        average(self.total_time_to_use, self.charge_count)
+    }
+
+    pub fn avg_uber_length(&self) -> f32 {
+        average(self.total_uber_length, self.charge_count)
+    }
+}
+
+impl Serialize for MedicStats {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: Serializer,
+    {
+        let mut state = serializer.serialize_struct("MedicStats", 16)?;
+        state.serialize_field("advantages_lost", &self.advantages_lost)?;
+        state.serialize_field("biggest_advantage_lost", &self.biggest_advantage_lost)?;
+        state.serialize_field("near_full_charge_death", &self.near_full_charge_death)?;
+        state.serialize_field("deaths_after_uber", &self.deaths_after_uber)?;
+        state.serialize_field("avg_time_before_healing", &self.avg_time_before_healing())?;
+        state.serialize_field("avg_time_to_build", &self.avg_time_to_build())?;
+        state.serialize_field("avg_time_to_use", &self.avg_time_to_use())?;
+        state.serialize_field("avg_uber_length", &self.avg_uber_length())?;
+        state.serialize_field("charge_count", &self.charge_count)?;
+        state.serialize_field("drops", &self.drops)?;
+        state.serialize_field("total_time_before_healing", &self.total_time_before_healing)?;
+        state.serialize_field("start_healing_count", &self.start_healing_count)?;
+        state.serialize_field("total_time_to_build", &self.total_time_to_build)?;
+        state.serialize_field("uber_build_count", &self.uber_build_count)?;
+        state.serialize_field("total_time_to_use", &self.total_time_to_use)?;
+        state.serialize_field("total_uber_length", &self.total_uber_length)?;
+        state.end()
+    }
+}
+
+impl Merge for MedicStats {
+    fn merge(&mut self, other: Self) {
+        self.advantages_lost.merge(other.advantages_lost);
+        self.biggest_advantage_lost = self
+            .biggest_advantage_lost
+            .max(other.biggest_advantage_lost);
+        self.near_full_charge_death
+            .merge(other.near_full_charge_death);
+        self.deaths_after_uber.merge(other.deaths_after_uber);
+        self.total_time_before_healing
+            .merge(other.total_time_before_healing);
+        self.start_healing_count.merge(other.start_healing_count);
+        self.total_time_to_build.merge(other.total_time_to_build);
+        self.uber_build_count.merge(other.uber_build_count);
+        self.total_time_to_use.merge(other.total_time_to_use);
+        self.total_uber_length.merge(other.total_uber_length);
+        self.charge_count.merge(other.charge_count);
+        self.drops.merge(other.drops);
+    }
 }
 
 impl From<MedicStatsBuilder> for MedicStats {
@@ -47,11 +128,12 @@ impl From<MedicStatsBuilder> for MedicStats {
             biggest_advantage_lost: builder.biggest_advantage_lost,
             near_full_charge_death: builder.near_full_charge_death,
             deaths_after_uber: builder.deaths_after_uber,
-            avg_time_before_healing: builder.total_time_before_healing
-                / builder.start_healing_count as f32,
-            avg_time_to_build: builder.total_time_to_build as f32 / builder.uber_build_count as f32,
-            avg_time_to_use: builder.total_time_to_use / builder.charge_count as f32,
-            avg_uber_length: builder.total_uber_length / builder.charge_count as f32,
+            total_time_before_healing: builder.total_time_before_healing,
+            start_healing_count: builder.start_healing_count,
+            total_time_to_build: builder.total_time_to_build,
+            uber_build_count: builder.uber_build_count,
+            total_time_to_use: builder.total_time_to_use,
+            total_uber_length: builder.total_uber_length,
             charge_count: builder.charge_count,
             drops: builder.drops,
         }
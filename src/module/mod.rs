@@ -1,7 +1,7 @@
 use crate::common::SubjectId;
 use crate::event::{EventMeta, GameEvent};
-use crate::raw_event::RawEventType;
-use crate::{Error, RawEvent, SubjectData, SubjectMap};
+use crate::raw_event::{RawEventType, RawSubject};
+use crate::{Error, MatchState, ParseOptions, RawEvent, SubjectData, SubjectMap};
 pub use chat::{ChatMessage, ChatMessages, ChatType};
 use chrono::NaiveDateTime;
 pub use classstats::{ClassStats, ClassStatsHandler};
@@ -9,46 +9,67 @@ pub use healspread::HealSpread;
 pub use lobbysettings::{
     LobbySettingsError, LobbySettingsHandler, Location, Settings as LobbySettings,
 };
+pub use matchresult::{MatchResult, MatchResultHandler};
 pub use medicstats::{MedicStats, MedicStatsBuilder};
+pub use playerinfo::{PlayerInfo, PlayerInfoHandler};
+pub use playtime::{PlayTimeData, PlayTimeHandler};
 use serde::Serialize;
 use std::convert::TryInto;
 use std::marker::PhantomData;
+pub use versus::{Versus, VersusStats};
 
 mod chat;
 mod classstats;
 mod healspread;
 mod lobbysettings;
+mod matchresult;
 mod medicstats;
+mod playerinfo;
+mod playtime;
+mod versus;
 
 pub trait EventHandler: Default {
     type GlobalOutput;
     type PerSubjectData: Default;
     type PerSubjectOutput;
 
+    /// Create the handler with the options used for parsing the log
+    fn with_options(_options: &ParseOptions) -> Self {
+        Self::default()
+    }
+
     fn does_handle(ty: RawEventType) -> bool;
 
+    /// Process an event, the event is handled as if the match is live
     fn process(
         &mut self,
         raw_event: &RawEvent,
         event: &GameEvent,
         start_time: &mut Option<NaiveDateTime>,
         subjects: &mut SubjectMap<Self::PerSubjectData>,
+    ) -> Result<(), Error> {
+        self.process_in_state(raw_event, event, MatchState::InRound, start_time, subjects)
+    }
+
+    /// Process an event that happened while the match was in `state`
+    fn process_in_state(
+        &mut self,
+        raw_event: &RawEvent,
+        event: &GameEvent,
+        state: MatchState,
+        start_time: &mut Option<NaiveDateTime>,
+        subjects: &mut SubjectMap<Self::PerSubjectData>,
     ) -> Result<(), Error> {
         let event_time: NaiveDateTime = raw_event.date.try_into().unwrap();
-        let match_time = match start_time {
-            Some(start_time) => (event_time - *start_time).num_seconds() as u32,
-            None => {
-                *start_time = Some(event_time);
-                0
-            }
-        };
-        let (subject, data) = subjects.insert(&raw_event.subject)?;
-        let meta = EventMeta {
-            time: match_time,
-            subject,
-        };
-        self.handle(&meta, subject, data, &event);
-        Ok(())
+        process_event(
+            self,
+            event_time,
+            (raw_event.subject.id()?, &raw_event.subject),
+            event,
+            state,
+            start_time,
+            subjects,
+        )
     }
 
     fn handle(
@@ -68,7 +89,34 @@ pub trait EventHandler: Default {
     ) -> Self::PerSubjectOutput;
 }
 
-#[derive(Default)]
+/// Pass an event to the handler with the match time and subject of the event resolved
+pub(crate) fn process_event<Handler: EventHandler>(
+    handler: &mut Handler,
+    event_time: NaiveDateTime,
+    (subject, raw_subject): (SubjectId, &RawSubject),
+    event: &GameEvent,
+    state: MatchState,
+    start_time: &mut Option<NaiveDateTime>,
+    subjects: &mut SubjectMap<Handler::PerSubjectData>,
+) -> Result<(), Error> {
+    let match_time = match start_time {
+        Some(start_time) => (event_time - *start_time).num_seconds().max(0) as u32,
+        None => {
+            *start_time = Some(event_time);
+            0
+        }
+    };
+    let data = subjects.insert_with_id(subject, raw_subject);
+    let meta = EventMeta {
+        time: match_time,
+        subject,
+        state,
+    };
+    handler.handle(&meta, subject, data, event);
+    Ok(())
+}
+
+#[derive(Default, Clone)]
 pub struct HandlerStack<Head, Tail> {
     head: Head,
     tail: Tail,
@@ -79,6 +127,13 @@ impl<Head: EventHandler, Tail: EventHandler> EventHandler for HandlerStack<Head,
     type PerSubjectData = (Head::PerSubjectData, Tail::PerSubjectData);
     type PerSubjectOutput = (Head::PerSubjectOutput, Tail::PerSubjectOutput);
 
+    fn with_options(options: &ParseOptions) -> Self {
+        HandlerStack {
+            head: Head::with_options(options),
+            tail: Tail::with_options(options),
+        }
+    }
+
     fn does_handle(ty: RawEventType) -> bool {
         Head::does_handle(ty) || Tail::does_handle(ty)
     }
@@ -125,8 +180,51 @@ macro_rules! count_tts {
     ($($tts:tt)*) => {0usize $(+ $crate::replace_expr!($tts 1usize))*};
 }
 
+/// Combine multiple handlers into a single handler
+///
+/// The child handlers and their per-subject data need to implement `Clone`, and their outputs need to
+/// implement `Deserialize` and [`Merge`](crate::Merge).
 #[macro_export]
 macro_rules! handler {
+    (@deserialize $output:ident, $assoc:ident, $($child:ident: $ty:path,)*) => {
+        impl<'de> serde::Deserialize<'de> for $output
+        {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: serde::Deserializer<'de>,
+            {
+                struct OutputVisitor;
+
+                impl<'de> serde::de::Visitor<'de> for OutputVisitor
+                {
+                    type Value = $output;
+
+                    fn expecting(&self, formatter: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+                        formatter.write_str(stringify!($output))
+                    }
+
+                    fn visit_map<A>(self, mut map: A) -> Result<Self::Value, A::Error>
+                    where
+                        A: serde::de::MapAccess<'de>,
+                    {
+                        // fields with the default value are skipped when serializing
+                        let mut output = $output::default();
+                        while let Some(key) = map.next_key::<String>()? {
+                            match key.as_str() {
+                                $(stringify!($child) => output.$child = map.next_value()?,)*
+                                _ => {
+                                    map.next_value::<serde::de::IgnoredAny>()?;
+                                }
+                            }
+                        }
+                        Ok(output)
+                    }
+                }
+
+                deserializer.deserialize_map(OutputVisitor)
+            }
+        }
+    };
     ($name:ident {$($child:ident: $ty:path),*}) => {
         handler!($name { $($child: $ty,)* } );
     };
@@ -137,6 +235,7 @@ macro_rules! handler {
                 pub $($child: $ty),*
             }
 
+            #[derive(Default, PartialEq)]
             pub struct [<$name GlobalOutput>] {
                 pub $($child: <$ty as $crate::EventHandler>::GlobalOutput),*
             }
@@ -169,6 +268,7 @@ macro_rules! handler {
                 }
             }
 
+            #[derive(Default, PartialEq)]
             pub struct [<$name PerSubjectOutput>] {
                 $(pub $child: <$ty as $crate::EventHandler>::PerSubjectOutput),*
             }
@@ -189,12 +289,50 @@ macro_rules! handler {
                 }
             }
 
+            impl Clone for $name {
+                fn clone(&self) -> Self {
+                    Self {
+                        $($child: self.$child.clone(),)*
+                    }
+                }
+            }
+
+            impl Clone for [<$name PerSubjectData>] {
+                fn clone(&self) -> Self {
+                    Self {
+                        $($child: self.$child.clone(),)*
+                    }
+                }
+            }
+
+            $crate::handler!(@deserialize [<$name GlobalOutput>], GlobalOutput, $($child: $ty,)*);
+            $crate::handler!(@deserialize [<$name PerSubjectOutput>], PerSubjectOutput, $($child: $ty,)*);
+
+            impl $crate::Merge for [<$name GlobalOutput>]
+            {
+                fn merge(&mut self, other: Self) {
+                    $(self.$child.merge(other.$child);)*
+                }
+            }
+
+            impl $crate::Merge for [<$name PerSubjectOutput>]
+            {
+                fn merge(&mut self, other: Self) {
+                    $(self.$child.merge(other.$child);)*
+                }
+            }
 
             impl $crate::EventHandler for $name {
                 type GlobalOutput = [<$name GlobalOutput>];
                 type PerSubjectData = [<$name PerSubjectData>];
                 type PerSubjectOutput = [<$name PerSubjectOutput>];
 
+                fn with_options(options: &$crate::ParseOptions) -> Self {
+                    Self {
+                        $($child: <$ty as $crate::EventHandler>::with_options(options),)*
+                    }
+                }
+
                 fn does_handle(ty: $crate::RawEventType) -> bool {
                     #[allow(unused_imports)]
                     use $crate::EventHandler;
@@ -275,6 +413,12 @@ pub trait PlayerSpecificData: Default {
 #[derive(Default)]
 pub struct PlayerHandler<T: PlayerSpecificData>(PhantomData<T>);
 
+impl<T: PlayerSpecificData> Clone for PlayerHandler<T> {
+    fn clone(&self) -> Self {
+        PlayerHandler(PhantomData)
+    }
+}
+
 impl<T: PlayerSpecificData + Default> EventHandler for PlayerHandler<T> {
     type GlobalOutput = ();
     type PerSubjectData = T;
@@ -0,0 +1,104 @@
+use crate::common::{Class, SubjectId, Team};
+use crate::event::{GameEvent, JoinedTeamEvent, RoleChangeEvent, SpawnEvent};
+use crate::module::EventHandler;
+use crate::raw_event::RawEventType;
+use crate::{EventMeta, Merge, SubjectData, SubjectMap};
+use serde::{Deserialize, Serialize};
+
+/// Name, team and played classes of a player
+#[derive(Debug, Serialize, Deserialize, Default, PartialEq)]
+pub struct PlayerInfo {
+    pub name: String,
+    /// The last team the player joined
+    pub team: Team,
+    /// Classes played, in the order they were first played
+    pub classes: Vec<Class>,
+}
+
+/// The name and team of the later log are kept
+impl Merge for PlayerInfo {
+    fn merge(&mut self, other: Self) {
+        self.name = other.name;
+        self.team = other.team;
+        for class in other.classes {
+            if !self.classes.contains(&class) {
+                self.classes.push(class);
+            }
+        }
+    }
+}
+
+#[derive(Default, Clone)]
+pub struct PlayerInfoData {
+    team: Option<Team>,
+    classes: Vec<Class>,
+}
+
+#[derive(Default, Clone)]
+pub struct PlayerInfoHandler;
+
+impl EventHandler for PlayerInfoHandler {
+    type GlobalOutput = ();
+    type PerSubjectData = PlayerInfoData;
+    type PerSubjectOutput = PlayerInfo;
+
+    fn does_handle(ty: RawEventType) -> bool {
+        matches!(
+            ty,
+            RawEventType::Joined | RawEventType::Spawned | RawEventType::RoleChange
+        )
+    }
+
+    fn handle(
+        &mut self,
+        _meta: &EventMeta,
+        _subject: SubjectId,
+        subject_data: &mut Self::PerSubjectData,
+        event: &GameEvent,
+    ) {
+        match event {
+            GameEvent::Joined(JoinedTeamEvent { team }) => subject_data.team = Some(*team),
+            GameEvent::Spawned(SpawnEvent { class: Some(class) })
+            | GameEvent::RoleChange(RoleChangeEvent { class: Some(class) })
+                if *class != Class::Unknown && !subject_data.classes.contains(class) =>
+            {
+                subject_data.classes.push(*class);
+            }
+            _ => {}
+        }
+    }
+
+    fn finish_global(self, _subjects: &SubjectMap) -> Self::GlobalOutput {}
+
+    fn finish_per_subject(
+        &mut self,
+        subject: &SubjectData,
+        data: Self::PerSubjectData,
+    ) -> Self::PerSubjectOutput {
+        // players that never changed team during the log keep the team they were first seen with
+        let (name, team) = match subject {
+            SubjectData::Player { name, team, .. } | SubjectData::Bot { name, team, .. } => {
+                (name.clone(), data.team.unwrap_or(*team))
+            }
+            _ => (String::new(), data.team.unwrap_or_default()),
+        };
+        PlayerInfo {
+            name,
+            team,
+            classes: data.classes,
+        }
+    }
+}
+
+#[test]
+fn test_player_info() {
+    use crate::{parse_with_handler, SteamId3};
+    use steamid_ng::SteamID;
+
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (_, players) = parse_with_handler::<PlayerInfoHandler>(&log).unwrap();
+    let liko = &players[&SteamId3(SteamID::from_steam3("[U:1:34394789]").unwrap())];
+    assert_eq!("Liko", liko.name);
+    assert_eq!(Team::Blue, liko.team);
+    assert_eq!(Class::Soldier, liko.classes[0]);
+}
@@ -0,0 +1,118 @@
+use crate::common::{Class, ClassMap, SubjectId};
+use crate::event::{GameEvent, RoleChangeEvent, SpawnEvent};
+use crate::module::EventHandler;
+use crate::raw_event::RawEventType;
+use crate::{EventMeta, SubjectData, SubjectMap};
+
+#[derive(Default, Clone)]
+pub struct PlayTimeData {
+    class: Option<Class>,
+    /// Live time when the player spawned as the class
+    since: u32,
+    time: ClassMap<u32>,
+}
+
+impl PlayTimeData {
+    fn stop(&mut self, live_time: u32) {
+        if let Some(class) = self.class.take() {
+            self.time[class] =
+                self.time[class].saturating_add(live_time.saturating_sub(self.since));
+        }
+    }
+}
+
+/// Seconds played on each class
+///
+/// Time is counted from spawning as a class until changing class, leaving the team or disconnecting,
+/// or until the last round of the log ends. Only time during rounds is counted.
+#[derive(Default, Clone)]
+pub struct PlayTimeHandler {
+    /// Seconds of the log that were spent in rounds so far
+    live_time: u32,
+    /// Time of the previous event and whether it happened during a round
+    last: Option<(u32, bool)>,
+}
+
+impl EventHandler for PlayTimeHandler {
+    type GlobalOutput = ();
+    type PerSubjectData = PlayTimeData;
+    type PerSubjectOutput = ClassMap<u32>;
+
+    fn does_handle(ty: RawEventType) -> bool {
+        matches!(
+            ty,
+            RawEventType::Spawned
+                | RawEventType::RoleChange
+                | RawEventType::Joined
+                | RawEventType::Disconnect
+                | RawEventType::Killed
+                | RawEventType::RoundStart
+                | RawEventType::RoundWin
+                | RawEventType::GameOver
+        )
+    }
+
+    fn handle(
+        &mut self,
+        meta: &EventMeta,
+        _subject: SubjectId,
+        subject_data: &mut Self::PerSubjectData,
+        event: &GameEvent,
+    ) {
+        // the time between two events is only counted if both happened during a round
+        let live = meta.state.is_live();
+        if let Some((last, true)) = self.last {
+            if live {
+                self.live_time = self
+                    .live_time
+                    .saturating_add(meta.time.saturating_sub(last));
+            }
+        }
+        self.last = Some((meta.time, live));
+
+        match event {
+            GameEvent::Spawned(SpawnEvent { class: Some(class) })
+            | GameEvent::RoleChange(RoleChangeEvent { class: Some(class) }) => {
+                subject_data.stop(self.live_time);
+                subject_data.class = Some(*class);
+                subject_data.since = self.live_time;
+            }
+            // switching teams or joining spectators ends the current life
+            GameEvent::Joined(_) | GameEvent::Disconnect(_) => subject_data.stop(self.live_time),
+            _ => {}
+        }
+    }
+
+    fn finish_global(self, _subjects: &SubjectMap) -> Self::GlobalOutput {}
+
+    fn finish_per_subject(
+        &mut self,
+        _subject: &SubjectData,
+        mut data: Self::PerSubjectData,
+    ) -> Self::PerSubjectOutput {
+        data.stop(self.live_time);
+        data.time
+    }
+}
+
+#[test]
+fn test_play_time() {
+    use crate::{parse_with_handler, SteamId3};
+    use steamid_ng::SteamID;
+
+    let log = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (_, players) = parse_with_handler::<PlayTimeHandler>(&log).unwrap();
+    let liko = &players[&SteamId3(SteamID::from_steam3("[U:1:34394789]").unwrap())];
+    assert!(liko[Class::Soldier] > 20 * 60);
+    // nobody plays longer than the rounds of the log
+    let rounds: f32 = crate::LineSplit::new(&log)
+        .filter_map(|line| crate::RawEvent::parse(line).ok())
+        .filter(|raw| raw.ty == RawEventType::RoundLength)
+        .filter_map(|raw| match GameEvent::parse(&raw) {
+            Ok(GameEvent::RoundLength(length)) => length.length,
+            _ => None,
+        })
+        .sum();
+    let longest = players.values().map(ClassMap::total).max().unwrap();
+    assert!(longest as f32 <= rounds.ceil());
+}
@@ -0,0 +1,92 @@
+use crate::common::{SteamId3, SubjectId};
+use crate::event::{DamageEvent, GameEvent};
+use crate::module::PlayerSpecificData;
+use crate::raw_event::{RawEventType, RawSubject};
+use crate::{EventMeta, Merge};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+
+/// Kills and damage of a player against a single target
+#[derive(Debug, Default, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+pub struct VersusStats {
+    pub kills: u32,
+    pub damage: u32,
+}
+
+impl Merge for VersusStats {
+    fn merge(&mut self, other: Self) {
+        self.kills.merge(other.kills);
+        self.damage.merge(other.damage);
+    }
+}
+
+/// Kills and damage of a player against each other player, only counted during rounds
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+pub struct Versus(BTreeMap<SteamId3, VersusStats>);
+
+impl Versus {
+    /// Iterate over the stats against each target
+    pub fn iter(&self) -> impl Iterator<Item = (&SteamId3, &VersusStats)> {
+        self.0.iter()
+    }
+
+    pub fn get(&self, target: &SteamId3) -> Option<&VersusStats> {
+        self.0.get(target)
+    }
+
+    fn target_mut(&mut self, target: &RawSubject) -> Option<&mut VersusStats> {
+        let steam_id = SubjectId::try_from(target).ok()?.steam_id()?;
+        Some(self.0.entry(SteamId3(steam_id)).or_default())
+    }
+}
+
+impl Merge for Versus {
+    fn merge(&mut self, other: Self) {
+        Merge::merge(&mut self.0, other.0);
+    }
+}
+
+impl PlayerSpecificData for Versus {
+    type Output = Versus;
+
+    fn does_handle(ty: RawEventType) -> bool {
+        matches!(ty, RawEventType::Killed | RawEventType::Damage)
+    }
+
+    fn handle_event(&mut self, meta: &EventMeta, _subject: SubjectId, event: &GameEvent) {
+        if !meta.state.is_live() {
+            return;
+        }
+        match event {
+            GameEvent::Killed(kill) => {
+                if let Some(stats) = self.target_mut(&kill.target) {
+                    stats.kills = stats.kills.saturating_add(1);
+                }
+            }
+            GameEvent::Damage(DamageEvent {
+                damage: Some(damage),
+                target,
+                ..
+            }) if *damage > 0 && *damage < 1500 => {
+                if let Some(stats) = self.target_mut(target) {
+                    stats.damage = stats.damage.saturating_add(*damage as u32);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    fn finish(self) -> Self::Output {
+        self
+    }
+}
+
+impl IntoIterator for Versus {
+    type Item = (SteamId3, VersusStats);
+    type IntoIter = <BTreeMap<SteamId3, VersusStats> as IntoIterator>::IntoIter;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
@@ -0,0 +1,83 @@
+use crate::RawEventType;
+use chrono::{Duration, NaiveDateTime};
+
+/// Options to configure how a log is parsed
+#[derive(Debug, Clone)]
+pub struct ParseOptions {
+    /// The point in time that the match time of events is counted from
+    pub time_origin: TimeOrigin,
+    /// Malformed lines that are followed by a jump in time larger than this are assumed to be
+    /// truncated while combining logs and are ignored.
+    ///
+    /// Setting this to `None` disables this heuristic.
+    pub combined_log_gap: Option<Duration>,
+    /// Ignore all events before the tournament mode is started.
+    ///
+    /// This has no effect for logs without a "Tournament mode started" event.
+    pub skip_warmup: bool,
+    /// Only pass events that happen during a round to the handlers, ignoring events during warmup,
+    /// humiliation, pauses and after the game is over.
+    pub live_only: bool,
+    /// Include bots in the per-player output.
+    ///
+    /// Since bots have no steam id, they are identified by `[I:0:<user id>]` in the output.
+    pub include_bots: bool,
+    /// Count kills, assists and damage outside of rounds (e.g. during warmup or humiliation) in the class stats
+    pub class_stats_outside_rounds: bool,
+}
+
+impl Default for ParseOptions {
+    fn default() -> Self {
+        ParseOptions {
+            time_origin: TimeOrigin::default(),
+            combined_log_gap: Some(Duration::seconds(60)),
+            skip_warmup: false,
+            live_only: false,
+            include_bots: false,
+            class_stats_outside_rounds: false,
+        }
+    }
+}
+
+impl ParseOptions {
+    /// Resolve the options that depend on looking ahead in the log
+    ///
+    /// `first_event_time` gives the time of the first event of a type in the log
+    pub(crate) fn with_lookahead<F>(&self, first_event_time: F) -> ParseOptions
+    where
+        F: Fn(RawEventType) -> Option<NaiveDateTime>,
+    {
+        let origin_type = match self.time_origin {
+            TimeOrigin::FirstRound => Some(RawEventType::RoundStart),
+            TimeOrigin::TournamentStart => Some(RawEventType::TournamentModeStarted),
+            _ => None,
+        };
+        ParseOptions {
+            time_origin: match origin_type {
+                Some(ty) => first_event_time(ty)
+                    .map(TimeOrigin::Fixed)
+                    .unwrap_or(TimeOrigin::FirstEvent),
+                None => self.time_origin,
+            },
+            skip_warmup: self.skip_warmup
+                && first_event_time(RawEventType::TournamentModeStarted).is_some(),
+            ..self.clone()
+        }
+    }
+}
+
+/// The point in time that the match time of events is counted from
+///
+/// Events that happen before the origin will have a match time of 0.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum TimeOrigin {
+    /// Count from the first event in the log
+    #[default]
+    FirstEvent,
+    /// Count from the first round start, or the first event if no round is started in the log
+    FirstRound,
+    /// Count from the start of tournament mode, or the first event if tournament mode isn't started in the log
+    TournamentStart,
+    /// Count from a fixed point in time
+    Fixed(NaiveDateTime),
+}
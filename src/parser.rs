@@ -0,0 +1,305 @@
+use crate::cache::CachedEvent;
+use crate::module::process_event;
+use crate::{
+    Error, EventHandler, GameEvent, HandlerOutput, MatchState, MatchStateTracker, ParseOptions,
+    RawEvent, RawEventType, Result, SteamId3, SubjectMap, TimeOrigin,
+};
+use chrono::{Duration, NaiveDate, NaiveDateTime};
+use std::convert::TryInto;
+
+/// Parser state that is fed the log one line at a time
+pub(crate) struct LogParser<Handler: EventHandler> {
+    handler: Handler,
+    options: ParseOptions,
+    start_time: Option<NaiveDateTime>,
+    /// Event type that sets the start time, if it hasn't been seen yet
+    pending_origin: Option<RawEventType>,
+    in_warmup: bool,
+    match_state: MatchStateTracker,
+    subjects: SubjectMap<Handler::PerSubjectData>,
+    lexer: LineLexer,
+}
+
+impl<Handler: EventHandler> LogParser<Handler> {
+    pub fn new(options: &ParseOptions) -> Self {
+        let (start_time, pending_origin) = match options.time_origin {
+            TimeOrigin::FirstEvent => (None, None),
+            TimeOrigin::FirstRound => (None, Some(RawEventType::RoundStart)),
+            TimeOrigin::TournamentStart => (None, Some(RawEventType::TournamentModeStarted)),
+            TimeOrigin::Fixed(time) => (Some(time), None),
+        };
+        LogParser {
+            handler: Handler::with_options(options),
+            options: options.clone(),
+            start_time,
+            pending_origin,
+            in_warmup: options.skip_warmup,
+            match_state: MatchStateTracker::default(),
+            subjects: SubjectMap::with_capacity(32),
+            lexer: LineLexer::new(options),
+        }
+    }
+
+    /// Handle the next line of the log
+    ///
+    /// `locate` is used to attach the location of the line to any error.
+    /// The line is also handled if an error for the previous line is returned, so parsing can continue
+    /// after an error.
+    pub fn handle_line<F: Fn(Error, &str) -> Error>(
+        &mut self,
+        line: &str,
+        locate: F,
+    ) -> Result<()> {
+        let raw_event = RawEvent::parse(line);
+        let previous = self.lexer.previous_error(&raw_event);
+        let result = self.handle_raw(raw_event, line, locate);
+        previous.and(result)
+    }
+
+    fn handle_raw<F: Fn(Error, &str) -> Error>(
+        &mut self,
+        raw_event: Result<RawEvent>,
+        line: &str,
+        locate: F,
+    ) -> Result<()> {
+        let Some(raw_event) = self.lexer.lex(raw_event, line, &locate)? else {
+            return Ok(());
+        };
+
+        let Some(state) = self.advance(raw_event.ty, || raw_event.date.try_into().ok()) else {
+            return Ok(());
+        };
+        if !Handler::does_handle(raw_event.ty) {
+            return Ok(());
+        }
+
+        let Some(event) = self.lexer.event(&raw_event, line, &locate) else {
+            return Ok(());
+        };
+
+        // events before the time origin all happen at time 0
+        let mut before_origin = None;
+        let start_time = if self.pending_origin.is_some() {
+            &mut before_origin
+        } else {
+            &mut self.start_time
+        };
+        self.handler
+            .process_in_state(&raw_event, &event, state, start_time, &mut self.subjects)
+            .map_err(|e| locate(e, line))
+    }
+
+    /// Handle the next event from an event cache
+    pub fn handle_cached(&mut self, cached: &CachedEvent) -> Result<()> {
+        let Some(state) = self.advance(cached.ty, || Some(cached.date)) else {
+            return Ok(());
+        };
+        let Some(event) = &cached.event else {
+            return Ok(());
+        };
+        if !Handler::does_handle(cached.ty) {
+            return Ok(());
+        }
+        let subject = match cached.subject_id {
+            Some(id) => id,
+            None => cached.subject.id()?,
+        };
+
+        let mut before_origin = None;
+        let start_time = if self.pending_origin.is_some() {
+            &mut before_origin
+        } else {
+            &mut self.start_time
+        };
+        process_event(
+            &mut self.handler,
+            cached.date,
+            (subject, &cached.subject),
+            event,
+            state,
+            start_time,
+            &mut self.subjects,
+        )
+    }
+
+    /// Update the time origin, warmup and match state for the next event
+    ///
+    /// Returns the state the event happened in, or `None` if the event should be skipped
+    fn advance<F: FnOnce() -> Option<NaiveDateTime>>(
+        &mut self,
+        ty: RawEventType,
+        date: F,
+    ) -> Option<MatchState> {
+        if self.pending_origin == Some(ty) {
+            self.start_time = date();
+            self.pending_origin = None;
+        }
+        if self.in_warmup {
+            if ty != RawEventType::TournamentModeStarted {
+                return None;
+            }
+            self.in_warmup = false;
+        }
+        let state = self.match_state.handle(ty);
+        if self.options.live_only && !state.is_live() {
+            return None;
+        }
+        Some(state)
+    }
+
+    /// Continue with the next log of the same match
+    ///
+    /// The last line of the previous log is allowed to be truncated and the `gap` between the logs is
+    /// removed from the match time, so the match time continues where the previous log left off.
+    pub fn next_log(&mut self, gap: Duration) {
+        self.lexer.pending_error = None;
+        if let Some(start_time) = &mut self.start_time {
+            *start_time += gap;
+        }
+    }
+
+    /// Get the output for the lines handled so far
+    ///
+    /// Errors for the last line are ignored since they are caused by the log being truncated
+    pub fn finish(self) -> HandlerOutput<Handler> {
+        finish_handler(self.handler, &self.options, self.subjects)
+    }
+}
+
+impl<Handler: EventHandler + Clone> LogParser<Handler>
+where
+    Handler::PerSubjectData: Clone,
+{
+    /// Get the output for the lines handled so far, without consuming the parser
+    pub fn snapshot(&self) -> HandlerOutput<Handler> {
+        finish_handler(self.handler.clone(), &self.options, self.subjects.clone())
+    }
+}
+
+/// Lexes the lines of a log, ignoring errors for lines that are truncated at the end of the log
+/// or by combining logs into a single file
+pub(crate) struct LineLexer {
+    combined_log_gap: Option<Duration>,
+    /// Error for the previous line that should only be reported if it isn't the last line of the log
+    /// or caused by log combining
+    pending_error: Option<(Error, Option<NaiveDateTime>)>,
+}
+
+impl LineLexer {
+    pub fn new(options: &ParseOptions) -> Self {
+        LineLexer {
+            combined_log_gap: options.combined_log_gap,
+            pending_error: None,
+        }
+    }
+
+    /// Lex the raw event of the next line, `None` if the line should be skipped
+    ///
+    /// Any error for the previous line that can't be explained by truncation is returned here.
+    pub fn raw_event<'a, F: Fn(Error, &str) -> Error>(
+        &mut self,
+        line: &'a str,
+        locate: F,
+    ) -> Result<Option<RawEvent<'a>>> {
+        let raw_event = RawEvent::parse(line);
+        self.previous_error(&raw_event)?;
+        self.lex(raw_event, line, locate)
+    }
+
+    /// Get the error for the previous line, if it can't be explained by truncation now that the next line is known
+    pub fn previous_error(&mut self, next: &Result<RawEvent>) -> Result<()> {
+        if let Some((error, date)) = self.pending_error.take() {
+            let next_date: Option<NaiveDateTime> = next
+                .as_ref()
+                .ok()
+                .and_then(|next| next.date.try_into().ok());
+            match (date, next_date, self.combined_log_gap) {
+                // truncated lines during log combining, ignore error
+                (Some(date), Some(next_date), Some(max_gap))
+                    if next_date.signed_duration_since(date) > max_gap => {}
+                _ => return Err(error),
+            }
+        }
+        Ok(())
+    }
+
+    /// Handle the result of lexing a line, `None` if the line should be skipped
+    pub fn lex<'a, F: Fn(Error, &str) -> Error>(
+        &mut self,
+        raw_event: Result<RawEvent<'a>>,
+        line: &str,
+        locate: F,
+    ) -> Result<Option<RawEvent<'a>>> {
+        match raw_event {
+            Ok(raw_event) => Ok(Some(raw_event)),
+            Err(Error::Incomplete) => {
+                // only an error if this isn't the last line
+                self.pending_error = Some((Error::Incomplete, None));
+                Ok(None)
+            }
+            Err(Error::Skip) => Ok(None),
+            Err(e) => Err(locate(e, line)),
+        }
+    }
+
+    /// Parse the event of a line, `None` if the event is malformed
+    ///
+    /// The error for a malformed event is only returned with the next line, since it might be truncated.
+    pub fn event<'a, F: Fn(Error, &str) -> Error>(
+        &mut self,
+        raw_event: &RawEvent<'a>,
+        line: &str,
+        locate: F,
+    ) -> Option<GameEvent<'a>> {
+        match GameEvent::parse(raw_event) {
+            Ok(event) => Some(event),
+            Err(e) => {
+                let date: Option<NaiveDateTime> = raw_event.date.try_into().ok();
+
+                // truncated newline during log combining, ignore error
+                if let Some(date) = date {
+                    if contains_line_start(raw_event.params, &date.date()) {
+                        return None;
+                    }
+                }
+
+                // only an error if this isn't the last line
+                self.pending_error = Some((locate(e.into(), line), date));
+                None
+            }
+        }
+    }
+}
+
+fn finish_handler<Handler: EventHandler>(
+    mut handler: Handler,
+    options: &ParseOptions,
+    subjects: SubjectMap<Handler::PerSubjectData>,
+) -> HandlerOutput<Handler> {
+    let just_subjects = subjects.to_just_subjects();
+    let per_player = subjects
+        .into_iter()
+        .filter_map(|(id, subject, data)| {
+            let steam_id = match id.steam_id() {
+                Some(steam_id) => steam_id,
+                None if options.include_bots => id.bot_steam_id()?,
+                None => return None,
+            };
+            Some((steam_id, subject, data))
+        })
+        .map(|(steam_id, subject, data)| {
+            (
+                SteamId3(steam_id),
+                handler.finish_per_subject(&subject, data),
+            )
+        })
+        .collect();
+    let global = handler.finish_global(&just_subjects);
+
+    (global, per_player)
+}
+
+fn contains_line_start(line: &str, date: &NaiveDate) -> bool {
+    let expected_start = format!("L {}", date.format("%m/%d/%Y"));
+    line.contains(&expected_start)
+}
@@ -0,0 +1,533 @@
+//! Glicko-2 skill ratings from match results
+//!
+//! Every match is rated as a game between each player and the average rating of the opposing team,
+//! weighted by how long the player played in the match. Ratings depend on the order of the matches,
+//! [`Ratings::replay`] rates a set of matches in a fixed order so the same archive always gives the same ratings.
+//!
+//! Matches are grouped into rating periods by date, the deviation of a player grows for every period
+//! without any rated matches until it reaches the deviation of an unrated player.
+
+use crate::bytes::log_start;
+use crate::module::{MatchResult, MatchResultHandler, PlayTimeHandler, PlayerInfoHandler};
+use crate::{parse_bytes_with_handler, Class, ClassMap, Error, SteamId3, Team};
+use chrono::NaiveDateTime;
+use enum_iterator::all;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::convert::TryFrom;
+use std::f64::consts::PI;
+
+/// Conversion between the glicko and glicko-2 scale
+const SCALE: f64 = 173.7178;
+/// Convergence tolerance for the volatility
+const EPSILON: f64 = 0.000001;
+
+crate::handler!(RatingHandler {
+    play_time: PlayTimeHandler,
+    info: PlayerInfoHandler,
+    result: MatchResultHandler,
+});
+
+/// Rating of a player on the glicko scale
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq)]
+pub struct Rating {
+    pub rating: f64,
+    pub deviation: f64,
+    pub volatility: f64,
+}
+
+impl Default for Rating {
+    fn default() -> Self {
+        Rating {
+            rating: 1500.0,
+            deviation: 350.0,
+            volatility: 0.06,
+        }
+    }
+}
+
+impl Rating {
+    fn mu(&self) -> f64 {
+        (self.rating - 1500.0) / SCALE
+    }
+
+    fn phi(&self) -> f64 {
+        self.deviation / SCALE
+    }
+
+    /// The rating after a number of rating periods without any games, with the deviation increased up to `max`
+    fn inactive(&self, periods: u32, max: f64) -> Rating {
+        let phi = (self.phi().powi(2) + periods as f64 * self.volatility.powi(2)).sqrt();
+        Rating {
+            deviation: (phi * SCALE).min(max.max(self.deviation)),
+            ..*self
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RatingOptions {
+    /// Rating of players without any rated matches
+    pub initial: Rating,
+    /// Constrains the change in volatility, reasonable values are between 0.3 and 1.2
+    ///
+    /// Must be larger than 0.
+    pub tau: f64,
+    /// Weight of the time played on each class, relative to 1.0
+    ///
+    /// Setting the weight of a class to 0 ignores the time played on that class.
+    pub class_weights: ClassMap<f64>,
+    /// Length of a rating period in days
+    ///
+    /// The deviation of a player grows for every period between two of their rated matches,
+    /// setting this to 0 disables the growth.
+    pub period_days: u32,
+}
+
+impl Default for RatingOptions {
+    fn default() -> Self {
+        let mut class_weights = ClassMap::default();
+        for class in all::<Class>() {
+            class_weights[class] = 1.0;
+        }
+        RatingOptions {
+            initial: Rating::default(),
+            tau: 0.5,
+            class_weights,
+            period_days: 7,
+        }
+    }
+}
+
+/// A player in a rated match
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct MatchPlayer {
+    pub steam_id: SteamId3,
+    pub team: Team,
+    /// Seconds played on each class
+    pub play_time: ClassMap<u32>,
+}
+
+/// The players and result of a single match
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RatedMatch {
+    pub name: String,
+    pub date: Option<NaiveDateTime>,
+    pub result: MatchResult,
+    pub players: Vec<MatchPlayer>,
+}
+
+impl RatedMatch {
+    /// Get the players and result from a log, spectators are ignored
+    pub fn from_log(name: &str, log: &[u8]) -> Result<Self, Error> {
+        let (global, players) = parse_bytes_with_handler::<RatingHandler>(log)?;
+        Ok(RatedMatch {
+            name: name.into(),
+            date: log_start(log),
+            result: global.result,
+            players: players
+                .into_iter()
+                .filter(|(_, player)| player.info.team != Team::Spectator)
+                .map(|(steam_id, player)| MatchPlayer {
+                    steam_id,
+                    team: player.info.team,
+                    play_time: player.play_time,
+                })
+                .collect(),
+        })
+    }
+}
+
+/// The rating of a player after a match
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct RatingChange {
+    /// Name of the match
+    pub name: String,
+    pub date: Option<NaiveDateTime>,
+    /// Playtime weight of the player in the match
+    pub weight: f64,
+    pub rating: Rating,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct PlayerRating {
+    pub rating: Rating,
+    /// Rating after each match, in the order the matches were rated
+    pub history: Vec<RatingChange>,
+}
+
+/// Ratings of all players by steam id
+#[derive(Debug, Default, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(try_from = "UncheckedRatings")]
+pub struct Ratings {
+    options: RatingOptions,
+    players: BTreeMap<SteamId3, PlayerRating>,
+}
+
+/// Deserialized ratings, before the options are validated
+#[derive(Deserialize)]
+struct UncheckedRatings {
+    options: RatingOptions,
+    players: BTreeMap<SteamId3, PlayerRating>,
+}
+
+impl TryFrom<UncheckedRatings> for Ratings {
+    type Error = Error;
+
+    fn try_from(unchecked: UncheckedRatings) -> Result<Self, Error> {
+        let mut ratings = Ratings::new(unchecked.options)?;
+        ratings.players = unchecked.players;
+        Ok(ratings)
+    }
+}
+
+impl Ratings {
+    /// Create empty ratings, returns an error if `tau` isn't larger than 0
+    pub fn new(options: RatingOptions) -> Result<Self, Error> {
+        if options.tau.is_nan() || options.tau <= 0.0 {
+            return Err(Error::InvalidTau(options.tau));
+        }
+        Ok(Ratings {
+            options,
+            players: BTreeMap::new(),
+        })
+    }
+
+    /// Rate a set of matches ordered by date, matches with the same date are ordered by name
+    pub fn replay(
+        options: RatingOptions,
+        matches: impl IntoIterator<Item = RatedMatch>,
+    ) -> Result<Self, Error> {
+        let mut matches: Vec<_> = matches.into_iter().collect();
+        matches.sort_by(|a, b| a.date.cmp(&b.date).then_with(|| a.name.cmp(&b.name)));
+        let mut ratings = Ratings::new(options)?;
+        for rated in &matches {
+            ratings.add_match(rated);
+        }
+        Ok(ratings)
+    }
+
+    pub fn players(&self) -> &BTreeMap<SteamId3, PlayerRating> {
+        &self.players
+    }
+
+    pub fn player(&self, steam_id: &SteamId3) -> Option<&PlayerRating> {
+        self.players.get(steam_id)
+    }
+
+    /// The rating of a player after their last rated match, the initial rating if the player hasn't played any rated matches
+    pub fn rating(&self, steam_id: &SteamId3) -> Rating {
+        self.player(steam_id)
+            .map(|player| player.rating)
+            .unwrap_or(self.options.initial)
+    }
+
+    /// The rating of a player at the start of a match, with the deviation increased for every
+    /// rating period since their last rated match
+    fn rating_at(&self, steam_id: &SteamId3, date: Option<NaiveDateTime>) -> Rating {
+        let Some(player) = self.player(steam_id) else {
+            return self.options.initial;
+        };
+        let last = player.history.last().and_then(|change| change.date);
+        let periods = match (self.period(last), self.period(date)) {
+            (Some(last), Some(current)) => (current - last - 1).clamp(0, u32::MAX as i64) as u32,
+            _ => 0,
+        };
+        player
+            .rating
+            .inactive(periods, self.options.initial.deviation)
+    }
+
+    /// The index of the rating period a date falls in
+    fn period(&self, date: Option<NaiveDateTime>) -> Option<i64> {
+        let length = i64::from(self.options.period_days) * 24 * 60 * 60;
+        if length == 0 {
+            return None;
+        }
+        Some(date?.and_utc().timestamp().div_euclid(length))
+    }
+
+    fn weighted_time(&self, player: &MatchPlayer) -> f64 {
+        all::<Class>()
+            .map(|class| player.play_time[class] as f64 * self.options.class_weights[class])
+            .sum()
+    }
+
+    /// Update the ratings with the result of the next match
+    ///
+    /// All players are rated against the ratings from before the match, matches without players
+    /// on both teams are ignored.
+    pub fn add_match(&mut self, rated: &RatedMatch) {
+        let longest = rated
+            .players
+            .iter()
+            .map(|player| player.play_time.total())
+            .max()
+            .unwrap_or_default();
+        if longest == 0 {
+            return;
+        }
+
+        let players: Vec<(&MatchPlayer, f64, Rating)> = rated
+            .players
+            .iter()
+            .map(|player| {
+                let weight = self.weighted_time(player) / longest as f64;
+                (player, weight, self.rating_at(&player.steam_id, rated.date))
+            })
+            .filter(|(_, weight, _)| *weight > 0.0)
+            .collect();
+        let red = Opponent::team(&players, Team::Red);
+        let blue = Opponent::team(&players, Team::Blue);
+        let (Some(red), Some(blue)) = (red, blue) else {
+            return;
+        };
+
+        let winner = rated.result.winner();
+        for (player, weight, rating) in players {
+            let opponent = match player.team {
+                Team::Red => blue,
+                Team::Blue => red,
+                Team::Spectator => continue,
+            };
+            let score = match winner {
+                Some(winner) if winner == player.team => 1.0,
+                Some(_) => 0.0,
+                None => 0.5,
+            };
+            let rating = update(rating, opponent, score, weight, self.options.tau);
+            let entry = self
+                .players
+                .entry(player.steam_id)
+                .or_insert_with(|| PlayerRating {
+                    rating,
+                    history: Vec::new(),
+                });
+            entry.rating = rating;
+            entry.history.push(RatingChange {
+                name: rated.name.clone(),
+                date: rated.date,
+                weight,
+                rating,
+            });
+        }
+    }
+}
+
+/// The average rating of a team on the glicko-2 scale, weighted by playtime
+#[derive(Clone, Copy)]
+struct Opponent {
+    mu: f64,
+    phi: f64,
+}
+
+impl Opponent {
+    fn team(players: &[(&MatchPlayer, f64, Rating)], team: Team) -> Option<Self> {
+        let (total, mu, phi_squared) = players
+            .iter()
+            .filter(|(player, _, _)| player.team == team)
+            .fold((0.0, 0.0, 0.0), |(total, mu, phi), (_, weight, rating)| {
+                (
+                    total + weight,
+                    mu + weight * rating.mu(),
+                    phi + weight * rating.phi().powi(2),
+                )
+            });
+        (total > 0.0).then(|| Opponent {
+            mu: mu / total,
+            phi: (phi_squared / total).sqrt(),
+        })
+    }
+}
+
+fn g(phi: f64) -> f64 {
+    1.0 / (1.0 + 3.0 * phi.powi(2) / PI.powi(2)).sqrt()
+}
+
+/// Glicko-2 update for a single game, with the game counting for `weight` games
+fn update(rating: Rating, opponent: Opponent, score: f64, weight: f64, tau: f64) -> Rating {
+    let mu = rating.mu();
+    let phi = rating.phi();
+    let sigma = rating.volatility;
+
+    let g = g(opponent.phi);
+    let expected = 1.0 / (1.0 + (-g * (mu - opponent.mu)).exp());
+    let v = 1.0 / (weight * g.powi(2) * expected * (1.0 - expected));
+    let delta = v * weight * g * (score - expected);
+
+    // new volatility, using the Illinois algorithm
+    let a = (sigma.powi(2)).ln();
+    let f = |x: f64| {
+        let ex = x.exp();
+        ex * (delta.powi(2) - phi.powi(2) - v - ex) / (2.0 * (phi.powi(2) + v + ex).powi(2))
+            - (x - a) / tau.powi(2)
+    };
+    let mut big_a = a;
+    let mut big_b = if delta.powi(2) > phi.powi(2) + v {
+        (delta.powi(2) - phi.powi(2) - v).ln()
+    } else {
+        let mut k = 1.0;
+        while f(a - k * tau) < 0.0 {
+            k += 1.0;
+        }
+        a - k * tau
+    };
+    let mut f_a = f(big_a);
+    let mut f_b = f(big_b);
+    while (big_b - big_a).abs() > EPSILON {
+        let big_c = big_a + (big_a - big_b) * f_a / (f_b - f_a);
+        let f_c = f(big_c);
+        if f_c * f_b <= 0.0 {
+            big_a = big_b;
+            f_a = f_b;
+        } else {
+            f_a /= 2.0;
+        }
+        big_b = big_c;
+        f_b = f_c;
+    }
+    let volatility = (big_a / 2.0).exp();
+
+    let phi_star = (phi.powi(2) + volatility.powi(2)).sqrt();
+    let new_phi = 1.0 / (1.0 / phi_star.powi(2) + 1.0 / v).sqrt();
+    let new_mu = mu + new_phi.powi(2) * weight * g * (score - expected);
+
+    Rating {
+        rating: new_mu * SCALE + 1500.0,
+        deviation: new_phi * SCALE,
+        volatility,
+    }
+}
+
+#[test]
+fn test_rating_update() {
+    let player = Rating {
+        rating: 1500.0,
+        deviation: 200.0,
+        volatility: 0.06,
+    };
+    let opponent = Rating {
+        rating: 1400.0,
+        deviation: 30.0,
+        volatility: 0.06,
+    };
+    let opponent = Opponent {
+        mu: opponent.mu(),
+        phi: opponent.phi(),
+    };
+    let won = update(player, opponent, 1.0, 1.0, 0.5);
+    let inactive = player.inactive(1, 350.0);
+    assert!(inactive.deviation > player.deviation && inactive.deviation < 350.0);
+    assert_eq!(player, player.inactive(0, 350.0));
+    assert_eq!(350.0, Rating::default().inactive(1, 350.0).deviation);
+    let lost = update(player, opponent, 0.0, 1.0, 0.5);
+    assert!(won.rating > player.rating);
+    assert!(lost.rating < player.rating);
+    assert!(won.deviation < player.deviation);
+    // playing only part of the match changes the rating less
+    let partial = update(player, opponent, 1.0, 0.5, 0.5);
+    assert!(partial.rating > player.rating && partial.rating < won.rating);
+}
+
+#[test]
+fn test_ratings_replay() {
+    let names = ["log_6s.log", "log_2788889.log", "log_2892242.log"];
+    let matches: Vec<_> = names
+        .iter()
+        .map(|name| {
+            let log = crate::read_log_bytes(format!("tests/data/{}.gz", name)).unwrap();
+            RatedMatch::from_log(name, &log).unwrap()
+        })
+        .collect();
+
+    let ratings = Ratings::replay(RatingOptions::default(), matches.clone()).unwrap();
+    let reversed =
+        Ratings::replay(RatingOptions::default(), matches.iter().rev().cloned()).unwrap();
+    assert_eq!(ratings, reversed);
+
+    let last = matches.iter().max_by_key(|rated| rated.date).unwrap();
+    let winner = last.result.winner().unwrap();
+    for player in &last.players {
+        let history = &ratings.player(&player.steam_id).unwrap().history;
+        let change = history.last().unwrap();
+        assert_eq!(last.name, change.name);
+        let before = match history.len() {
+            1 => RatingOptions::default().initial.rating,
+            len => history[len - 2].rating.rating,
+        };
+        assert_eq!(player.team == winner, change.rating.rating > before);
+    }
+
+    // ignoring all classes leaves nobody to rate
+    let options = RatingOptions {
+        class_weights: ClassMap::default(),
+        ..RatingOptions::default()
+    };
+    assert!(Ratings::replay(options, matches.clone())
+        .unwrap()
+        .players()
+        .is_empty());
+
+    for tau in [0.0, -0.5, f64::NAN] {
+        let options = RatingOptions {
+            tau,
+            ..RatingOptions::default()
+        };
+        assert!(matches!(
+            Ratings::replay(options, matches.clone()),
+            Err(Error::InvalidTau(_))
+        ));
+    }
+
+    let json = serde_json::to_string(&ratings).unwrap();
+    let deserialized: Ratings = serde_json::from_str(&json).unwrap();
+    assert_eq!(ratings.options, deserialized.options);
+    assert_eq!(ratings.players().len(), deserialized.players().len());
+    let invalid = json.replace("\"tau\":0.5", "\"tau\":0.0");
+    assert!(serde_json::from_str::<Ratings>(&invalid).is_err());
+}
+
+#[test]
+fn test_rating_periods() {
+    use chrono::NaiveDate;
+
+    let rated = |name: &str, day: u32, red: u32, blue: u32| {
+        let mut play_time = ClassMap::default();
+        play_time[Class::Scout] = 1800;
+        let player = |account: u32, team: Team| MatchPlayer {
+            steam_id: SteamId3(
+                steamid_ng::SteamID::from_steam3(&format!("[U:1:{}]", account)).unwrap(),
+            ),
+            team,
+            play_time: play_time.clone(),
+        };
+        RatedMatch {
+            name: name.into(),
+            date: NaiveDate::from_ymd_opt(2023, 1, 1)
+                .and_then(|date| date.checked_add_days(chrono::Days::new(day.into())))
+                .and_then(|date| date.and_hms_opt(20, 0, 0)),
+            result: MatchResult { red: 1, blue: 0 },
+            players: vec![player(red, Team::Red), player(blue, Team::Blue)],
+        }
+    };
+    let player = rated("", 0, 1, 2).players[0].steam_id;
+    let deviation = |matches: Vec<RatedMatch>| {
+        Ratings::replay(RatingOptions::default(), matches)
+            .unwrap()
+            .rating(&player)
+            .deviation
+    };
+
+    // matches without the player don't change their rating
+    let unrelated: Vec<_> = (1..50).map(|day| rated("other", day, 3, 4)).collect();
+    let together = [rated("a", 0, 1, 2), rated("b", 50, 1, 2)];
+    assert_eq!(
+        deviation(together.to_vec()),
+        deviation(together.iter().cloned().chain(unrelated).collect())
+    );
+
+    // the deviation grows with the time between matches
+    let soon = deviation(vec![rated("a", 0, 1, 2), rated("b", 1, 1, 2)]);
+    let later = deviation(vec![rated("a", 0, 1, 2), rated("b", 50, 1, 2)]);
+    assert!(later > soon);
+}
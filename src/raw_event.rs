@@ -3,6 +3,7 @@ use crate::parsing::{skip, skip_matches, split_once, split_subject_end};
 use crate::{Error, Result, ResultExt};
 use crate::{SubjectError, SubjectId};
 use chrono::{NaiveDate, NaiveDateTime};
+use enum_iterator::Sequence;
 use logos::{Lexer, Logos};
 use std::convert::{TryFrom, TryInto};
 use std::num::ParseIntError;
@@ -71,7 +72,7 @@ fn test_parse_date() {
     );
 }
 
-#[derive(Debug, PartialEq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum RawSubject<'a> {
     Player(&'a str),
     Team(Team),
@@ -144,7 +145,7 @@ pub fn subject_parser(input: &str) -> Result<(&str, RawSubject)> {
     let full = input;
     if let Some(input) = input.strip_prefix('"') {
         let Ok((player, input)) = split_subject_end(input, 1) else {
-            return Ok((full, RawSubject::Console))
+            return Ok((full, RawSubject::Console));
         };
         let input = skip(input, 1)?;
         if player.ends_with("le>") {
@@ -153,12 +154,12 @@ pub fn subject_parser(input: &str) -> Result<(&str, RawSubject)> {
             Ok((input, RawSubject::Player(player)))
         }
     } else if input.starts_with("Te") {
-        // Team "red" or Team "blue"
+        // Team "Red" or Team "Blue"
         let first_byte = input.as_bytes().get(6).copied();
-        if first_byte == Some(b'r') {
+        if matches!(first_byte, Some(b'r' | b'R')) {
             let rest = input.get(11..).ok_or(Error::Malformed)?;
             Ok((rest, RawSubject::Team(Team::Red)))
-        } else if first_byte == Some(b'b') {
+        } else if matches!(first_byte, Some(b'b' | b'B')) {
             let rest = input.get(12..).ok_or(Error::Malformed)?;
             Ok((rest, RawSubject::Team(Team::Blue)))
         } else {
@@ -169,7 +170,7 @@ pub fn subject_parser(input: &str) -> Result<(&str, RawSubject)> {
         }
     } else {
         let Ok((system, input)) = split_once(input, b' ', 1) else {
-            return Ok(("", RawSubject::System(input)))
+            return Ok(("", RawSubject::System(input)));
         };
         Ok((input, RawSubject::System(system)))
     }
@@ -184,9 +185,13 @@ fn test_subject_parser() {
         ),
         subject_parser(r#""Buddie :")<25><[U:1:123]><>" connected"#).unwrap()
     );
+    assert_eq!(
+        (r#"triggered "pointcaptured""#, RawSubject::Team(Team::Red)),
+        subject_parser(r#"Team "Red" triggered "pointcaptured""#).unwrap()
+    );
 }
 
-#[derive(Copy, Clone, Debug, PartialEq, Logos)]
+#[derive(Copy, Clone, Debug, PartialEq, Eq, Hash, Logos, Sequence)]
 pub enum RawEventType {
     #[token(r#"joined "#)]
     Joined,
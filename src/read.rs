@@ -0,0 +1,213 @@
+use crate::Error;
+use sha2::{Digest, Sha256};
+use std::ffi::OsStr;
+use std::fs;
+use std::io;
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "zip", test))]
+use std::io::Read;
+use std::path::Path;
+
+const GZIP_MAGIC: &[u8] = &[0x1f, 0x8b];
+const ZIP_MAGIC: &[u8] = b"PK\x03\x04";
+const BZIP2_MAGIC: &[u8] = b"BZh";
+
+/// Maximum size of a decompressed log, larger logs are rejected to protect against decompression bombs
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "zip", test))]
+pub const MAX_DECOMPRESSED_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Read a log file, decompressing it if needed
+///
+/// Gzip, bzip2 and zip files containing a single `.log` file are detected by their content,
+/// any invalid utf8 in the log is replaced.
+///
+/// Each compression format requires the feature with the same name, only `gzip` is enabled by default.
+pub fn read_log(path: impl AsRef<Path>) -> Result<String, Error> {
+    decode_log(fs::read(path)?)
+}
+
+/// Read a log file, decompressing it if needed, without decoding it as utf8
+///
+/// The result can be parsed with [`parse_bytes`](crate::parse_bytes), see [`read_log`] for the supported formats.
+pub fn read_log_bytes(path: impl AsRef<Path>) -> Result<Vec<u8>, Error> {
+    decompress_log(fs::read(path)?)
+}
+
+/// Decompress the content of a log file if needed, without decoding it as utf8
+///
+/// See [`read_log`] for the supported formats.
+pub fn decompress_log(data: Vec<u8>) -> Result<Vec<u8>, Error> {
+    Ok(decompress(data)?)
+}
+
+/// Decode the content of a log file, decompressing it if needed
+///
+/// See [`read_log`] for the supported formats.
+pub fn decode_log(data: Vec<u8>) -> Result<String, Error> {
+    let data = decompress(data)?;
+    Ok(match String::from_utf8(data) {
+        Ok(text) => text,
+        Err(e) => String::from_utf8_lossy(e.as_bytes()).into_owned(),
+    })
+}
+
+/// Hash of the decompressed content of a log, used to recognize logs that were added before under another name
+pub(crate) fn content_hash(log: &[u8]) -> String {
+    format!("{:x}", Sha256::digest(log))
+}
+
+/// Whether the file name is a `.log` file, optionally compressed
+pub(crate) fn is_log(path: &Path) -> bool {
+    match path.extension().and_then(OsStr::to_str) {
+        Some("log") => true,
+        Some("gz" | "bz2" | "zip") => {
+            path.file_stem().map(Path::new).and_then(Path::extension) == Some(OsStr::new("log"))
+        }
+        _ => false,
+    }
+}
+
+fn decompress(data: Vec<u8>) -> io::Result<Vec<u8>> {
+    if data.starts_with(GZIP_MAGIC) {
+        decompress_gzip(&data)
+    } else if data.starts_with(BZIP2_MAGIC) {
+        decompress_bzip2(&data)
+    } else if data.starts_with(ZIP_MAGIC) {
+        decompress_zip(data)
+    } else {
+        Ok(data)
+    }
+}
+
+/// Read a decompressing reader to the end, failing if the output is larger than `limit`
+#[cfg(any(feature = "gzip", feature = "bzip2", feature = "zip", test))]
+fn read_limited(reader: impl Read, limit: u64) -> io::Result<Vec<u8>> {
+    let mut decompressed = Vec::new();
+    reader.take(limit + 1).read_to_end(&mut decompressed)?;
+    if decompressed.len() as u64 > limit {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("decompressed log is larger than {} bytes", limit),
+        ));
+    }
+    Ok(decompressed)
+}
+
+#[cfg(not(all(any(feature = "gzip", test), feature = "bzip2", feature = "zip")))]
+fn unsupported(format: &str) -> io::Error {
+    io::Error::new(
+        io::ErrorKind::Unsupported,
+        format!(
+            "reading {} compressed logs requires the {} feature",
+            format, format
+        ),
+    )
+}
+
+// the test logs are gzip compressed, so gzip is always available for the tests
+#[cfg(any(feature = "gzip", test))]
+fn decompress_gzip(data: &[u8]) -> io::Result<Vec<u8>> {
+    read_limited(flate2::read::GzDecoder::new(data), MAX_DECOMPRESSED_SIZE)
+}
+
+#[cfg(not(any(feature = "gzip", test)))]
+fn decompress_gzip(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported("gzip"))
+}
+
+#[cfg(feature = "bzip2")]
+fn decompress_bzip2(data: &[u8]) -> io::Result<Vec<u8>> {
+    read_limited(bzip2::read::BzDecoder::new(data), MAX_DECOMPRESSED_SIZE)
+}
+
+#[cfg(not(feature = "bzip2"))]
+fn decompress_bzip2(_data: &[u8]) -> io::Result<Vec<u8>> {
+    Err(unsupported("bzip2"))
+}
+
+#[cfg(feature = "zip")]
+fn decompress_zip(data: Vec<u8>) -> io::Result<Vec<u8>> {
+    let mut archive = zip::ZipArchive::new(io::Cursor::new(data))?;
+    let logs: Vec<_> = archive
+        .file_names()
+        .filter(|name| name.ends_with(".log"))
+        .map(String::from)
+        .collect();
+    let [name] = logs.as_slice() else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("expected a single .log file in zip, found {}", logs.len()),
+        ));
+    };
+    let decompressed = read_limited(archive.by_name(name)?, MAX_DECOMPRESSED_SIZE)?;
+    Ok(decompressed)
+}
+
+#[cfg(not(feature = "zip"))]
+fn decompress_zip(_data: Vec<u8>) -> io::Result<Vec<u8>> {
+    Err(unsupported("zip"))
+}
+
+#[test]
+fn test_read_log() {
+    let log = read_log("tests/data/log_2892242.log.gz").unwrap();
+    assert!(log.starts_with("L 04/11/2021 - 23:28:37: Log file started"));
+    assert_eq!(log, decode_log(log.clone().into_bytes()).unwrap());
+
+    let invalid = b"L 04/11/2021 - 23:28:37: \"\xff<2><[U:1:1]><Red>\" say \"hi\"".to_vec();
+    assert_eq!(
+        "L 04/11/2021 - 23:28:37: \"\u{fffd}<2><[U:1:1]><Red>\" say \"hi\"",
+        decode_log(invalid).unwrap()
+    );
+}
+
+#[test]
+fn test_decompression_limit() {
+    use flate2::read::GzDecoder;
+    use flate2::write::GzEncoder;
+    use std::io::Write;
+
+    let mut gzip = GzEncoder::new(Vec::new(), flate2::Compression::fast());
+    gzip.write_all(&[0; 1024]).unwrap();
+    let gzip = gzip.finish().unwrap();
+    assert_eq!(
+        1024,
+        read_limited(GzDecoder::new(gzip.as_slice()), 1024)
+            .unwrap()
+            .len()
+    );
+    assert_eq!(
+        io::ErrorKind::InvalidData,
+        read_limited(GzDecoder::new(gzip.as_slice()), 1023)
+            .unwrap_err()
+            .kind()
+    );
+}
+
+#[cfg(all(feature = "bzip2", feature = "zip"))]
+#[test]
+fn test_read_compressed() {
+    use bzip2::write::BzEncoder;
+    use std::io::{Cursor, Write};
+    use zip::write::{FileOptions, ZipWriter};
+
+    let log = read_log("tests/data/log_2892242.log.gz").unwrap();
+
+    let mut bzip2 = BzEncoder::new(Vec::new(), bzip2::Compression::fast());
+    bzip2.write_all(log.as_bytes()).unwrap();
+    assert_eq!(log, decode_log(bzip2.finish().unwrap()).unwrap());
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    zip.start_file("log_2892242.log", FileOptions::default())
+        .unwrap();
+    zip.write_all(log.as_bytes()).unwrap();
+    let zip = zip.finish().unwrap().into_inner();
+    assert_eq!(log, decode_log(zip).unwrap());
+
+    let mut zip = ZipWriter::new(Cursor::new(Vec::new()));
+    for name in ["a.log", "b.log"] {
+        zip.start_file(name, FileOptions::default()).unwrap();
+        zip.write_all(log.as_bytes()).unwrap();
+    }
+    let zip = zip.finish().unwrap().into_inner();
+    assert!(matches!(decode_log(zip), Err(Error::Io(_))));
+}
@@ -0,0 +1,251 @@
+use crate::event::{SayEvent, TournamentModeStartedEvent};
+use crate::raw_event::RawSubject;
+use crate::{GameEvent, LineSplit, RawEvent, RawEventType};
+use chrono::NaiveDateTime;
+use serde::Serialize;
+use std::convert::TryInto;
+use std::ops::Range;
+
+/// A single match inside a log file
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MatchSegment {
+    /// Byte range of the match in the log
+    pub range: Range<usize>,
+    pub map: Option<String>,
+    /// Name of the blue team, if tournament mode was started
+    pub blue: Option<String>,
+    /// Name of the red team, if tournament mode was started
+    pub red: Option<String>,
+    pub start: NaiveDateTime,
+    pub end: NaiveDateTime,
+    /// Whether the match was played until the game was over
+    pub completed: bool,
+}
+
+impl MatchSegment {
+    /// Get the part of the log containing this match
+    pub fn log<'a>(&self, log: &'a str) -> &'a str {
+        &log[self.range.clone()]
+    }
+}
+
+/// Split a log containing multiple matches into the separate matches
+///
+/// A new match is started by a new log file, by tournament mode being started after the previous match
+/// has gone live, or by a new round after the game is over. Any warmup before tournament mode is started
+/// is considered part of the match, use [`ParseOptions::skip_warmup`](crate::ParseOptions) to ignore it.
+///
+/// The segments together cover the full log, each segment can be parsed independently.
+pub fn segment_matches(log: &str) -> Vec<MatchSegment> {
+    let mut segments: Vec<MatchSegment> = Vec::new();
+    let mut current: Option<SegmentState> = None;
+
+    for line in LineSplit::new(log) {
+        let Ok(raw) = RawEvent::parse(line) else {
+            continue;
+        };
+        let Ok(date) = raw.date.try_into() else {
+            continue;
+        };
+
+        if matches!(&current, Some(state) if state.ends_before(&raw)) {
+            // the "L " before the line belongs to the new segment
+            let start = line.as_ptr() as usize - log.as_ptr() as usize - 2;
+            if let Some(last) = segments.last_mut() {
+                last.range.end = start;
+            }
+            current = None;
+        }
+
+        let state = current.get_or_insert_with(|| {
+            let start = match segments.last() {
+                Some(last) => last.range.end,
+                None => 0,
+            };
+            segments.push(MatchSegment {
+                range: start..log.len(),
+                map: None,
+                blue: None,
+                red: None,
+                start: date,
+                end: date,
+                completed: false,
+            });
+            SegmentState::default()
+        });
+        let segment = segments.last_mut().expect("segment was just pushed");
+        segment.end = date;
+        state.handle(segment, line, &raw);
+    }
+
+    segments
+}
+
+#[derive(Default)]
+struct SegmentState {
+    tournament: bool,
+    live: bool,
+    game_over: bool,
+    closed: bool,
+}
+
+impl SegmentState {
+    /// Whether the event is the start of the next match
+    fn ends_before(&self, raw: &RawEvent) -> bool {
+        match raw.ty {
+            _ if self.closed => true,
+            RawEventType::LogFileStarted => true,
+            RawEventType::TournamentModeStarted => self.tournament && (self.live || self.game_over),
+            RawEventType::RoundStart => self.tournament && self.game_over,
+            _ => false,
+        }
+    }
+
+    fn handle(&mut self, segment: &mut MatchSegment, line: &str, raw: &RawEvent) {
+        match raw.ty {
+            RawEventType::TournamentModeStarted => {
+                // a game over during the warmup doesn't count
+                self.tournament = true;
+                self.live = false;
+                self.game_over = false;
+                if let Ok(GameEvent::TournamentModeStarted(TournamentModeStartedEvent {
+                    blue,
+                    red,
+                })) = GameEvent::parse(raw)
+                {
+                    segment.blue = Some(blue.to_string());
+                    segment.red = Some(red.to_string());
+                }
+            }
+            RawEventType::RoundStart => self.live = true,
+            RawEventType::GameOver => self.game_over = true,
+            RawEventType::LogFileClosed => self.closed = true,
+            RawEventType::Say => {
+                if let Some(map) = announced_map(raw) {
+                    segment.map.get_or_insert(map);
+                }
+            }
+            _ => {
+                if let Some(map) = map_from_line(line) {
+                    segment.map = Some(map.to_string());
+                }
+            }
+        }
+        segment.completed = self.game_over;
+    }
+}
+
+/// Get the map from a lobby bot announcing it in chat
+pub(crate) fn announced_map(raw: &RawEvent) -> Option<String> {
+    if raw.subject != RawSubject::Console {
+        return None;
+    }
+    match GameEvent::parse(raw) {
+        Ok(GameEvent::Say(SayEvent { text })) => text.strip_prefix("Map: ").map(String::from),
+        _ => None,
+    }
+}
+
+/// Get the map from a `Loading map "..."` or `Started map "..."` line
+pub(crate) fn map_from_line(line: &str) -> Option<&str> {
+    let message = line.get(23..)?;
+    let map = message
+        .strip_prefix("Loading map \"")
+        .or_else(|| message.strip_prefix("Started map \""))?;
+    map.split_once('"').map(|(map, _)| map)
+}
+
+#[test]
+fn test_segment_matches() {
+    let log = concat!(
+        "L 08/06/2018 - 20:55:00: Log file started (file \"logs/L0806001.log\") (game \"/tf\") (version \"1\")\n",
+        "L 08/06/2018 - 20:55:00: Loading map \"cp_process_final\"\n",
+        "L 08/06/2018 - 20:56:00: World triggered \"Round_Start\"\n",
+        "L 08/06/2018 - 21:00:00: Tournament mode started\n",
+        "Blue Team: BLU\n",
+        "Red Team: RED\n",
+        "L 08/06/2018 - 21:00:10: World triggered \"Round_Start\"\n",
+        "L 08/06/2018 - 21:10:10: World triggered \"Game_Over\" reason \"Reached Win Limit\"\n",
+        "L 08/06/2018 - 21:10:10: Team \"Red\" final score \"5\" with \"6\" players\n",
+        "L 08/06/2018 - 21:11:00: Tournament mode started\n",
+        "Blue Team: foo\n",
+        "Red Team: bar\n",
+        "L 08/06/2018 - 21:11:10: World triggered \"Round_Start\"\n",
+        "L 08/06/2018 - 21:15:10: Tournament mode started\n",
+        "Blue Team: foo\n",
+        "Red Team: bar\n",
+        "L 08/06/2018 - 21:15:20: World triggered \"Round_Start\"\n",
+        "L 08/06/2018 - 21:25:10: World triggered \"Game_Over\" reason \"Reached Win Limit\"\n",
+        "L 08/06/2018 - 21:25:10: Log file closed.\n",
+    );
+
+    let segments = segment_matches(log);
+    let summary: Vec<_> = segments
+        .iter()
+        .map(|segment| {
+            (
+                segment.map.as_deref(),
+                segment.blue.as_deref(),
+                segment.start.format("%H:%M:%S").to_string(),
+                segment.end.format("%H:%M:%S").to_string(),
+                segment.completed,
+            )
+        })
+        .collect();
+    assert_eq!(
+        vec![
+            (
+                Some("cp_process_final"),
+                Some("BLU"),
+                "20:55:00".to_string(),
+                "21:10:10".to_string(),
+                true
+            ),
+            (
+                None,
+                Some("foo"),
+                "21:11:00".to_string(),
+                "21:11:10".to_string(),
+                false
+            ),
+            (
+                None,
+                Some("foo"),
+                "21:15:10".to_string(),
+                "21:25:10".to_string(),
+                true
+            ),
+        ],
+        summary
+    );
+
+    assert_eq!(0, segments[0].range.start);
+    assert_eq!(log.len(), segments[2].range.end);
+    for pair in segments.windows(2) {
+        assert_eq!(pair[0].range.end, pair[1].range.start);
+    }
+    for segment in &segments {
+        crate::parse(segment.log(log)).unwrap();
+    }
+    assert!(segments[1]
+        .log(log)
+        .starts_with("L 08/06/2018 - 21:11:00: Tournament mode started\n"));
+}
+
+#[test]
+fn test_segment_test_logs() {
+    for name in ["log_2892242", "log_bball", "log_6s"] {
+        let input = crate::read_log(format!("tests/data/{}.log.gz", name)).expect("failed to read");
+
+        let segments = segment_matches(&input);
+        assert_eq!(1, segments.len(), "{}", name);
+        assert_eq!(0..input.len(), segments[0].range);
+        assert!(segments[0].completed);
+    }
+
+    let input = crate::read_log("tests/data/log_2892242.log.gz").expect("failed to read");
+    let segment = &segment_matches(&input)[0];
+    assert_eq!(Some("cp_gullywash_final1"), segment.map.as_deref());
+    assert_eq!(Some("alshuy"), segment.blue.as_deref());
+    assert_eq!(Some("RED"), segment.red.as_deref());
+}
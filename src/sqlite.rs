@@ -0,0 +1,417 @@
+//! Import parsed logs into a sqlite database
+//!
+//! Logs are identified by a hash of their content, importing a log that is already in the database does nothing.
+
+use crate::module::ChatType;
+use crate::parser::LogParser;
+use crate::read::content_hash;
+use crate::segment::{announced_map, map_from_line};
+use crate::{raw_events_bytes, Class, Error, GameEvent, ParseOptions, RawLine};
+use chrono::NaiveDateTime;
+use enum_iterator::all;
+use import::ImportHandler;
+use rusqlite::{params, Connection, OptionalExtension, Transaction};
+use std::convert::TryFrom;
+use std::path::Path;
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS logs (
+    id INTEGER PRIMARY KEY,
+    hash TEXT NOT NULL UNIQUE,
+    name TEXT NOT NULL,
+    map TEXT,
+    date TEXT,
+    duration INTEGER NOT NULL
+);
+CREATE TABLE IF NOT EXISTS players (
+    steam_id TEXT PRIMARY KEY,
+    name TEXT NOT NULL
+);
+CREATE TABLE IF NOT EXISTS player_stats (
+    log_id INTEGER NOT NULL REFERENCES logs(id) ON DELETE CASCADE,
+    steam_id TEXT NOT NULL REFERENCES players(steam_id),
+    name TEXT NOT NULL,
+    team TEXT NOT NULL,
+    kills INTEGER NOT NULL,
+    assists INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    damage INTEGER NOT NULL,
+    healing INTEGER NOT NULL,
+    charges INTEGER NOT NULL,
+    drops INTEGER NOT NULL,
+    PRIMARY KEY (log_id, steam_id)
+);
+CREATE TABLE IF NOT EXISTS class_stats (
+    log_id INTEGER NOT NULL REFERENCES logs(id) ON DELETE CASCADE,
+    steam_id TEXT NOT NULL REFERENCES players(steam_id),
+    class TEXT NOT NULL,
+    kills INTEGER NOT NULL,
+    assists INTEGER NOT NULL,
+    deaths INTEGER NOT NULL,
+    damage INTEGER NOT NULL,
+    PRIMARY KEY (log_id, steam_id, class)
+);
+CREATE TABLE IF NOT EXISTS heal_spread (
+    log_id INTEGER NOT NULL REFERENCES logs(id) ON DELETE CASCADE,
+    healer TEXT NOT NULL REFERENCES players(steam_id),
+    target TEXT NOT NULL,
+    healing INTEGER NOT NULL,
+    PRIMARY KEY (log_id, healer, target)
+);
+CREATE TABLE IF NOT EXISTS chat (
+    log_id INTEGER NOT NULL REFERENCES logs(id) ON DELETE CASCADE,
+    idx INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    steam_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    message TEXT NOT NULL,
+    team_only INTEGER NOT NULL,
+    PRIMARY KEY (log_id, idx)
+);
+CREATE TABLE IF NOT EXISTS rounds (
+    log_id INTEGER NOT NULL REFERENCES logs(id) ON DELETE CASCADE,
+    round INTEGER NOT NULL,
+    start INTEGER NOT NULL,
+    length REAL,
+    winner TEXT,
+    PRIMARY KEY (log_id, round)
+);
+CREATE TABLE IF NOT EXISTS events (
+    log_id INTEGER NOT NULL REFERENCES logs(id) ON DELETE CASCADE,
+    idx INTEGER NOT NULL,
+    time INTEGER NOT NULL,
+    type TEXT NOT NULL,
+    steam_id TEXT,
+    data TEXT NOT NULL,
+    PRIMARY KEY (log_id, idx)
+);
+CREATE INDEX IF NOT EXISTS player_stats_steam_id ON player_stats(steam_id);
+CREATE INDEX IF NOT EXISTS class_stats_steam_id ON class_stats(steam_id);
+CREATE INDEX IF NOT EXISTS events_type ON events(log_id, type);
+";
+
+mod import {
+    use crate::module::PlayerInfoHandler;
+    use crate::LogHandler;
+
+    crate::handler!(ImportHandler {
+        log: LogHandler,
+        info: PlayerInfoHandler,
+    });
+}
+
+/// Result of importing a log
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Import {
+    /// The log was imported with the given id
+    Imported(i64),
+    /// A log with the same content was already imported with the given id
+    Existing(i64),
+}
+
+impl Import {
+    /// The id of the log in the database
+    pub fn id(&self) -> i64 {
+        match self {
+            Import::Imported(id) | Import::Existing(id) => *id,
+        }
+    }
+}
+
+/// Database of imported logs
+pub struct Database {
+    connection: Connection,
+}
+
+impl Database {
+    /// Open a database file, creating the tables if needed
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, Error> {
+        Self::from_connection(Connection::open(path)?)
+    }
+
+    pub fn open_in_memory() -> Result<Self, Error> {
+        Self::from_connection(Connection::open_in_memory()?)
+    }
+
+    /// Use an existing connection, creating the tables if needed
+    pub fn from_connection(connection: Connection) -> Result<Self, Error> {
+        connection.execute_batch("PRAGMA foreign_keys = ON;")?;
+        connection.execute_batch(SCHEMA)?;
+        Ok(Database { connection })
+    }
+
+    pub fn connection(&self) -> &Connection {
+        &self.connection
+    }
+
+    /// Import a log, `name` can be the log id or the filename
+    ///
+    /// The log is only imported if no log with the same content was imported before.
+    pub fn import(&mut self, name: &str, log: &[u8]) -> Result<Import, Error> {
+        let hash = content_hash(log);
+        let existing = self
+            .connection
+            .query_row("SELECT id FROM logs WHERE hash = ?1", [&hash], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        if let Some(id) = existing {
+            return Ok(Import::Existing(id));
+        }
+
+        let transaction = self.connection.transaction()?;
+        transaction.execute(
+            "INSERT INTO logs (hash, name, duration) VALUES (?1, ?2, 0)",
+            params![hash, name],
+        )?;
+        let log_id = transaction.last_insert_rowid();
+
+        // the stats, events and log info are all collected in a single pass over the log
+        let mut parser = LogParser::<ImportHandler>::new(&ParseOptions::default());
+        let mut events = EventImporter::new(log_id);
+        for line in raw_events_bytes(log) {
+            parser.handle_line(line.as_str(), |e, _| line.locate(log, e))?;
+            events.handle(&transaction, &line)?;
+        }
+        let (global, players) = parser.finish();
+
+        let date = events
+            .start
+            .map(|start| start.format("%Y-%m-%d %H:%M:%S").to_string());
+        let duration = match (events.start, events.end) {
+            (Some(start), Some(end)) => (end - start).num_seconds(),
+            _ => 0,
+        };
+        transaction.execute(
+            "UPDATE logs SET map = ?2, date = ?3, duration = ?4 WHERE id = ?1",
+            params![log_id, events.map, date, duration],
+        )?;
+
+        for (steam_id, player) in &players {
+            let steam_id = steam_id.0.steam3();
+            let info = &player.info;
+            let stats = &player.log;
+            transaction
+                .prepare_cached(
+                    "INSERT INTO players (steam_id, name) VALUES (?1, ?2)
+                    ON CONFLICT(steam_id) DO UPDATE SET name = excluded.name",
+                )?
+                .execute(params![steam_id, info.name])?;
+
+            let class_stats = &stats.class_stats;
+            transaction
+                .prepare_cached(
+                    "INSERT INTO player_stats
+                    (log_id, steam_id, name, team, kills, assists, deaths, damage, healing, charges, drops)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11)",
+                )?
+                .execute(params![
+                    log_id,
+                    steam_id,
+                    info.name,
+                    info.team.as_str(),
+                    class_stats.kills.total(),
+                    class_stats.assists.total(),
+                    class_stats.deaths.total(),
+                    class_stats.damage.total(),
+                    stats.heal_spread.total(),
+                    stats.medic_stats.charge_count,
+                    stats.medic_stats.drops,
+                ])?;
+
+            for class in all::<Class>() {
+                let row = (
+                    class_stats.kills[class],
+                    class_stats.assists[class],
+                    class_stats.deaths[class],
+                    class_stats.damage[class],
+                );
+                if row == (0, 0, 0, 0) {
+                    continue;
+                }
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO class_stats (log_id, steam_id, class, kills, assists, deaths, damage)
+                        VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                    )?
+                    .execute(params![log_id, steam_id, class.as_str(), row.0, row.1, row.2, row.3])?;
+            }
+
+            for (target, healing) in stats.heal_spread.iter() {
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO heal_spread (log_id, healer, target, healing) VALUES (?1, ?2, ?3, ?4)",
+                    )?
+                    .execute(params![log_id, steam_id, target.0.steam3(), healing])?;
+            }
+        }
+
+        for (index, message) in global.log.chat.iter().enumerate() {
+            transaction
+                .prepare_cached(
+                    "INSERT INTO chat (log_id, idx, time, steam_id, name, message, team_only)
+                    VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7)",
+                )?
+                .execute(params![
+                    log_id,
+                    index,
+                    message.time,
+                    message.steam_id.steam3(),
+                    message.name,
+                    message.message,
+                    matches!(message.chat_type, ChatType::Team),
+                ])?;
+        }
+
+        transaction.commit()?;
+        Ok(Import::Imported(log_id))
+    }
+}
+
+/// Inserts the events of a log and the rounds derived from them, while keeping track of the map and time span
+///
+/// Times are in seconds since the first event of the log, events that fail to parse are skipped.
+struct EventImporter {
+    log_id: i64,
+    start: Option<NaiveDateTime>,
+    end: Option<NaiveDateTime>,
+    map: Option<String>,
+    index: usize,
+    round: u32,
+}
+
+impl EventImporter {
+    fn new(log_id: i64) -> Self {
+        EventImporter {
+            log_id,
+            start: None,
+            end: None,
+            map: None,
+            index: 0,
+            round: 0,
+        }
+    }
+
+    fn handle(&mut self, transaction: &Transaction, line: &RawLine) -> Result<(), Error> {
+        let log_id = self.log_id;
+        let Ok(raw) = line.parse() else {
+            return Ok(());
+        };
+        let Ok(date) = NaiveDateTime::try_from(raw.date) else {
+            return Ok(());
+        };
+        let time = (date - *self.start.get_or_insert(date)).num_seconds();
+        self.end = Some(date);
+        if self.map.is_none() {
+            self.map =
+                announced_map(&raw).or_else(|| map_from_line(line.as_str()).map(String::from));
+        }
+
+        if !GameEvent::is_supported(raw.ty) {
+            return Ok(());
+        }
+        let Ok(event) = GameEvent::parse(&raw) else {
+            return Ok(());
+        };
+
+        match &event {
+            GameEvent::RoundStart => {
+                self.round += 1;
+                transaction
+                    .prepare_cached(
+                        "INSERT INTO rounds (log_id, round, start) VALUES (?1, ?2, ?3)",
+                    )?
+                    .execute(params![log_id, self.round, time])?;
+            }
+            GameEvent::RoundWin(win) => {
+                transaction
+                    .prepare_cached(
+                        "UPDATE rounds SET winner = ?3 WHERE log_id = ?1 AND round = ?2",
+                    )?
+                    .execute(params![log_id, self.round, win.team])?;
+            }
+            GameEvent::RoundLength(length) => {
+                transaction
+                    .prepare_cached(
+                        "UPDATE rounds SET length = ?3 WHERE log_id = ?1 AND round = ?2",
+                    )?
+                    .execute(params![log_id, self.round, length.length])?;
+            }
+            _ => {}
+        }
+
+        let Ok(event) = event.into_owned() else {
+            return Ok(());
+        };
+        let steam_id = raw
+            .subject
+            .id()
+            .ok()
+            .and_then(|id| id.steam_id())
+            .map(|steam_id| steam_id.steam3());
+        let data = serde_json::to_string(&event).map_err(Error::Json)?;
+        transaction
+            .prepare_cached(
+                "INSERT INTO events (log_id, idx, time, type, steam_id, data) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+            )?
+            .execute(params![log_id, self.index, time, format!("{:?}", raw.ty), steam_id, data])?;
+        self.index += 1;
+        Ok(())
+    }
+}
+
+#[test]
+fn test_import() {
+    let log = crate::read_log_bytes("tests/data/log_2892242.log.gz").expect("failed to read");
+    let (_, players) = crate::parse_bytes(&log).unwrap();
+
+    let mut database = Database::open_in_memory().unwrap();
+    let import = database.import("2892242", &log).unwrap();
+    assert!(matches!(import, Import::Imported(_)));
+    assert_eq!(
+        Import::Existing(import.id()),
+        database.import("again", &log).unwrap()
+    );
+
+    let count = |table: &str| -> i64 {
+        database
+            .connection()
+            .query_row(&format!("SELECT COUNT(*) FROM {}", table), [], |row| {
+                row.get(0)
+            })
+            .unwrap()
+    };
+    assert_eq!(1, count("logs"));
+    assert_eq!(players.len() as i64, count("player_stats"));
+    assert!(count("class_stats") > 0);
+    assert!(count("heal_spread") > 0);
+    assert!(count("chat") > 0);
+    assert!(count("rounds") > 0);
+    assert!(count("events") > 0);
+
+    let map: Option<String> = database
+        .connection()
+        .query_row("SELECT map FROM logs", [], |row| row.get(0))
+        .unwrap();
+    assert_eq!(Some("cp_gullywash_final1"), map.as_deref());
+    let segment = &crate::segment_matches(&String::from_utf8_lossy(&log))[0];
+    let (date, duration): (String, i64) = database
+        .connection()
+        .query_row("SELECT date, duration FROM logs", [], |row| {
+            Ok((row.get(0)?, row.get(1)?))
+        })
+        .unwrap();
+    assert_eq!(segment.start.format("%Y-%m-%d %H:%M:%S").to_string(), date);
+    assert_eq!((segment.end - segment.start).num_seconds(), duration);
+
+    let (steam_id, player) = players.iter().next().unwrap();
+    let damage: u32 = database
+        .connection()
+        .query_row(
+            "SELECT SUM(damage) FROM class_stats WHERE steam_id = ?1",
+            [steam_id.0.steam3()],
+            |row| row.get(0),
+        )
+        .unwrap();
+    assert_eq!(player.class_stats.damage.total(), damage);
+}
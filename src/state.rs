@@ -0,0 +1,145 @@
+use crate::RawEventType;
+use serde::Serialize;
+
+/// The state of the match at the time of an event
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum MatchState {
+    /// Before the first round starts, this includes any warmup before tournament mode is started
+    #[default]
+    Pregame,
+    /// During a round
+    InRound,
+    /// After a round has been won, before the next round starts
+    Humiliation,
+    /// The game is paused
+    Paused,
+    /// After the game is over
+    Postgame,
+}
+
+impl MatchState {
+    /// Whether the match is being played
+    pub fn is_live(self) -> bool {
+        matches!(self, MatchState::InRound)
+    }
+}
+
+/// Keeps track of the state of the match based on the events in the log
+#[derive(Debug, Default, Clone)]
+pub struct MatchStateTracker {
+    state: MatchState,
+    paused_from: MatchState,
+}
+
+impl MatchStateTracker {
+    pub fn state(&self) -> MatchState {
+        self.state
+    }
+
+    /// Update the state for the next event, returning the state the event happened in
+    ///
+    /// Events that start a round are considered part of the round, events that end a round are
+    /// considered the last event of the round.
+    pub fn handle(&mut self, ty: RawEventType) -> MatchState {
+        match ty {
+            RawEventType::RoundStart | RawEventType::MiniRoundStart => {
+                self.state = MatchState::InRound;
+                self.state
+            }
+            RawEventType::TournamentModeStarted if self.state != MatchState::InRound => {
+                self.state = MatchState::Pregame;
+                self.state
+            }
+            RawEventType::RoundWin | RawEventType::MiniRoundWin
+                if self.state != MatchState::Postgame =>
+            {
+                let state = self.state;
+                self.state = MatchState::Humiliation;
+                state
+            }
+            RawEventType::GameOver => {
+                self.state = MatchState::Postgame;
+                self.state
+            }
+            RawEventType::Paused if self.state != MatchState::Paused => {
+                self.paused_from = self.state;
+                self.state = MatchState::Paused;
+                self.state
+            }
+            RawEventType::UnPaused if self.state == MatchState::Paused => {
+                self.state = self.paused_from;
+                MatchState::Paused
+            }
+            // logs don't always contain the unpause event, nothing happens while the game is paused
+            // so gameplay events mean that the game was unpaused
+            ty if self.state == MatchState::Paused && is_gameplay(ty) => {
+                self.state = self.paused_from;
+                self.state
+            }
+            _ => self.state,
+        }
+    }
+}
+
+fn is_gameplay(ty: RawEventType) -> bool {
+    matches!(
+        ty,
+        RawEventType::ShotFired
+            | RawEventType::ShotHit
+            | RawEventType::Damage
+            | RawEventType::Healed
+            | RawEventType::Killed
+            | RawEventType::KillAssist
+            | RawEventType::ChargeDeployed
+            | RawEventType::PointCaptured
+            | RawEventType::CaptureBlocked
+    )
+}
+
+#[test]
+fn test_match_state() {
+    let mut tracker = MatchStateTracker::default();
+    let states: Vec<_> = [
+        RawEventType::Killed,
+        RawEventType::RoundStart,
+        RawEventType::TournamentModeStarted,
+        RawEventType::Killed,
+        RawEventType::Paused,
+        RawEventType::Say,
+        RawEventType::UnPaused,
+        RawEventType::Killed,
+        RawEventType::Paused,
+        RawEventType::Damage,
+        RawEventType::RoundWin,
+        RawEventType::Killed,
+        RawEventType::RoundStart,
+        RawEventType::GameOver,
+        RawEventType::RoundWin,
+        RawEventType::Say,
+    ]
+    .iter()
+    .map(|ty| tracker.handle(*ty))
+    .collect();
+    assert_eq!(
+        vec![
+            MatchState::Pregame,
+            MatchState::InRound,
+            MatchState::InRound,
+            MatchState::InRound,
+            MatchState::Paused,
+            MatchState::Paused,
+            MatchState::Paused,
+            MatchState::InRound,
+            MatchState::Paused,
+            MatchState::InRound,
+            MatchState::InRound,
+            MatchState::Humiliation,
+            MatchState::InRound,
+            MatchState::Postgame,
+            MatchState::Postgame,
+            MatchState::Postgame,
+        ],
+        states
+    );
+}
@@ -0,0 +1,300 @@
+use crate::parser::LogParser;
+use crate::{Error, EventHandler, HandlerOutput, LineLocation, ParseOptions, Result};
+use std::io::{self, BufRead};
+use std::mem::take;
+
+/// Assembles lines read from a stream into log lines
+///
+/// Lines that don't start with `L ` are a continuation of the previous log line,
+/// giving the same result as [`LineSplit`](crate::LineSplit).
+#[derive(Default)]
+pub(crate) struct LineBuffer {
+    current: Option<(String, LineLocation)>,
+    started: bool,
+    line: usize,
+    offset: usize,
+}
+
+impl LineBuffer {
+    /// Add the next line of the input, including the trailing newline.
+    ///
+    /// Returns the previous log line if it is complete
+    pub(crate) fn push(&mut self, raw: &[u8]) -> Option<(String, LineLocation)> {
+        let start = self.offset;
+        let end = start + raw.len() - raw.ends_with(b"\n") as usize;
+        self.offset += raw.len();
+        self.line += 1;
+
+        let text = String::from_utf8_lossy(raw);
+        let text = text.strip_suffix('\n').unwrap_or(&text);
+
+        if !self.started {
+            // skip anything before the first delimiter, including any byte order mark
+            let skip = text.find("L ")?;
+            self.started = true;
+            self.current = Some((
+                text[skip + 2..].to_string(),
+                LineLocation {
+                    line: self.line,
+                    span: start + skip..end,
+                },
+            ));
+            return None;
+        }
+
+        match text.strip_prefix("L ") {
+            Some(text) => self.current.replace((
+                text.to_string(),
+                LineLocation {
+                    line: self.line,
+                    span: start..end,
+                },
+            )),
+            None => {
+                if let Some((current, location)) = &mut self.current {
+                    current.push('\n');
+                    current.push_str(text);
+                    location.span.end = end;
+                }
+                None
+            }
+        }
+    }
+
+    /// Advance past a complete log line that was provided separately
+    fn skip(&mut self, line: &str) -> LineLocation {
+        let start = self.offset;
+        // account for the leading `L ` and trailing newline
+        self.offset += line.len() + 3;
+        self.line += 1;
+        let location = LineLocation {
+            line: self.line,
+            span: start..start + line.len() + 2,
+        };
+        self.line += memchr::memchr_iter(b'\n', line.as_bytes()).count();
+        self.started = true;
+        location
+    }
+
+    /// Get the last log line at the end of the input
+    pub(crate) fn flush(&mut self) -> Option<(String, LineLocation)> {
+        self.current.take().map(|(mut line, location)| {
+            line.truncate(line.trim_end_matches('\n').len());
+            (line, location)
+        })
+    }
+}
+
+/// Read the lines of a log from a [`BufRead`]
+///
+/// This yields the same lines as [`LineSplit`](crate::LineSplit) without requiring the full log to be in memory,
+/// any invalid utf8 is replaced per line.
+pub struct LineReader<R> {
+    reader: R,
+    buffer: LineBuffer,
+    raw: Vec<u8>,
+    location: Option<LineLocation>,
+}
+
+impl<R: BufRead> LineReader<R> {
+    pub fn new(reader: R) -> Self {
+        LineReader {
+            reader,
+            buffer: LineBuffer::default(),
+            raw: Vec::with_capacity(256),
+            location: None,
+        }
+    }
+
+    /// Get the line number and byte span of the line that was last returned
+    pub fn location(&self) -> Option<&LineLocation> {
+        self.location.as_ref()
+    }
+}
+
+impl<R: BufRead> Iterator for LineReader<R> {
+    type Item = io::Result<String>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            self.raw.clear();
+            let line = match self.reader.read_until(b'\n', &mut self.raw) {
+                Ok(0) => self.buffer.flush(),
+                Ok(_) => match self.buffer.push(&self.raw) {
+                    Some(line) => Some(line),
+                    None => continue,
+                },
+                Err(e) => return Some(Err(e)),
+            };
+            return line.map(|(line, location)| {
+                self.location = Some(location);
+                Ok(line)
+            });
+        }
+    }
+}
+
+/// Parser that processes the log incrementally as it's being read
+///
+/// Unlike [`parse_with_options`](crate::parse_with_options), the streaming parser can't look ahead in the log,
+/// so events before the time origin all happen at time 0 even if the origin is never reached, and with
+/// `skip_warmup` all events are skipped until tournament mode is started.
+pub struct StreamParser<Handler: EventHandler> {
+    lines: LineBuffer,
+    partial: Vec<u8>,
+    parser: LogParser<Handler>,
+}
+
+impl<Handler: EventHandler> StreamParser<Handler> {
+    pub fn new(options: &ParseOptions) -> Self {
+        StreamParser {
+            lines: LineBuffer::default(),
+            partial: Vec::new(),
+            parser: LogParser::new(options),
+        }
+    }
+
+    /// Process the next chunk of the log
+    ///
+    /// An incomplete line at the end of the chunk is kept until the rest of the line is pushed.
+    pub fn push(&mut self, data: &[u8]) -> Result<()> {
+        for chunk in data.split_inclusive(|byte| *byte == b'\n') {
+            if !chunk.ends_with(b"\n") {
+                self.partial.extend_from_slice(chunk);
+            } else if self.partial.is_empty() {
+                self.push_raw_line(chunk)?;
+            } else {
+                self.partial.extend_from_slice(chunk);
+                let line = take(&mut self.partial);
+                self.push_raw_line(&line)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Read and process all data that is currently available from the reader
+    ///
+    /// This can be called repeatedly for a log file that is still being written to.
+    /// Returns the number of bytes read.
+    pub fn read_from<R: BufRead>(&mut self, reader: &mut R) -> Result<usize> {
+        let mut total = 0;
+        loop {
+            let data = reader.fill_buf()?;
+            let len = data.len();
+            if len == 0 {
+                return Ok(total);
+            }
+            let result = self.push(data);
+            reader.consume(len);
+            total += len;
+            result?;
+        }
+    }
+
+    /// Process a single complete log line, without the leading `L `
+    ///
+    /// This is intended for sources that deliver the log one line at a time, like the udp log protocol.
+    pub fn push_line(&mut self, line: &str) -> Result<()> {
+        if let Some((previous, location)) = self.lines.flush() {
+            self.handle_line(&previous, location)?;
+        }
+        let location = self.lines.skip(line);
+        self.handle_line(line, location)
+    }
+
+    fn push_raw_line(&mut self, raw: &[u8]) -> Result<()> {
+        match self.lines.push(raw) {
+            Some((line, location)) => self.handle_line(&line, location),
+            None => Ok(()),
+        }
+    }
+
+    fn handle_line(&mut self, line: &str, location: LineLocation) -> Result<()> {
+        self.parser.handle_line(line, |e: Error, line| {
+            e.located_at(&format!("L {}", line), location.clone())
+        })
+    }
+
+    /// Get the output for the log read so far
+    pub fn finish(mut self) -> Result<HandlerOutput<Handler>> {
+        let partial = take(&mut self.partial);
+        if !partial.is_empty() {
+            self.push_raw_line(&partial)?;
+        }
+        if let Some((line, location)) = self.lines.flush() {
+            self.handle_line(&line, location)?;
+        }
+        Ok(self.parser.finish())
+    }
+}
+
+impl<Handler: EventHandler + Clone> StreamParser<Handler>
+where
+    Handler::PerSubjectData: Clone,
+{
+    /// Get the output for the log read so far, without consuming the parser
+    ///
+    /// Lines pushed with [`push`](Self::push) are only included once the next line starts,
+    /// since a log line can continue on the next line.
+    pub fn snapshot(&self) -> HandlerOutput<Handler> {
+        self.parser.snapshot()
+    }
+}
+
+/// Parse a log from a reader without loading the full log into memory
+pub fn parse_reader<Handler: EventHandler, R: BufRead>(
+    mut reader: R,
+    options: &ParseOptions,
+) -> Result<HandlerOutput<Handler>> {
+    let mut parser = StreamParser::<Handler>::new(options);
+    parser.read_from(&mut reader)?;
+    parser.finish()
+}
+
+#[test]
+fn test_line_reader() {
+    use crate::LineSplit;
+    use std::io::BufReader;
+
+    for name in ["log_2892242.log", "log_2788889.log", "log_hl.log"] {
+        let input = crate::read_log(format!("tests/data/{}.gz", name)).expect("failed to read");
+
+        let expected: Vec<_> = LineSplit::new(&input).collect();
+        let mut reader = LineReader::new(BufReader::new(input.as_bytes()));
+        let mut lines = Vec::new();
+        while let Some(line) = reader.next() {
+            let line = line.unwrap();
+            let location = reader.location().unwrap();
+            assert_eq!(format!("L {}", line), input[location.span.clone()]);
+            lines.push(line);
+        }
+        assert_eq!(expected, lines);
+    }
+}
+
+#[test]
+fn test_stream_parse() {
+    use crate::{parse, LogHandler};
+
+    let input = crate::read_log("tests/data/log_6s.log.gz").expect("failed to read");
+
+    let expected = parse(&input).unwrap();
+
+    // feed the log in small chunks to make sure we handle lines being split between chunks
+    let mut parser = StreamParser::<LogHandler>::new(&ParseOptions::default());
+    for chunk in input.as_bytes().chunks(100) {
+        parser.push(chunk).unwrap();
+    }
+    let snapshot = parser.snapshot();
+    let streamed = parser.finish().unwrap();
+
+    assert_eq!(
+        serde_json::to_value(&expected).unwrap(),
+        serde_json::to_value(&streamed).unwrap()
+    );
+    // only the last line isn't in the snapshot yet
+    assert_eq!(
+        serde_json::to_value(&expected).unwrap(),
+        serde_json::to_value(&snapshot).unwrap()
+    );
+}
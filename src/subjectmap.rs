@@ -4,7 +4,7 @@ use ahash::{AHashMap, RandomState};
 use std::convert::TryInto;
 use std::ops::{Index, IndexMut};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct SubjectMap<T = ()>(AHashMap<SubjectId, (SubjectData, T)>);
 
 impl<T> SubjectMap<T> {
@@ -37,11 +37,16 @@ impl<T> IndexMut<SubjectId> for SubjectMap<T> {
 impl<T: Default> SubjectMap<T> {
     pub fn insert(&mut self, raw: &RawSubject) -> Result<(SubjectId, &mut T), SubjectError> {
         let id = raw.id()?;
+        Ok((id, self.insert_with_id(id, raw)))
+    }
+
+    /// Insert a subject for which the id is already known
+    pub(crate) fn insert_with_id(&mut self, id: SubjectId, raw: &RawSubject) -> &mut T {
         let (_, data) = self
             .0
             .entry(id)
             .or_insert_with(|| (raw.try_into().unwrap(), T::default()));
-        Ok((id, data))
+        data
     }
 }
 
@@ -0,0 +1,425 @@
+//! Render events back into the log format
+
+use crate::event::*;
+use crate::raw_event::RawSubject;
+use crate::{GameEvent, RawEvent, RawEventType, Result};
+use chrono::NaiveDateTime;
+use std::borrow::Cow;
+use std::convert::TryInto;
+use std::fmt::{self, Display, Formatter, Write as _};
+use std::io::Write;
+
+/// Writes events as log lines
+///
+/// Raw events are written with their parameters as-is, game events are rendered with only the parameters
+/// that are parsed into the event.
+pub struct LogWriter<W> {
+    writer: W,
+    line: String,
+}
+
+impl<W: Write> LogWriter<W> {
+    pub fn new(writer: W) -> Self {
+        LogWriter {
+            writer,
+            line: String::with_capacity(256),
+        }
+    }
+
+    pub fn into_inner(self) -> W {
+        self.writer
+    }
+
+    pub fn write_raw(&mut self, event: &RawEvent) -> Result<()> {
+        let date: NaiveDateTime = event.date.try_into()?;
+        self.write_line(date, &event.subject, event.ty, event.params)
+    }
+
+    pub fn write_event(
+        &mut self,
+        date: NaiveDateTime,
+        subject: &RawSubject,
+        event: &GameEvent,
+    ) -> Result<()> {
+        let mut params = String::new();
+        write_params(&mut params, event);
+        let params = params.strip_prefix(' ').unwrap_or(&params);
+        self.write_line(date, subject, event.ty(), params)
+    }
+
+    fn write_line(
+        &mut self,
+        date: NaiveDateTime,
+        subject: &RawSubject,
+        ty: RawEventType,
+        params: &str,
+    ) -> Result<()> {
+        let line = &mut self.line;
+        line.clear();
+        let _ = write!(line, "L {}: ", date.format("%m/%d/%Y - %H:%M:%S"));
+        match subject {
+            RawSubject::Player(_) | RawSubject::Console => {
+                let _ = write!(line, "\"{}\"", subject_text(subject));
+            }
+            RawSubject::Team(team) => {
+                let _ = write!(line, "Team \"{}\"", team.as_str());
+            }
+            RawSubject::System(name) => line.push_str(name),
+            RawSubject::World => line.push_str("World"),
+        }
+        let token = event_token(ty);
+        if !token.is_empty() {
+            line.push(' ');
+            line.push_str(token);
+        }
+        if !params.is_empty() {
+            // "Log file closed." and "Tournament mode started\n..." have no space before the parameters
+            if !token.ends_with(' ') && !params.starts_with(&['\n', '.'][..]) {
+                line.push(' ');
+            }
+            line.push_str(params);
+        }
+        line.push('\n');
+        self.writer.write_all(line.as_bytes())?;
+        Ok(())
+    }
+}
+
+/// The text of a subject as it appears inside quotes
+fn subject_text<'a>(subject: &RawSubject<'a>) -> Cow<'a, str> {
+    match subject {
+        RawSubject::Player(player) => Cow::Borrowed(player),
+        RawSubject::Team(team) => Cow::Borrowed(team.as_str()),
+        RawSubject::System(name) => Cow::Borrowed(name),
+        RawSubject::Console => Cow::Borrowed("Console<0><Console><Console>"),
+        RawSubject::World => Cow::Borrowed("World"),
+    }
+}
+
+struct Position((i32, i32, i32));
+
+impl Display for Position {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        let (x, y, z) = self.0;
+        write!(f, "{} {} {}", x, y, z)
+    }
+}
+
+/// ` "value"`
+fn quoted<T: Display>(out: &mut String, value: T) {
+    let _ = write!(out, " \"{}\"", value);
+}
+
+/// ` key "value"`
+fn named<T: Display>(out: &mut String, key: &str, value: T) {
+    let _ = write!(out, " {} \"{}\"", key, value);
+}
+
+/// ` (key "value")`
+fn param<T: Display>(out: &mut String, key: &str, value: Option<T>) {
+    if let Some(value) = value {
+        let _ = write!(out, " ({} \"{}\")", key, value);
+    }
+}
+
+fn position(out: &mut String, key: &str, value: Option<(i32, i32, i32)>) {
+    param(out, key, value.map(Position));
+}
+
+fn write_params(out: &mut String, event: &GameEvent) {
+    match event {
+        GameEvent::ShotFired(ShotFiredEvent { weapon })
+        | GameEvent::ShotHit(ShotHitEvent { weapon }) => param(out, "weapon", *weapon),
+        GameEvent::Damage(event) => {
+            named(out, "against", subject_text(&event.target));
+            param(out, "damage", event.damage);
+            param(out, "realdamage", event.real_damage);
+            param(out, "weapon", event.weapon);
+        }
+        GameEvent::Killed(event) => {
+            quoted(out, subject_text(&event.target));
+            named(out, "with", event.weapon);
+            position(out, "attacker_position", event.attacker_position);
+            position(out, "victim_position", event.victim_position);
+        }
+        GameEvent::KillAssist(event) => {
+            named(out, "against", subject_text(&event.target));
+            position(out, "attacker_position", event.attacker_position);
+            position(out, "victim_position", event.victim_position);
+        }
+        GameEvent::Say(SayEvent { text }) | GameEvent::SayTeam(SayTeamEvent { text }) => {
+            quoted(out, text)
+        }
+        GameEvent::Healed(event) => {
+            if let Some(target) = &event.target {
+                named(out, "against", subject_text(target));
+            }
+            param(out, "healing", Some(event.amount));
+        }
+        GameEvent::ChargeDeployed(event) => param(out, "medigun", event.medigun),
+        GameEvent::ChargeEnded(event) => param(out, "duration", event.duration),
+        GameEvent::AdvantageLost(event) => param(out, "time", event.time),
+        GameEvent::FirstHeal(event) => param(out, "time", event.time),
+        GameEvent::MedicDeath(event) => param(out, "ubercharge", event.charge),
+        GameEvent::MedicDeathEx(event) => param(out, "charge_percentage", event.charge_percentage),
+        GameEvent::Spawned(event) => {
+            if let Some(class) = event.class {
+                named(out, "as", class.as_str());
+            }
+        }
+        GameEvent::RoleChange(event) => {
+            if let Some(class) = event.class {
+                named(out, "to", class.as_str());
+            }
+        }
+        GameEvent::RoundWin(event) => param(out, "winner", event.team),
+        GameEvent::RoundLength(event) => param(out, "seconds", event.length),
+        GameEvent::LogFileStarted(event) => {
+            param(out, "file", event.file);
+            param(out, "game", event.game);
+            param(out, "version", event.version);
+        }
+        GameEvent::Connected(event) => named(out, "address", event.address),
+        GameEvent::Disconnect(event) => param(out, "reason", event.reason),
+        GameEvent::Joined(event) => named(out, "team", event.team.as_str()),
+        GameEvent::Suicide(event) => {
+            named(out, "with", event.weapon);
+            position(out, "attacker_position", event.attacker_position);
+        }
+        GameEvent::PickedUp(event) => named(out, "item", event.item),
+        GameEvent::Domination(DominationEvent { against })
+        | GameEvent::Revenge(RevengeEvent { against }) => {
+            named(out, "against", subject_text(against))
+        }
+        GameEvent::TournamentModeStarted(event) => {
+            let _ = write!(out, "\nBlue Team: {}\nRed Team: {}", event.blue, event.red);
+        }
+        GameEvent::CaptureBlocked(event) => {
+            param(out, "cp", event.cp);
+            param(out, "cpname", event.cp_name);
+            position(out, "position", event.position);
+        }
+        GameEvent::PointCaptured(event) => {
+            param(out, "cp", event.cp);
+            param(out, "cpname", event.cp_name);
+            param(out, "numcappers", event.num_cappers);
+            for (i, (player, pos)) in event.players.iter().enumerate() {
+                param(out, &format!("player{}", i + 1), Some(subject_text(player)));
+                position(out, &format!("position{}", i + 1), Some(*pos));
+            }
+        }
+        GameEvent::CurrentScore(CurrentScoreEvent { score, players })
+        | GameEvent::FinalScore(FinalScoreEvent { score, players }) => {
+            named(out, "score", score);
+            named(out, "with", players);
+            out.push_str(" players");
+        }
+        GameEvent::BuiltObject(BuiltObjectEvent {
+            object,
+            position: pos,
+        })
+        | GameEvent::DropObject(DropObjectEvent {
+            object,
+            position: pos,
+        })
+        | GameEvent::CarryObject(BuiltCarryEvent {
+            object,
+            position: pos,
+        }) => {
+            param(out, "object", *object);
+            position(out, "position", *pos);
+        }
+        GameEvent::KilledObject(event) => {
+            param(out, "object", event.object);
+            param(out, "weapon", event.weapon);
+            param(
+                out,
+                "objectowner",
+                event.object_owner.as_ref().map(subject_text),
+            );
+            position(out, "attacker_position", event.attacker_position);
+        }
+        GameEvent::Extinguished(event) => {
+            named(out, "against", subject_text(&event.against));
+            named(out, "with", event.with);
+            position(out, "attacker_position", event.attacker_position);
+            position(out, "victim_position", event.victim_position);
+        }
+        GameEvent::GameOver(event) => named(out, "reason", event.reason),
+        GameEvent::ObjectDetonated(event) => {
+            param(out, "object", event.object);
+            position(out, "attacker_position", event.position);
+        }
+        GameEvent::Request(UnparsedEvent { params })
+        | GameEvent::Response(UnparsedEvent { params }) => {
+            out.push(' ');
+            out.push_str(params);
+        }
+        GameEvent::LogFileClosed => out.push('.'),
+        GameEvent::ChargeReady
+        | GameEvent::RoundStart
+        | GameEvent::RoundOverTime
+        | GameEvent::SteamIdValidated
+        | GameEvent::Entered
+        | GameEvent::EmptyUber => {}
+    }
+}
+
+/// The text identifying the type of event, following the subject
+fn event_token(ty: RawEventType) -> &'static str {
+    match ty {
+        RawEventType::Joined => "joined ",
+        RawEventType::RoleChange => "changed role ",
+        RawEventType::ShotFired => r#"triggered "shot_fired""#,
+        RawEventType::ShotHit => r#"triggered "shot_hit""#,
+        RawEventType::Damage => r#"triggered "damage""#,
+        RawEventType::Healed => r#"triggered "healed""#,
+        RawEventType::FirstHeal => r#"triggered "first_heal_after_spawn""#,
+        RawEventType::Killed => "killed ",
+        RawEventType::KillAssist => r#"triggered "kill assist""#,
+        RawEventType::Suicide => "committed suicide ",
+        RawEventType::Domination => r#"triggered "domination""#,
+        RawEventType::Revenge => r#"triggered "revenge""#,
+        RawEventType::Spawned => "spawned ",
+        RawEventType::SayTeam => "say_team ",
+        RawEventType::Say => "say ",
+        RawEventType::EmptyUber => r#"triggered "empty_uber""#,
+        RawEventType::BuiltObject => r#"triggered "player_builtobject""#,
+        RawEventType::CarryObject => r#"triggered "player_dropobject""#,
+        RawEventType::DropObject => r#"triggered "player_carryobject""#,
+        RawEventType::RocketJump => r#"triggered "rocket_jump""#,
+        RawEventType::KilledObject => r#"triggered "killedobject""#,
+        RawEventType::ObjectDetonated => r#"triggered "object_detonated""#,
+        RawEventType::Extinguished => r#"triggered "player_extinguished""#,
+        RawEventType::PickedUp => "picked up ",
+        RawEventType::MedicDeath => r#"triggered "medic_death""#,
+        RawEventType::MedicDeathEx => r#"triggered "medic_death_ex""#,
+        RawEventType::ChargeEnded => r#"triggered "chargeended""#,
+        RawEventType::ChargeReady => r#"triggered "chargeready""#,
+        RawEventType::ChargeDeployed => r#"triggered "chargedeployed""#,
+        RawEventType::AdvantageLost => r#"triggered "lost_uber_advantage""#,
+        RawEventType::RoundStart => r#"triggered "Round_Start""#,
+        RawEventType::RoundSetupBegin => r#"triggered "Round_Setup_Begin""#,
+        RawEventType::RoundSetupEnd => r#"triggered "Round_Setup_End""#,
+        RawEventType::MiniRoundSelected => r#"triggered "Mini_Round_Selected""#,
+        RawEventType::MiniRoundStart => r#"triggered "Mini_Round_Start""#,
+        RawEventType::RoundWin => r#"triggered "Round_Win""#,
+        RawEventType::MiniRoundWin => r#"triggered "Mini_Round_Win""#,
+        RawEventType::RoundLength => r#"triggered "Round_Length""#,
+        RawEventType::MiniRoundLength => r#"triggered "Mini_Round_Length""#,
+        RawEventType::RoundOverTime => r#"triggered "Round_Overtime""#,
+        RawEventType::PointCaptured => r#"triggered "pointcaptured""#,
+        RawEventType::CaptureBlocked => r#"triggered "captureblocked""#,
+        RawEventType::GameOver => r#"triggered "Game_Over""#,
+        RawEventType::CurrentScore => "current ",
+        RawEventType::FinalScore => "final ",
+        RawEventType::WinLimit => r#"triggered "Intermission_Win_Limit""#,
+        RawEventType::Paused => r#"triggered "Game_Paused""#,
+        RawEventType::UnPaused => r#"triggered "Game_Unpaused""#,
+        RawEventType::Request => "Request:  ",
+        RawEventType::Response => "Response:  ",
+        RawEventType::Connected => "connected, ",
+        RawEventType::Disconnect => "disconnected ",
+        RawEventType::SteamIdValidated => "STEAM USERID validated",
+        RawEventType::Entered => "entered the game",
+        RawEventType::LogFileStarted => "file started ",
+        RawEventType::LogFileClosed => "file closed",
+        RawEventType::NotUploaded => "The log might have not been uploaded. ",
+        RawEventType::TournamentModeStarted => "mode started",
+        RawEventType::FlagEvent => r#"triggered "flagevent""#,
+        RawEventType::CVars => "cvars ",
+        RawEventType::Unknown => "",
+    }
+}
+
+/// Writes every line of the test logs back and compares it with the original line
+#[test]
+fn test_round_trip() {
+    use crate::{LineSplit, OwnedEvent};
+    use std::collections::HashSet;
+    use std::fs::read_dir;
+
+    let mut subjects = HashSet::new();
+    for entry in read_dir("tests/data").unwrap() {
+        let path = entry.unwrap().path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("gz") {
+            continue;
+        }
+        let input = crate::read_log(&path).expect("failed to read");
+
+        for line in LineSplit::new(&input) {
+            let raw = match RawEvent::parse(line) {
+                Ok(raw) if raw.ty != RawEventType::Unknown => raw,
+                _ => continue,
+            };
+
+            subjects.insert(match raw.subject {
+                RawSubject::Player(_) => "player",
+                RawSubject::Team(_) => "team",
+                RawSubject::System(_) => "system",
+                RawSubject::Console => "console",
+                RawSubject::World => "world",
+            });
+
+            let mut writer = LogWriter::new(Vec::new());
+            writer.write_raw(&raw).unwrap();
+            let written = String::from_utf8(writer.into_inner()).unwrap();
+            let written = written
+                .strip_prefix("L ")
+                .unwrap()
+                .strip_suffix('\n')
+                .unwrap();
+            if let RawSubject::Team(_) = raw.subject {
+                // the case of team names isn't kept
+                assert!(
+                    line.eq_ignore_ascii_case(written),
+                    "{} rendered as {} in {}",
+                    line,
+                    written,
+                    path.display()
+                );
+            } else {
+                assert_eq!(line, written, "in {}", path.display());
+            }
+            assert_eq!(raw, RawEvent::parse(written).unwrap());
+
+            // the world is parsed as a system subject
+            if raw.subject == RawSubject::System("World") {
+                let world = RawEvent {
+                    subject: RawSubject::World,
+                    ..raw
+                };
+                let mut writer = LogWriter::new(Vec::new());
+                writer.write_raw(&world).unwrap();
+                let written = String::from_utf8(writer.into_inner()).unwrap();
+                assert_eq!(format!("L {}\n", line), written);
+                subjects.insert("world");
+            }
+
+            if !GameEvent::is_supported(raw.ty) {
+                continue;
+            }
+            let event = match GameEvent::parse(&raw) {
+                Ok(event) => event,
+                Err(_) => continue,
+            };
+            let mut writer = LogWriter::new(Vec::new());
+            writer
+                .write_event(raw.date.try_into().unwrap(), &raw.subject, &event)
+                .unwrap();
+            let written = String::from_utf8(writer.into_inner()).unwrap();
+            let written = written.strip_prefix("L ").unwrap().trim_end_matches('\n');
+            let rendered = RawEvent::parse(written).unwrap();
+            assert_eq!(
+                OwnedEvent::new(&raw, event).unwrap(),
+                OwnedEvent::parse(&rendered).unwrap(),
+                "{} rendered as {}",
+                line,
+                written
+            );
+        }
+    }
+
+    // the test logs contain every kind of subject
+    assert_eq!(5, subjects.len(), "{:?}", subjects);
+}
@@ -10,12 +10,18 @@ use tf_log_parser::{LineSplit, RawEvent};
 #[test_case("log_bball.log")]
 #[test_case("log_hl.log")]
 fn smoke_test(name: &str) {
+    let content = read_fixture(name);
+    for line in LineSplit::new(&content) {
+        RawEvent::parse(line).expect("failed to parse raw event");
+    }
+}
+
+/// Read a gzip compressed test log, without depending on the `gzip` feature
+fn read_fixture(name: &str) -> String {
     let path = format!("tests/data/{}.gz", name);
     let mut content = String::new();
     GzDecoder::new(File::open(path).expect("failed to open"))
         .read_to_string(&mut content)
         .expect("failed to read");
-    for line in LineSplit::new(&content) {
-        RawEvent::parse(line).expect("failed to parse raw event");
-    }
+    content
 }
@@ -1,19 +1,19 @@
 use flate2::read::GzDecoder;
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use std::collections::BTreeMap;
 use std::fs::File;
 use std::io::Read;
 use test_case::test_case;
 use tf_log_parser::module::{ClassStats, MedicStats};
-use tf_log_parser::{parse, EventHandler, LogHandler, LogHandlerPerSubjectOutput};
+use tf_log_parser::{parse, EventHandler, HandlerOutput, LogHandler, LogHandlerPerSubjectOutput};
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, PartialEq)]
 struct LogResult {
     global: <LogHandler as EventHandler>::GlobalOutput,
     per_player: BTreeMap<String, LogPlayerData>,
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, PartialEq)]
 struct LogPlayerData {
     stats: ClassStatsRaw,
     heals: BTreeMap<String, u32>,
@@ -34,12 +34,12 @@ impl From<LogHandlerPerSubjectOutput> for LogPlayerData {
     }
 }
 
-#[derive(Serialize)]
+#[derive(Serialize, Deserialize, PartialEq)]
 struct ClassStatsRaw {
-    kills: [u16; 10],
-    deaths: [u16; 10],
-    assists: [u16; 10],
-    damage: [u16; 10],
+    kills: [u32; 10],
+    deaths: [u32; 10],
+    assists: [u32; 10],
+    damage: [u32; 10],
 }
 
 impl From<ClassStats> for ClassStatsRaw {
@@ -59,18 +59,61 @@ impl From<ClassStats> for ClassStatsRaw {
 #[test_case("log_bball.log")]
 #[test_case("log_hl.log")]
 fn test_parse(name: &str) {
-    let path = format!("tests/data/{}.gz", name);
-    let mut content = String::new();
-    GzDecoder::new(File::open(path).expect("failed to open"))
-        .read_to_string(&mut content)
-        .expect("failed to read");
-    let (global, per_player) = parse(&content).unwrap();
-    let log = LogResult {
+    let content = read_fixture(name);
+    let log = log_result(parse(&content).unwrap());
+    insta::assert_json_snapshot!(format!("parse-{}", name), log);
+}
+
+fn log_result((global, per_player): HandlerOutput<LogHandler>) -> LogResult {
+    LogResult {
         global,
         per_player: per_player
             .into_iter()
             .map(|(key, value)| (key.0.steam3(), value.into()))
             .collect(),
-    };
-    insta::assert_json_snapshot!(format!("parse-{}", name), log);
+    }
+}
+
+#[test_case("log_6s.log")]
+#[test_case("log_2788889.log")]
+#[test_case("log_2892242.log")]
+#[test_case("log_bball.log")]
+#[test_case("log_hl.log")]
+fn test_deserialize_snapshot(name: &str) {
+    let content = read_fixture(name);
+    let log = log_result(parse(&content).unwrap());
+
+    let snapshot =
+        std::fs::read_to_string(format!("tests/snapshots/snapshot__parse-{}.snap", name))
+            .expect("failed to read snapshot");
+    let json = snapshot
+        .splitn(3, "---\n")
+        .nth(2)
+        .expect("invalid snapshot");
+    let snapshot: LogResult = serde_json::from_str(json).unwrap();
+    assert!(log == snapshot);
+}
+
+#[test_case("log_6s.log")]
+#[test_case("log_2788889.log")]
+#[test_case("log_2892242.log")]
+#[test_case("log_bball.log")]
+#[test_case("log_hl.log")]
+fn test_output_round_trip(name: &str) {
+    let content = read_fixture(name);
+    let output = parse(&content).unwrap();
+
+    let json = serde_json::to_string(&output).unwrap();
+    let deserialized: HandlerOutput<LogHandler> = serde_json::from_str(&json).unwrap();
+    assert!(output == deserialized);
+}
+
+/// Read a gzip compressed test log, without depending on the `gzip` feature
+fn read_fixture(name: &str) -> String {
+    let path = format!("tests/data/{}.gz", name);
+    let mut content = String::new();
+    GzDecoder::new(File::open(path).expect("failed to open"))
+        .read_to_string(&mut content)
+        .expect("failed to read");
+    content
 }